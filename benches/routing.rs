@@ -0,0 +1,89 @@
+//! Cost of an ACL packet for one of N connections, given to every
+//! connection's processors ("broadcast") or routed by connection handle
+//!
+//! Medians with `cargo bench --bench routing`, release build, one CPU:
+//!
+//! | connections | broadcast | routed   |
+//! |-------------|-----------|----------|
+//! | 1           | 439 ns    | 244 ns   |
+//! | 8           | 2.37 µs   | 338 ns   |
+//! | 32          | 7.34 µs   | 558 ns   |
+//! | 128         | 31.0 µs   | 1.25 µs  |
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+
+use bt_only_headers::atthandler::AttHandler;
+use bt_only_headers::hcimanager::{AppMsg, HciError, HciManager, MsgProcessor};
+use bt_only_headers::messages::*;
+use bt_only_headers::pairinghandler::PairingHandler;
+
+/// Gives every message to the wrapped processor, like before routing
+struct Broadcast(Box<dyn MsgProcessor>);
+
+impl MsgProcessor for Broadcast {
+    fn process(&mut self, msg: AppMsg) -> Result<Vec<AppMsg>, HciError> {
+        self.0.process(msg)
+    }
+}
+
+fn connection(handle: u16) -> LeConnectionComplete {
+    LeConnectionComplete {
+        status: HciStatus::Success,
+        connection_handle: ConnectionHandle(handle),
+        role: Role::Peripheral,
+        peer_address_type: AddressType::Public,
+        peer_address: BdAddr([38, 14, 214, 232, 194, 80]),
+        connection_interval: 48,
+        peripheral_latency: 0,
+        supervision_timeout: 960,
+        central_clock_accuracy: ClockAccuracy::Ppm250,
+    }
+}
+
+/// Manager with ATT and SMP handlers for `n` connections
+fn manager(n: u16, routed: bool) -> HciManager {
+    let mut mgr = HciManager::new().unwrap();
+    for handle in 0..n {
+        let handlers: Vec<Box<dyn MsgProcessor>> = vec![
+            Box::new(AttHandler::new(connection(handle))),
+            Box::new(PairingHandler::new(
+                connection(handle),
+                BdAddr([6, 51, 116, 214, 86, 211]),
+                AddressType::Random,
+                49055469533520638048878300062363381969,
+                282559536878159528170380446798965774951,
+                723151060346651216,
+            )),
+        ];
+        for handler in handlers {
+            if routed {
+                mgr.add_processor(handler);
+            } else {
+                mgr.add_processor(Box::new(Broadcast(handler)));
+            }
+        }
+    }
+    mgr
+}
+
+fn bench_routing(c: &mut Criterion) {
+    let msg = AppMsg::Recv(H4Packet::Acl(HciAcl {
+        connection_handle: ConnectionHandle(0),
+        bc: BroadcastFlag::PointToPoint,
+        pb: PacketBoundaryFlag::FirstNonFlushable,
+        msg: L2CapMessage::Att(AttPdu::ExchangeMtuResponse(100)),
+    }));
+    let mut group = c.benchmark_group("acl_to_one_connection");
+    for n in [1, 8, 32, 128] {
+        for (name, routed) in [("broadcast", false), ("routed", true)] {
+            let mut mgr = manager(n, routed);
+            group.bench_with_input(BenchmarkId::new(name, n), &msg, |b, msg| {
+                b.iter(|| mgr.process(msg.clone()).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_routing);
+criterion_main!(benches);
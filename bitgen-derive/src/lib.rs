@@ -0,0 +1,77 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::Item;
+
+use bitgen::describer::describer;
+use bitgen::generator::generator;
+use bitgen::implementer::implementer;
+use bitgen::validate::validate;
+
+/// Implements `FromToPacket` from the bitgen doc comment attributes (`id`,
+/// `id_type`, `bits`, `prepend_length`, `length_after_id`, ...), and
+/// `PacketIdentifier` for enums
+///
+/// Problems with the attributes are reported as compile errors, all at once.
+#[proc_macro_derive(FromToPacket)]
+pub fn derive_from_to_packet(input: TokenStream) -> TokenStream {
+    let item = syn::parse_macro_input!(input as Item);
+    let imports = match item {
+        Item::Enum(_) => quote! { FromToPacket, Packet, PacketError, PacketIdentifier, PacketVariant },
+        _ => quote! { FromToPacket, Packet, PacketError },
+    };
+    let impls = implementer(&vec![item]);
+    quote! {
+        const _: () = {
+            use ::bt_only_headers::packer::{#imports};
+            #(#impls)*
+        };
+    }
+    .into()
+}
+
+/// Implements `Describe` and `Display` from the `name`, `unit`, `scale`,
+/// `names` and `describe` doc comment attributes, and `VariantName` for enums
+#[proc_macro_derive(Describe)]
+pub fn derive_describe(input: TokenStream) -> TokenStream {
+    let item = syn::parse_macro_input!(input as Item);
+    if validate(std::slice::from_ref(&item)).is_err() {
+        // The problems are reported by `#[derive(FromToPacket)]`
+        return TokenStream::new();
+    }
+    let imports = match item {
+        Item::Enum(_) => quote! {
+            use ::bt_only_headers::describe::{Describe, Describer, VariantName};
+            use ::bt_only_headers::packer::PacketIdentifier;
+        },
+        _ => quote! { use ::bt_only_headers::describe::{Describe, Describer}; },
+    };
+    let impls = describer(&[item]);
+    quote! {
+        const _: () = {
+            #imports
+            #(#impls)*
+        };
+    }
+    .into()
+}
+
+/// Implements `Generate` with random values that pack and unpack back to
+/// themselves, following the `FromToPacket` doc comment attributes
+#[proc_macro_derive(Generate)]
+pub fn derive_generate(input: TokenStream) -> TokenStream {
+    let item = syn::parse_macro_input!(input as Item);
+    if validate(std::slice::from_ref(&item)).is_err() {
+        // The problems are reported by `#[derive(FromToPacket)]`
+        return TokenStream::new();
+    }
+    let impls = generator(&[item]);
+    quote! {
+        const _: () = {
+            use ::bt_only_headers::generate::{generate_count, generate_repeated, Generate};
+            use ::bt_only_headers::packer::{FromToPacket, PacketIdentifier};
+            use ::bt_only_headers::random::Random;
+            #(#impls)*
+        };
+    }
+    .into()
+}
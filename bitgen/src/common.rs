@@ -1,4 +1,7 @@
 use proc_macro2::Span;
+use proc_macro2::TokenStream;
+use proc_macro2::TokenTree;
+use quote::ToTokens;
 use syn;
 use syn::Attribute;
 use syn::FieldsNamed;
@@ -88,6 +91,20 @@ pub fn get_field_names(fields: &Vec<FieldDef>) -> Vec<Ident> {
         .collect::<Vec<_>>()
 }
 
+/// Get the field types for named fields
+pub fn get_field_types(fields: &Vec<FieldDef>) -> Vec<Type> {
+    fields
+        .iter()
+        .filter_map(|field| {
+            if let FieldDef::Named { ty, .. } = field {
+                Some(ty.clone())
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>()
+}
+
 /// Get the field matchers for unnamed fields. This is used for destructuring,
 /// e.g. `m0` in `SomeValue(m0, m1)`.
 pub fn get_field_matchers(fields: &Vec<FieldDef>) -> Vec<Ident> {
@@ -103,6 +120,47 @@ pub fn get_field_matchers(fields: &Vec<FieldDef>) -> Vec<Ident> {
         .collect::<Vec<_>>()
 }
 
+/// Field name in a `count` or `element_length` attribute, e.g. `pair_length`
+/// in `element_length = pair_length`
+pub fn find_field_reference(attrs: &Vec<Attribute>, name: &str) -> Option<Ident> {
+    match find_attr_by_name(attrs, name)? {
+        Expr::Path(path) => path.path.get_ident().cloned(),
+        _ => None,
+    }
+}
+
+/// Does any field depend on another field, e.g. for its count, element
+/// length, presence or variant
+pub fn has_field_references(fields: &Vec<FieldDef>) -> bool {
+    fields.iter().any(|field| match field {
+        FieldDef::Named { attrs, .. } => {
+            find_field_reference(attrs, "count").is_some()
+                || find_field_reference(attrs, "element_length").is_some()
+                || find_field_reference(attrs, "variant_by").is_some()
+                || find_attr_by_name(attrs, "present_if").is_some()
+        }
+        _ => false,
+    })
+}
+
+/// Fields used in the expression, e.g. `format` in `present_if = format == 2`
+pub fn get_referenced_fields(expr: &Expr, field_names: &Vec<Ident>) -> Vec<Ident> {
+    fn collect(tokens: TokenStream, field_names: &Vec<Ident>, found: &mut Vec<Ident>) {
+        for token in tokens {
+            match token {
+                TokenTree::Ident(ident) if field_names.contains(&ident) && !found.contains(&ident) => {
+                    found.push(ident)
+                }
+                TokenTree::Group(group) => collect(group.stream(), field_names, found),
+                _ => {}
+            }
+        }
+    }
+    let mut found = Vec::new();
+    collect(expr.to_token_stream(), field_names, &mut found);
+    found
+}
+
 pub fn find_attr_by_name(attrs: &Vec<Attribute>, name: &str) -> Option<Expr> {
     let mut res = None;
 
@@ -3,12 +3,15 @@ use quote::quote;
 use syn;
 use syn::Attribute;
 use syn::Fields;
+use syn::Ident;
 use syn::Type;
 use syn::TypePath;
 
 use crate::common::build_field_defs_named;
 use crate::common::build_field_defs_unnamed;
 use crate::common::get_field_names;
+use crate::common::get_field_types;
+use crate::common::has_field_references;
 use crate::common::FieldDef;
 use crate::common::GenItem;
 
@@ -19,6 +22,12 @@ pub struct ConstructorCbArg {
     /// Top-level atttributes
     pub top_level_attrs: Vec<Attribute>,
 
+    /// Variant of the enum, e.g. `Bar` in `Foo::Bar`, `None` for structs
+    pub variant_name: Option<Ident>,
+
+    /// Number of fields in the struct or enum variant
+    pub field_count: usize,
+
     /// Definition of single field
     pub field: FieldDef,
 }
@@ -30,7 +39,9 @@ pub struct Constructor<T: Fn(&ConstructorCbArg) -> TokenStream> {
 
 /// Constructs the value of the type based on the provided callback
 ///
-/// The callback is called for each field of the struct or enum variant.
+/// The callback is called for each field of the struct or enum variant. When a
+/// named field refers to an earlier one, e.g. `element_length = pair_length`,
+/// the fields are first bound to variables in order.
 pub fn construct<T>(args: &Constructor<T>) -> TokenStream
 where
     T: Fn(&ConstructorCbArg) -> TokenStream,
@@ -45,10 +56,13 @@ where
                 qself: None,
                 path: istruct.ident.clone().into(),
             });
+            let field_count = istruct.fields.len();
             let map_cb = |field: &FieldDef| {
                 cb(&ConstructorCbArg {
                     top_level_attrs: top_level_attrs.clone(),
                     type_name: struct_name.clone(),
+                    variant_name: None,
+                    field_count,
                     field: field.clone(),
                 })
             };
@@ -58,6 +72,15 @@ where
                     let field_defs = build_field_defs_named(&fields);
                     let field_names = get_field_names(&field_defs);
                     let field_values = field_defs.iter().map(map_cb);
+                    if has_field_references(&field_defs) {
+                        let field_types = get_field_types(&field_defs);
+                        return quote! {
+                            {
+                                #(let #field_names: #field_types = #field_values;)*
+                                #struct_name { #(#field_names),* }
+                            }
+                        };
+                    }
 
                     quote! {
                         #struct_name {
@@ -79,6 +102,8 @@ where
                     let field_value = cb(&ConstructorCbArg {
                         top_level_attrs: top_level_attrs.clone(),
                         type_name: struct_name.clone(),
+                        variant_name: None,
+                        field_count,
                         field: FieldDef::UnitStruct {
                             attrs: top_level_attrs.clone(),
                         },
@@ -99,6 +124,8 @@ where
                 cb(&ConstructorCbArg {
                     top_level_attrs: ienum.attrs.clone(),
                     type_name: enum_name.clone(),
+                    variant_name: Some(variant_name.clone()),
+                    field_count: variant.fields.len(),
                     field: field.clone(),
                 })
             };
@@ -107,6 +134,15 @@ where
                     let field_defs = build_field_defs_named(fields);
                     let field_names = get_field_names(&field_defs);
                     let field_values = field_defs.iter().map(map_cb);
+                    if has_field_references(&field_defs) {
+                        let field_types = get_field_types(&field_defs);
+                        return quote! {
+                            {
+                                #(let #field_names: #field_types = #field_values;)*
+                                #enum_name::#variant_name { #(#field_names),* }
+                            }
+                        };
+                    }
                     return quote! {
                         #enum_name::#variant_name {
                             #(#field_names : #field_values),*
@@ -0,0 +1,433 @@
+use proc_macro2::Span;
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn;
+use syn::Attribute;
+use syn::Expr;
+use syn::Fields;
+use syn::Ident;
+use syn::Item;
+use syn::Type;
+
+use crate::common::{find_attr_by_name, find_attr_by_name_type};
+
+/// Name of the field from its identifier, e.g. `Connection interval` for
+/// `connection_interval`
+fn field_label(name: &str) -> String {
+    let words = name.trim_start_matches('_').replace('_', " ");
+    let mut chars = words.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => words,
+    }
+}
+
+/// Name of the variant from its identifier, e.g. `Connection Complete` for
+/// `ConnectionComplete` and `Scan Enabled, Page Scan Disabled` for
+/// `ScanEnabled_PageScanDisabled`
+fn variant_label(name: &str) -> String {
+    name.split('_')
+        .map(|part| {
+            let mut words = String::new();
+            for (index, c) in part.chars().enumerate() {
+                if index > 0 && c.is_uppercase() {
+                    words.push(' ');
+                }
+                words.push(c);
+            }
+            words
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `name` attribute, or the name made by `label` from the identifier
+fn find_name(attrs: &Vec<Attribute>, ident: &str, label: fn(&str) -> String) -> String {
+    match find_attr_by_name(attrs, "name") {
+        Some(Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(name), .. })) => name.value(),
+        _ => label(ident),
+    }
+}
+
+/// Value of the `describe = hex` style attribute
+fn find_describe(attrs: &Vec<Attribute>) -> Option<String> {
+    match find_attr_by_name(attrs, "describe")? {
+        Expr::Path(path) => path.path.get_ident().map(|ident| ident.to_string()),
+        _ => None,
+    }
+}
+
+/// Field of a struct or variant with the variable it's matched to
+struct DescribedField {
+    attrs: Vec<Attribute>,
+    label: String,
+    var: Ident,
+    ty: Type,
+}
+
+/// Match pattern and described fields, `skip` fields and the `first` fields
+/// are not bound
+fn describe_pattern(path: TokenStream, fields: &Fields, first: usize) -> (TokenStream, Vec<DescribedField>) {
+    let skipped = |attrs: &Vec<Attribute>, index: usize| {
+        index < first || find_describe(attrs).is_some_and(|describe| describe == "skip")
+    };
+    let mut described = Vec::new();
+    let pattern = match fields {
+        Fields::Named(named) => {
+            let mut bindings = Vec::new();
+            for (index, field) in named.named.iter().enumerate() {
+                let ident = field.ident.clone().expect("Expected named fields");
+                if skipped(&field.attrs, index) || ident.to_string().starts_with('_') {
+                    continue;
+                }
+                bindings.push(ident.clone());
+                described.push(DescribedField {
+                    attrs: field.attrs.clone(),
+                    label: find_name(&field.attrs, &ident.to_string(), field_label),
+                    var: ident,
+                    ty: field.ty.clone(),
+                });
+            }
+            quote! { #path { #(#bindings,)* .. } }
+        }
+        Fields::Unnamed(unnamed) => {
+            let bindings = unnamed.unnamed.iter().enumerate().map(|(index, field)| {
+                if skipped(&field.attrs, index) {
+                    return quote! { _ };
+                }
+                let var = Ident::new(&format!("m{}", index), Span::call_site());
+                described.push(DescribedField {
+                    attrs: field.attrs.clone(),
+                    label: find_name(&field.attrs, &index.to_string(), field_label),
+                    var: var.clone(),
+                    ty: field.ty.clone(),
+                });
+                quote! { #var }
+            });
+            let bindings = bindings.collect::<Vec<_>>();
+            quote! { #path(#(#bindings),*) }
+        }
+        Fields::Unit => path,
+    };
+    (pattern, described)
+}
+
+/// Writes the value of the field, e.g. `d.unit(*interval, 1.25, "msec")`
+fn describe_value(field: &DescribedField) -> TokenStream {
+    let var = &field.var;
+    if let Some(names) = find_attr_by_name_type(&field.attrs, "names") {
+        return quote! { d.named_id::<#names, _>(#var) };
+    }
+    if let Some(unit) = find_attr_by_name(&field.attrs, "unit") {
+        let scale = find_attr_by_name(&field.attrs, "scale").unwrap_or(syn::parse_quote! { 1.0 });
+        return quote! { d.unit(*#var, #scale, #unit) };
+    }
+    match find_describe(&field.attrs).as_deref() {
+        Some("hex") => quote! { #var.describe_hex(d) },
+        _ => quote! { #var.describe(d) },
+    }
+}
+
+/// Writes every field on its own line, or the names of the set flags
+fn describe_fields(fields: &[DescribedField], flags: bool) -> TokenStream {
+    if flags {
+        let flags = fields
+            .iter()
+            .filter(|field| matches!(&field.ty, Type::Path(path) if path.path.is_ident("bool")))
+            .map(|field| {
+                let label = &field.label;
+                let var = &field.var;
+                quote! { (#label, *#var) }
+            });
+        return quote! { d.flags(&[#(#flags),*])?; };
+    }
+    let lines = fields.iter().map(|field| {
+        let label = &field.label;
+        let value = describe_value(field);
+        quote! { d.field(#label, |d| #value)?; }
+    });
+    quote! { #(#lines)* }
+}
+
+/// Display of the type is its description
+fn build_display(name: &Ident) -> TokenStream {
+    quote! {
+        impl ::std::fmt::Display for #name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                self.describe(&mut Describer::new(f))
+            }
+        }
+    }
+}
+
+/// Generates `Describe` and `Display` implementations
+///
+/// Struct fields are written on their own lines, except for a single unnamed
+/// field which is written as the struct, e.g. `ConnectionHandle(64)` is `64`.
+/// Enum variants are written by name and id with their fields on the lines
+/// below. Enums also get `VariantName` for the `names = ...` fields.
+pub fn describer(items: &[Item]) -> Vec<TokenStream> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Struct(istruct) => {
+                let struct_name = &istruct.ident;
+                let flags = find_describe(&istruct.attrs).is_some_and(|describe| describe == "flags");
+                let (pattern, fields) = describe_pattern(quote! { #struct_name }, &istruct.fields, 0);
+                let body = match (&istruct.fields, fields.as_slice()) {
+                    (Fields::Unit, _) => {
+                        let label = variant_label(&struct_name.to_string());
+                        quote! { d.text(format_args!("{}", #label))?; }
+                    }
+                    (Fields::Unnamed(_), [field]) => {
+                        let value = describe_value(field);
+                        quote! { #value?; }
+                    }
+                    _ => describe_fields(&fields, flags),
+                };
+                let display = build_display(struct_name);
+                Some(quote! {
+                    impl Describe for #struct_name {
+                        fn describe(&self, d: &mut Describer) -> ::std::fmt::Result {
+                            match self {
+                                #pattern => {
+                                    #body
+                                }
+                            }
+                            Ok(())
+                        }
+                    }
+
+                    #display
+                })
+            }
+            Item::Enum(ienum) => {
+                let enum_name = &ienum.ident;
+                let id_type = find_attr_by_name_type(&ienum.attrs, "id_type")
+                    .expect("Missing id_type attribute, required for enums");
+                let mut arms = Vec::new();
+                let mut names = Vec::new();
+                for variant in &ienum.variants {
+                    let variant_name = &variant.ident;
+                    let label = find_name(&variant.attrs, &variant_name.to_string(), variant_label);
+                    let id = find_attr_by_name(&variant.attrs, "id")
+                        .or_else(|| variant.discriminant.as_ref().map(|(_, id)| id.clone()));
+
+                    // Passthrough has the id as its first field
+                    let passthrough = matches!(id, Some(Expr::Infer(_)));
+                    if let Some(id) = id.filter(|_| !passthrough) {
+                        names.push(quote! {
+                            if *id == #id {
+                                return Some(#label);
+                            }
+                        });
+                    }
+                    let (pattern, fields) =
+                        describe_pattern(quote! { #enum_name::#variant_name }, &variant.fields, passthrough as usize);
+                    let payload = match (&variant.fields, fields.as_slice()) {
+                        (_, []) => quote! {},
+                        (Fields::Unnamed(_), [field]) => {
+                            let value = describe_value(field);
+                            quote! { d.nested(|d| #value)?; }
+                        }
+                        _ => {
+                            let fields = describe_fields(&fields, false);
+                            quote! {
+                                d.nested(|d| {
+                                    #fields
+                                    Ok(())
+                                })?;
+                            }
+                        }
+                    };
+                    arms.push(quote! {
+                        #pattern => {
+                            d.variant(#label, &self.get_id())?;
+                            #payload
+                        }
+                    });
+                }
+                let display = build_display(enum_name);
+                Some(quote! {
+                    impl Describe for #enum_name {
+                        fn describe(&self, d: &mut Describer) -> ::std::fmt::Result {
+                            match self {
+                                #(#arms)*
+                            }
+                            Ok(())
+                        }
+                    }
+
+                    impl VariantName<#id_type> for #enum_name {
+                        fn variant_name(id: &#id_type) -> Option<&'static str> {
+                            #(#names)*
+                            None
+                        }
+                    }
+
+                    #display
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn pretty_string(toks: TokenStream) -> String {
+        prettyplease::unparse(&syn::parse2(toks).unwrap())
+    }
+
+    #[test]
+    fn test_labels() {
+        assert_eq!(field_label("connection_interval"), "Connection interval");
+        assert_eq!(field_label("_reserved"), "Reserved");
+        assert_eq!(variant_label("ConnectionComplete"), "Connection Complete");
+        assert_eq!(
+            variant_label("InquiryScanEnabled_PageScanDisabled"),
+            "Inquiry Scan Enabled, Page Scan Disabled"
+        );
+    }
+
+    #[test]
+    fn test_describer() {
+        let input_file_contents = quote! {
+            /// id_type = u8
+            enum Event {
+                /// name = "LE Meta Event"
+                /// id = 0x3e
+                LeMeta(LeMeta),
+                /// id = 0x05
+                Disconnected {
+                    /// describe = hex
+                    reason: u8,
+                },
+                Reset = 0x03,
+                /// id = _
+                Unknown(u8, Vec<u8>),
+            }
+
+            struct LeMeta {
+                /// describe = skip
+                length: u8,
+                /// unit = "msec"
+                /// scale = 1.25
+                connection_interval: u16,
+                /// names = HciCommand
+                command_opcode: OpCode,
+                _reserved: u8,
+            }
+
+            /// describe = flags
+            struct Flags {
+                /// name = "EncKey"
+                /// bits = 1
+                enc_key: bool,
+                /// bits = 7
+                _reserved: u8,
+            }
+
+            struct Handle(u16);
+        };
+        let res = syn::parse2::<syn::File>(input_file_contents).unwrap();
+        let impls = describer(&res.items);
+        let output = pretty_string(quote! { #(#impls)* });
+        let expected = pretty_string(quote! {
+            impl Describe for Event {
+                fn describe(&self, d: &mut Describer) -> ::std::fmt::Result {
+                    match self {
+                        Event::LeMeta(m0) => {
+                            d.variant("LE Meta Event", &self.get_id())?;
+                            d.nested(|d| m0.describe(d))?;
+                        }
+                        Event::Disconnected { reason, .. } => {
+                            d.variant("Disconnected", &self.get_id())?;
+                            d.nested(|d| {
+                                d.field("Reason", |d| reason.describe_hex(d))?;
+                                Ok(())
+                            })?;
+                        }
+                        Event::Reset => {
+                            d.variant("Reset", &self.get_id())?;
+                        }
+                        Event::Unknown(_, m1) => {
+                            d.variant("Unknown", &self.get_id())?;
+                            d.nested(|d| m1.describe(d))?;
+                        }
+                    }
+                    Ok(())
+                }
+            }
+            impl VariantName<u8> for Event {
+                fn variant_name(id: &u8) -> Option<&'static str> {
+                    if *id == 0x3e {
+                        return Some("LE Meta Event");
+                    }
+                    if *id == 0x05 {
+                        return Some("Disconnected");
+                    }
+                    if *id == 0x03 {
+                        return Some("Reset");
+                    }
+                    None
+                }
+            }
+            impl ::std::fmt::Display for Event {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    self.describe(&mut Describer::new(f))
+                }
+            }
+            impl Describe for LeMeta {
+                fn describe(&self, d: &mut Describer) -> ::std::fmt::Result {
+                    match self {
+                        LeMeta { connection_interval, command_opcode, .. } => {
+                            d.field("Connection interval", |d| d.unit(*connection_interval, 1.25, "msec"))?;
+                            d.field("Command opcode", |d| d.named_id::<HciCommand, _>(command_opcode))?;
+                        }
+                    }
+                    Ok(())
+                }
+            }
+            impl ::std::fmt::Display for LeMeta {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    self.describe(&mut Describer::new(f))
+                }
+            }
+            impl Describe for Flags {
+                fn describe(&self, d: &mut Describer) -> ::std::fmt::Result {
+                    match self {
+                        Flags { enc_key, .. } => {
+                            d.flags(&[("EncKey", *enc_key)])?;
+                        }
+                    }
+                    Ok(())
+                }
+            }
+            impl ::std::fmt::Display for Flags {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    self.describe(&mut Describer::new(f))
+                }
+            }
+            impl Describe for Handle {
+                fn describe(&self, d: &mut Describer) -> ::std::fmt::Result {
+                    match self {
+                        Handle(m0) => {
+                            m0.describe(d)?;
+                        }
+                    }
+                    Ok(())
+                }
+            }
+            impl ::std::fmt::Display for Handle {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    self.describe(&mut Describer::new(f))
+                }
+            }
+        });
+        assert_eq!(output, expected);
+    }
+}
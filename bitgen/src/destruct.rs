@@ -3,6 +3,7 @@ use quote::quote;
 use syn;
 use syn::Attribute;
 use syn::Fields;
+use syn::Ident;
 use syn::Type;
 use syn::TypePath;
 
@@ -18,6 +19,9 @@ pub struct DestructurerCbArg {
     pub type_name: Type,
     pub top_level_attrs: Vec<Attribute>,
     pub field: FieldDef,
+
+    /// Names of the named fields, e.g. to refer to other fields
+    pub field_names: Vec<Ident>,
 }
 
 pub struct Destructurer<
@@ -29,6 +33,14 @@ pub struct Destructurer<
     pub destructrurer: D,
 }
 
+/// Names of the fields, empty unless they are named
+fn named_field_names(fields: &Fields) -> Vec<Ident> {
+    match fields {
+        Fields::Named(fields) => get_field_names(&build_field_defs_named(fields)),
+        _ => vec![],
+    }
+}
+
 /// Returns destructuring syntax for pattern matching
 ///
 /// Result does not contain `match foo {}` only single arm of the match case.
@@ -45,12 +57,14 @@ where
                 qself: None,
                 path: item_struct.ident.clone().into(),
             });
+            let named_fields = named_field_names(&item_struct.fields);
             let my_cb = |field: &FieldDef| {
                 cb(&DestructurerCbArg {
                     top_level_attrs: item_struct.attrs.clone(),
                     type_name: struct_name.clone(),
                     // var_name: var_name.clone(),
                     field: field.clone(),
+                    field_names: named_fields.clone(),
                 })
             };
             match &item_struct.fields {
@@ -88,6 +102,7 @@ where
                         field: FieldDef::UnitStruct {
                             attrs: item_struct.attrs.clone(),
                         },
+                        field_names: vec![],
                     });
                     let body = wrapper(vec![field_value]);
                     quote! {
@@ -104,12 +119,14 @@ where
                 path: ienum.ident.clone().into(),
             });
             let variant_name = &variant.ident;
+            let named_fields = named_field_names(&variant.fields);
             let my_cb = |field: &FieldDef| {
                 cb(&DestructurerCbArg {
                     top_level_attrs: ienum.attrs.clone(),
                     type_name: enum_name.clone(),
                     // var_name: var_name.clone(),
                     field: field.clone(),
+                    field_names: named_fields.clone(),
                 })
             };
 
@@ -150,6 +167,7 @@ where
                             variant_name: variant.ident.clone(),
                             discriminant: variant.discriminant.clone().map(|d| d.1),
                         },
+                        field_names: vec![],
                     });
                     let body = wrapper(vec![field_value]);
                     quote! {
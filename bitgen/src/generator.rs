@@ -0,0 +1,329 @@
+use proc_macro2::Span;
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn;
+use syn::Attribute;
+use syn::Expr;
+use syn::Fields;
+use syn::Ident;
+use syn::Item;
+use syn::Type;
+
+use crate::common::{find_attr_by_name, find_field_reference};
+
+/// Field of a struct or variant with the variable it's generated to
+struct GeneratedField {
+    attrs: Vec<Attribute>,
+    var: Ident,
+    ty: Type,
+}
+
+fn generated_fields(fields: &Fields) -> Vec<GeneratedField> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| GeneratedField {
+            attrs: field.attrs.clone(),
+            var: field
+                .ident
+                .clone()
+                .unwrap_or_else(|| Ident::new(&format!("m{}", index), Span::call_site())),
+            ty: field.ty.clone(),
+        })
+        .collect()
+}
+
+/// Random value of the field, within its `bits`
+fn generate_value(field: &GeneratedField) -> TokenStream {
+    match find_attr_by_name(&field.attrs, "bits") {
+        Some(bits) => quote! { Generate::generate_bits(random, #bits) },
+        None => quote! { Generate::generate(random) },
+    }
+}
+
+/// Generates the fields as variables named after them, or `m0`, `m1`, ... for
+/// unnamed fields
+///
+/// Fields referred by `count`, `element_length` or `variant_by` are set from
+/// the referring field after it's generated, and `present_if` fields after all
+/// the others. Passthrough variants give the ids of the other variants, which
+/// their first field must not be.
+fn generate_fields(fields: &Fields, other_ids: Option<&Vec<Expr>>) -> TokenStream {
+    let fields = generated_fields(fields);
+    let mut generated = Vec::new();
+    let mut referring = Vec::new();
+    let mut referred = Vec::new();
+    let mut conditional = Vec::new();
+    let referred_by = |name: &Ident| {
+        fields.iter().find_map(|field| {
+            ["count", "element_length", "variant_by"]
+                .into_iter()
+                .find(|attr| find_field_reference(&field.attrs, attr).as_ref() == Some(name))
+                .map(|attr| (attr, &field.var))
+        })
+    };
+    for (index, field) in fields.iter().enumerate() {
+        let var = &field.var;
+        let ty = &field.ty;
+        if let Some(ids) = other_ids.filter(|_| index == 0) {
+            let value = generate_value(field);
+            generated.push(if ids.is_empty() {
+                quote! { let #var: #ty = #value; }
+            } else {
+                quote! {
+                    let #var: #ty = loop {
+                        let id = #value;
+                        if #(id != #ids)&&* {
+                            break id;
+                        }
+                    };
+                }
+            });
+        } else if let Some((attr, referring_var)) = referred_by(var) {
+            referred.push(match attr {
+                "count" => quote! { let #var = #referring_var.len() as #ty; },
+                "element_length" => quote! {
+                    let #var = #referring_var.first().map_or(0, |element| element.to_bytes().len()) as #ty;
+                },
+                _ => quote! { let #var: #ty = #referring_var.get_id(); },
+            });
+        } else if let Some(condition) = find_attr_by_name(&field.attrs, "present_if") {
+            let value = generate_value(field);
+            conditional.push(quote! {
+                let #var: #ty = if #condition { Some(#value) } else { None };
+            });
+        } else if let Some(count) = find_attr_by_name(&field.attrs, "count")
+            .filter(|_| find_field_reference(&field.attrs, "count").is_none())
+        {
+            referring.push(quote! { let #var: #ty = generate_count(random, #count); });
+        } else if find_field_reference(&field.attrs, "element_length").is_some() {
+            referring.push(quote! { let #var: #ty = generate_repeated(random); });
+        } else {
+            let value = generate_value(field);
+            generated.push(quote! { let #var: #ty = #value; });
+        }
+    }
+    quote! {
+        #(#generated)*
+        #(#referring)*
+        #(#referred)*
+        #(#conditional)*
+    }
+}
+
+/// Generates the fields like those of `self`, for structs with no fields
+/// depending on others
+///
+/// Each field then keeps its own size, e.g. the length of a `Vec<u8>`, and
+/// `bits` fields have a fixed size anyway.
+fn generate_fields_like(fields: &Fields) -> Option<TokenStream> {
+    let dependent = fields.iter().any(|field| {
+        ["count", "element_length", "variant_by"]
+            .into_iter()
+            .any(|attr| find_field_reference(&field.attrs, attr).is_some())
+            || find_attr_by_name(&field.attrs, "present_if").is_some()
+    });
+    if dependent || matches!(fields, Fields::Unit) {
+        return None;
+    }
+    let generated = fields
+        .members()
+        .zip(generated_fields(fields))
+        .map(|(member, field)| {
+            let var = &field.var;
+            let ty = &field.ty;
+            let value = match find_attr_by_name(&field.attrs, "bits") {
+                Some(bits) => quote! { Generate::generate_bits(random, #bits) },
+                None => quote! { Generate::generate_like(&self.#member, random) },
+            };
+            quote! { let #var: #ty = #value; }
+        });
+    Some(quote! { #(#generated)* })
+}
+
+/// Construction of the value from the generated fields
+fn construct_fields(path: TokenStream, fields: &Fields) -> TokenStream {
+    let vars = generated_fields(fields).into_iter().map(|field| field.var);
+    match fields {
+        Fields::Named(_) => quote! { #path { #(#vars),* } },
+        Fields::Unnamed(_) => quote! { #path(#(#vars),*) },
+        Fields::Unit => path,
+    }
+}
+
+/// Generates `Generate` implementations
+///
+/// Structs with a single unnamed field generate it within the bits of the
+/// struct, e.g. `ConnectionHandle` for the 12 bits of `HciAcl`. Structs with
+/// no fields depending on others generate values like their own, field by
+/// field. Enums pick a variant at random.
+pub fn generator(items: &[Item]) -> Vec<TokenStream> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Struct(istruct) => {
+                let struct_name = &istruct.ident;
+                let fields = generate_fields(&istruct.fields, None);
+                let construct = construct_fields(quote! { #struct_name }, &istruct.fields);
+                let generate_like = generate_fields_like(&istruct.fields).map(|fields| {
+                    quote! {
+                        fn generate_like(&self, random: &mut dyn Random) -> Self {
+                            #fields
+                            #construct
+                        }
+                    }
+                });
+                let generate_bits = match &istruct.fields {
+                    Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => quote! {
+                        fn generate_bits(random: &mut dyn Random, bits: u32) -> Self {
+                            #struct_name(Generate::generate_bits(random, bits))
+                        }
+                    },
+                    _ => quote! {},
+                };
+                Some(quote! {
+                    impl Generate for #struct_name {
+                        fn generate(random: &mut dyn Random) -> Self {
+                            #fields
+                            #construct
+                        }
+                        #generate_bits
+                        #generate_like
+                    }
+                })
+            }
+            Item::Enum(ienum) => {
+                let enum_name = &ienum.ident;
+                let ids = ienum
+                    .variants
+                    .iter()
+                    .filter_map(|variant| {
+                        find_attr_by_name(&variant.attrs, "id")
+                            .or_else(|| variant.discriminant.as_ref().map(|(_, id)| id.clone()))
+                    })
+                    .filter(|id| !matches!(id, Expr::Infer(_)))
+                    .collect::<Vec<_>>();
+                let count = ienum.variants.len();
+                let arms = ienum.variants.iter().enumerate().map(|(index, variant)| {
+                    let variant_name = &variant.ident;
+                    let passthrough = matches!(
+                        find_attr_by_name(&variant.attrs, "id"),
+                        Some(Expr::Infer(_))
+                    );
+                    let fields = generate_fields(&variant.fields, passthrough.then_some(&ids));
+                    let construct =
+                        construct_fields(quote! { #enum_name::#variant_name }, &variant.fields);
+                    let pattern = if index == count - 1 {
+                        quote! { _ }
+                    } else {
+                        quote! { #index }
+                    };
+                    quote! {
+                        #pattern => {
+                            #fields
+                            #construct
+                        }
+                    }
+                });
+                Some(quote! {
+                    impl Generate for #enum_name {
+                        fn generate(random: &mut dyn Random) -> Self {
+                            match random.below(#count) {
+                                #(#arms)*
+                            }
+                        }
+                    }
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn pretty_string(toks: TokenStream) -> String {
+        prettyplease::unparse(&syn::parse2(toks).unwrap())
+    }
+
+    #[test]
+    fn test_generator() {
+        let input_file_contents = quote! {
+            /// id_type = u8
+            enum Status {
+                /// id = 0x00
+                Success,
+                /// id = _
+                Failure(u8),
+            }
+
+            struct Response {
+                /// bits = 12
+                handle: Handle,
+                /// bits = 4
+                flags: u8,
+                pair_length: u8,
+                /// element_length = pair_length
+                values: Vec<Data>,
+                command_opcode: OpCode,
+                /// present_if = command_opcode != OpCode(0x0000, 0x00)
+                status: Option<Status>,
+            }
+
+            struct Handle(u16);
+        };
+        let res = syn::parse2::<syn::File>(input_file_contents).unwrap();
+        let impls = generator(&res.items);
+        let output = pretty_string(quote! { #(#impls)* });
+        let expected = pretty_string(quote! {
+            impl Generate for Status {
+                fn generate(random: &mut dyn Random) -> Self {
+                    match random.below(2usize) {
+                        0usize => Status::Success,
+                        _ => {
+                            let m0: u8 = loop {
+                                let id = Generate::generate(random);
+                                if id != 0x00 {
+                                    break id;
+                                }
+                            };
+                            Status::Failure(m0)
+                        }
+                    }
+                }
+            }
+            impl Generate for Response {
+                fn generate(random: &mut dyn Random) -> Self {
+                    let handle: Handle = Generate::generate_bits(random, 12);
+                    let flags: u8 = Generate::generate_bits(random, 4);
+                    let command_opcode: OpCode = Generate::generate(random);
+                    let values: Vec<Data> = generate_repeated(random);
+                    let pair_length = values.first().map_or(0, |element| element.to_bytes().len()) as u8;
+                    let status: Option<Status> = if command_opcode != OpCode(0x0000, 0x00) {
+                        Some(Generate::generate(random))
+                    } else {
+                        None
+                    };
+                    Response { handle, flags, pair_length, values, command_opcode, status }
+                }
+            }
+            impl Generate for Handle {
+                fn generate(random: &mut dyn Random) -> Self {
+                    let m0: u16 = Generate::generate(random);
+                    Handle(m0)
+                }
+                fn generate_bits(random: &mut dyn Random, bits: u32) -> Self {
+                    Handle(Generate::generate_bits(random, bits))
+                }
+                fn generate_like(&self, random: &mut dyn Random) -> Self {
+                    let m0: u16 = Generate::generate_like(&self.0, random);
+                    Handle(m0)
+                }
+            }
+        });
+        assert_eq!(output, expected);
+    }
+}
@@ -1,4 +1,5 @@
 use proc_macro2::TokenStream;
+use quote::format_ident;
 use quote::quote;
 use quote::ToTokens;
 use syn;
@@ -8,7 +9,7 @@ use syn::Item;
 use syn::Type;
 use syn::Expr;
 
-use crate::common::{find_attr_by_name, find_attr_by_name_type};
+use crate::common::{find_attr_by_name, find_attr_by_name_type, find_field_reference, get_referenced_fields};
 use crate::common::FieldDef;
 use crate::common::GenItem;
 use crate::construct::construct;
@@ -17,6 +18,7 @@ use crate::construct::ConstructorCbArg;
 use crate::destruct::destruct;
 use crate::destruct::Destructurer;
 use crate::destruct::DestructurerCbArg;
+use crate::validate::validate;
 
 /// Packs unit struct id bytes
 fn build_unitstruct_packer(attrs: &Vec<Attribute>, name: &Type) -> TokenStream {
@@ -45,13 +47,42 @@ fn build_object_unpacker(attrs: &Vec<Attribute>, object_name: &Ident) -> TokenSt
         })
     }
     if let Some(expr) = find_attr_by_name(&attrs, "prepend_length") {
-        ret.extend(quote! {
-            bytes.unpack_length::<#expr>()?;
-        });
+        if let Some(offset) = find_attr_by_name(&attrs, "prepend_length_offset") {
+            ret.extend(quote! {
+                bytes.unpack_length_with_offset::<#expr>(#offset)?;
+            });
+        } else {
+            ret.extend(quote! {
+                bytes.unpack_length::<#expr>()?;
+            });
+        }
     }
     ret
 }
 
+/// Unpack the value within the bytes covered by the prepended length
+fn build_bounded_unpack(attrs: &Vec<Attribute>, unpack: TokenStream) -> TokenStream {
+    if find_attr_by_name(attrs, "prepend_length").is_some() {
+        quote! {
+            bytes.unpack_bounded(|bytes| { #unpack })
+        }
+    } else {
+        unpack
+    }
+}
+
+/// Pack the value within the prepended length, so the length is patched with
+/// the bytes packed
+fn build_bounded_pack(attrs: &Vec<Attribute>, pack: TokenStream) -> TokenStream {
+    if find_attr_by_name(attrs, "prepend_length").is_some() {
+        quote! {
+            bytes.pack_bounded(|bytes| { #pack })
+        }
+    } else {
+        pack
+    }
+}
+
 /// Build pack length tokens
 fn build_pack_length(attrs: &Vec<Attribute>) -> TokenStream {
     let mut ret = quote! {};
@@ -73,12 +104,69 @@ fn build_pack_length(attrs: &Vec<Attribute>) -> TokenStream {
     ret
 }
 
+/// Pack the fields of the variant, within the length after the id if the
+/// enum has `length_after_id`
+fn build_length_after_id_pack<'a>(
+    length_type: &Option<Expr>,
+    fields: impl Iterator<Item = &'a TokenStream>,
+) -> TokenStream {
+    let fields = fields.collect::<Vec<_>>();
+    let Some(length_type) = length_type else {
+        return quote! { #(#fields)* };
+    };
+    // Unit variants don't pack anything within the length
+    let pack = if fields.iter().all(|field| field.is_empty()) {
+        quote! { |_| Ok(()) }
+    } else {
+        quote! {
+            |bytes| {
+                #(#fields)*
+                Ok(())
+            }
+        }
+    };
+    quote! {
+        bytes.pack_length::<#length_type>()?.pack_bounded(#pack)?;
+    }
+}
+
+/// Build `Vec` count and element length tokens, shared by packer and unpacker
+///
+/// - `count = 4` or `count = num_handles` sets the element count
+/// - `count_prefix = u8` has the element count before the elements
+/// - `element_length = pair_length` gives the size of every element
+///
+/// Fields are dereferenced when packing as they are borrowed from `self`.
+fn build_vec_layout(attrs: &Vec<Attribute>, packing: bool) -> TokenStream {
+    let mut ret = quote! {};
+    let deref = if packing { quote! { * } } else { quote! {} };
+    if let Some(ty) = find_attr_by_name_type(attrs, "count_prefix") {
+        ret.extend(if packing {
+            quote! { .pack_count::<#ty>()? }
+        } else {
+            quote! { .unpack_count::<#ty>()? }
+        });
+    }
+    for (name, setter) in [("count", quote! { set_count }), ("element_length", quote! { set_element_length })] {
+        if let Some(field) = find_field_reference(attrs, name) {
+            ret.extend(quote! { .#setter(#deref #field as usize) });
+        } else if let Some(expr) = find_attr_by_name(attrs, name) {
+            ret.extend(quote! { .#setter(#expr) });
+        }
+    }
+    ret
+}
+
 /// Build field packer tokens
 ///
 /// Full example: `bytes.pack_length::<u16>().set_bits(12).pack::<MyType>()?`
 /// 
 /// Type is inferred from the field type, so we don't need to specify it again
-fn build_field_packer(attrs: &Vec<Attribute>, field_name: &Ident) -> TokenStream {
+///
+/// With `present_if` the `Option` field is packed only if the condition holds,
+/// and it must be `None` otherwise. Fields in the condition are cloned, as they
+/// are borrowed from `self`.
+fn build_field_packer(attrs: &Vec<Attribute>, field_name: &Ident, field_names: &Vec<Ident>) -> TokenStream {
 
     let mut ret = quote! {
         bytes
@@ -100,13 +188,37 @@ fn build_field_packer(attrs: &Vec<Attribute>, field_name: &Ident) -> TokenStream
             .set_bits(#bexpr)
         });
     }
-    ret.extend(quote! { .pack(#field_name)?; });
+    ret.extend(build_vec_layout(attrs, true));
+    if let Some(id_field) = find_field_reference(attrs, "variant_by") {
+        ret.extend(quote! { .pack_variant(#field_name, #id_field)?; });
+    } else {
+        ret.extend(quote! { .pack(#field_name)?; });
+    }
+    if let Some(condition) = find_attr_by_name(attrs, "present_if") {
+        let fields = get_referenced_fields(&condition, field_names);
+        ret = quote! {
+            let present = {
+                #(let #fields = #fields.clone();)*
+                #condition
+            };
+            match (present, #field_name) {
+                (true, Some(#field_name)) => {
+                    #ret
+                }
+                (false, None) => {}
+                _ => return Err(PacketError::InvalidBytes),
+            }
+        };
+    }
     ret
 }
 
 /// Build field unpacker tokens
 ///
-/// Full example: `bytes.unpack_length::<u16>().set_bits(12).unpack::<MyType>()?`
+/// Full example: `bytes.unpack_length::<u16>()?.set_bits(12).unpack::<MyType>()`
+///
+/// `bytes_after = 12` unpacks the field from all but the last 12 bytes, which
+/// are left to the fields after it. Packing needs nothing for it.
 fn build_field_unpacker(attrs: &Vec<Attribute>, ty: Option<Type>) -> TokenStream {
     let mut ret = quote! {
         bytes
@@ -116,22 +228,59 @@ fn build_field_unpacker(attrs: &Vec<Attribute>, ty: Option<Type>) -> TokenStream
             .unpack_length::<#expr>()?
         });
     }
+    if let Some(expr) = find_attr_by_name(attrs, "bytes_after") {
+        ret.extend(quote! {
+            .set_bytes_after(#expr)?
+        });
+    }
     if let Some(bexpr) = find_attr_by_name(&attrs, "bits") {
         ret.extend(quote! {
             .set_bits(#bexpr)
         });
     }
+    ret.extend(build_vec_layout(attrs, false));
+
+    if let Some(id_field) = find_field_reference(attrs, "variant_by") {
+        ret.extend(quote! { .unpack_variant(&#id_field) });
+        return ret;
+    }
 
     ret.extend(quote! { .unpack });
 
     if let Some(ty) = ty {
-        ret.extend(quote! {::<#ty>()? });
+        ret.extend(quote! {::<#ty>() });
     } else {
-        ret.extend(quote! {()? });
+        ret.extend(quote! {() });
     }
     ret
 }
 
+/// Path of the field in unpacking errors, e.g. `HciAcl.msg` or `H4Packet::Acl`
+///
+/// Index of the only unnamed field is left out.
+fn field_path(arg: &ConstructorCbArg) -> String {
+    let mut path = arg.type_name.to_token_stream().to_string();
+    if let Some(variant_name) = &arg.variant_name {
+        path = format!("{}::{}", path, variant_name);
+    }
+    match &arg.field {
+        FieldDef::Named { name, .. } => format!("{}.{}", path, name),
+        FieldDef::Unnamed { index, .. } if arg.field_count > 1 => format!("{}.{}", path, index),
+        _ => path,
+    }
+}
+
+/// Can the id be a `match` pattern, e.g. `0x01` or `OpCode(0x0003, 0x03)`
+fn is_id_pattern(id: &TokenStream) -> bool {
+    match syn::parse2::<Expr>(id.clone()) {
+        Ok(Expr::Lit(_)) => true,
+        Ok(Expr::Call(call)) => {
+            matches!(*call.func, Expr::Path(_)) && call.args.iter().all(|arg| matches!(arg, Expr::Lit(_)))
+        }
+        _ => false,
+    }
+}
+
 #[derive(Clone)]
 enum IdBytes {
     Bytes(TokenStream),
@@ -159,7 +308,7 @@ fn get_id_bytes(variant: &syn::Variant) -> IdBytes {
             #val
         });
     }
-    panic!("No id found for variant {:?}", variant.to_token_stream());
+    unreachable!("Variant ids are checked by validate");
 }
 
 fn unpacking_callback(arg: &ConstructorCbArg) -> TokenStream {
@@ -167,8 +316,15 @@ fn unpacking_callback(arg: &ConstructorCbArg) -> TokenStream {
     let type_name = &arg.type_name;
 
     match field {
-        FieldDef::Named { attrs, .. } => build_field_unpacker(&attrs, None),
-        FieldDef::Unnamed { attrs, .. } => build_field_unpacker(&attrs, None),
+        FieldDef::Named { attrs, .. } | FieldDef::Unnamed { attrs, .. } => {
+            let path = field_path(arg);
+            let unpacker = build_field_unpacker(&attrs, None);
+            let unpack = quote! { bytes.unpack_field(#path, |bytes| #unpacker)? };
+            match find_attr_by_name(attrs, "present_if") {
+                Some(condition) => quote! { if #condition { Some(#unpack) } else { None } },
+                None => unpack,
+            }
+        }
         FieldDef::UnitStruct { .. } => quote! { #type_name },
         FieldDef::UnitEnum { variant_name, .. } => quote! { #type_name::#variant_name },
     }
@@ -179,14 +335,17 @@ fn packing_callback(args: &DestructurerCbArg) -> TokenStream {
     let type_name = &args.type_name;
 
     match &args.field {
-        FieldDef::Named { attrs, name, .. } => build_field_packer(attrs, name),
-        FieldDef::Unnamed { attrs, var_match, .. } => build_field_packer(attrs, var_match),
+        FieldDef::Named { attrs, name, .. } => build_field_packer(attrs, name, &args.field_names),
+        FieldDef::Unnamed { attrs, var_match, .. } => build_field_packer(attrs, var_match, &args.field_names),
         FieldDef::UnitStruct { attrs } => build_unitstruct_packer(attrs, type_name),
         FieldDef::UnitEnum {..} => quote! {},
     }
 }
 
 pub fn implementer(items: &Vec<Item>) -> Vec<proc_macro2::TokenStream> {
+    if let Err(error) = validate(items) {
+        return vec![error.to_compile_error()];
+    }
     let impls = items
         .iter()
         .filter_map(|item| {
@@ -207,19 +366,23 @@ pub fn implementer(items: &Vec<Item>) -> Vec<proc_macro2::TokenStream> {
                         item: genitem.clone(),
                         constructer: unpacking_callback,
                     });
+                    let unpack = build_bounded_unpack(&istruct.attrs, quote! { Ok(#unpack_to_value) });
+                    let pack = build_bounded_pack(&istruct.attrs, quote! {
+                        match self {
+                            #pack_fields
+                        };
+                        Ok(())
+                    });
 
                     Some(quote! {
                         impl FromToPacket for #struct_name {
                             fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
                                 #object_unpacker
-                                Ok(#unpack_to_value)
+                                #unpack
                             }
                             fn to_packet(&self, bytes: &mut Packet) -> Result<(), PacketError> {
                                 #object_length_packer
-                                match self {
-                                    #pack_fields
-                                };
-                                Ok(())
+                                #pack
                             }
                         }
                     })
@@ -230,6 +393,9 @@ pub fn implementer(items: &Vec<Item>) -> Vec<proc_macro2::TokenStream> {
                     let object_unpacker = build_object_unpacker(&ienum.attrs, enum_name);
                     let mut packers = Vec::new();
                     let mut unpackers = Vec::new();
+                    let mut arms = Vec::new();
+                    let mut variant_arms = Vec::new();
+                    let mut variant_packers = Vec::new();
                     let mut idcreators = Vec::new();
                     let last_variant = ienum.variants.last().unwrap();
                     let last_variant_id = get_id_bytes(&last_variant);
@@ -237,13 +403,23 @@ pub fn implementer(items: &Vec<Item>) -> Vec<proc_macro2::TokenStream> {
                     let length_after_id_unpack = find_attr_by_name(&ienum.attrs, "length_after_id").map(|f| quote! { 
                         bytes.unpack_length::<#f>()?;
                     });
-                    let length_after_id_pack = find_attr_by_name(&ienum.attrs, "length_after_id").map(|f| quote! { 
-                        bytes.pack_length::<#f>()?;
+                    let length_after_id_type = find_attr_by_name(&ienum.attrs, "length_after_id");
+
+                    // Unpack the id once and match on it, unless the ids
+                    // can't be patterns, e.g. `vec![0x01, 0x02]`
+                    let dispatch = ienum.variants.iter().all(|variant| match get_id_bytes(variant) {
+                        IdBytes::Bytes(id) => is_id_pattern(&id),
+                        IdBytes::Passthrough => true,
                     });
-                    for variant in &ienum.variants {
+                    let ids = ienum.variants.iter().filter_map(|variant| match get_id_bytes(variant) {
+                        IdBytes::Bytes(id) => Some(id),
+                        IdBytes::Passthrough => None,
+                    }).collect::<Vec<_>>();
+                    for variant in ienum.variants.iter() {
                         
                         // Match variant id bytes (&[0x01, 0x02] or _ which is the passthrough)
                         let id_bytes = get_id_bytes(variant);
+
                         let pack_id_bytes = match &id_bytes {
                             IdBytes::Bytes(id_bytes) => Some(quote! {
                                 bytes.pack::<#id_type>(&#id_bytes)?;
@@ -253,6 +429,20 @@ pub fn implementer(items: &Vec<Item>) -> Vec<proc_macro2::TokenStream> {
                             }
                         };
 
+                        // The passthrough would unpack as the variant of its
+                        // id if it has the id of another variant
+                        let check_passthrough_id = match (&id_bytes, variant.fields.iter().next()) {
+                            (IdBytes::Passthrough, Some(field)) if dispatch && !ids.is_empty() => {
+                                let id = field.ident.clone().unwrap_or_else(|| format_ident!("m0"));
+                                Some(quote! {
+                                    if [#(#ids),*].contains(#id) {
+                                        return Err(PacketError::InvalidBytes);
+                                    }
+                                })
+                            }
+                            _ => None,
+                        };
+
                         let genitem = GenItem::Enum(ienum.clone(), variant.clone());
                         let unpack = construct(&Constructor {
                             item: genitem.clone(),
@@ -260,19 +450,85 @@ pub fn implementer(items: &Vec<Item>) -> Vec<proc_macro2::TokenStream> {
                         });
                         let pack = destruct(&Destructurer {
                             item: genitem.clone(),
-                            wrapper: |fields| quote! {
-                                #pack_id_bytes
-                                #length_after_id_pack
-                                #(#fields)*
+                            wrapper: |fields| {
+                                let fields = build_length_after_id_pack(&length_after_id_type, fields.iter());
+                                quote! {
+                                    #check_passthrough_id
+                                    #pack_id_bytes
+                                    #fields
+                                }
                             },
                             destructrurer: packing_callback,
                         });
                         packers.push(pack);
+
+                        // Packed without the id, which the passthrough has as
+                        // its first field
+                        let is_passthrough = matches!(id_bytes, IdBytes::Passthrough);
+                        let variant_pack = destruct(&Destructurer {
+                            item: genitem.clone(),
+                            wrapper: |fields| {
+                                let fields = build_length_after_id_pack(&length_after_id_type, fields.iter().skip(is_passthrough as usize));
+                                quote! {
+                                    #check_passthrough_id
+                                    #fields
+                                }
+                            },
+                            destructrurer: packing_callback,
+                        });
+                        variant_packers.push(variant_pack);
+                        // Unit variants don't unpack anything within the length
+                        let bounded_bytes = match variant.fields {
+                            syn::Fields::Unit => quote! { _ },
+                            _ => quote! { bytes },
+                        };
+                        let (length_after_id, unpacked) = match &length_after_id_unpack {
+                            Some(length_after_id_unpack) => (
+                                length_after_id_unpack.clone(),
+                                quote! { bytes.unpack_bounded(|#bounded_bytes| Ok(#unpack)) },
+                            ),
+                            None => (quote! {}, quote! { Ok(#unpack) }),
+                        };
+                        variant_arms.push(match &id_bytes {
+                            IdBytes::Bytes(id) => quote! {
+                                #id => {
+                                    #length_after_id
+                                    #unpacked
+                                }
+                            },
+                            IdBytes::Passthrough => {
+                                let unpack_with_id = construct(&Constructor {
+                                    item: genitem.clone(),
+                                    constructer: |arg: &ConstructorCbArg| match &arg.field {
+                                        FieldDef::Named { index, .. } | FieldDef::Unnamed { index, .. }
+                                            if index.base10_digits() == "0" => quote! { id.clone() },
+                                        _ => unpacking_callback(arg),
+                                    },
+                                });
+                                quote! {
+                                    _ => Ok(#unpack_with_id),
+                                }
+                            }
+                        });
+                        arms.push(match &id_bytes {
+                            IdBytes::Bytes(id) => quote! {
+                                Ok(#id) => {
+                                    #length_after_id
+                                    #unpacked
+                                }
+                            },
+                            IdBytes::Passthrough => quote! {
+                                _ => {
+                                    bytes.rewind(checkpoint);
+                                    Ok(#unpack)
+                                }
+                            },
+                        });
                         unpackers.push(match &id_bytes {
                             IdBytes::Bytes(token_stream) => quote! {
                                 if bytes.next_if_eq::<#id_type>(&#token_stream) {
-                                    #length_after_id_unpack
-                                    return Ok(#unpack);
+                                    #length_after_id
+                                    return #unpacked;
                                 }
                             },
                             IdBytes::Passthrough => quote! {
@@ -315,27 +571,77 @@ pub fn implementer(items: &Vec<Item>) -> Vec<proc_macro2::TokenStream> {
                         }));
                     }
 
-                    let err_or_nothing = match last_variant_id {
-                        IdBytes::Bytes(_) => quote! {
-                            Err(PacketError::Unspecified(format!("No matching variant found for {}", stringify!(#enum_name))))
-                        },
-                        IdBytes::Passthrough => quote! {},
+                    let no_match = quote! {
+                        Err(PacketError::Unspecified(format!("No matching variant found for {}", stringify!(#enum_name))))
                     };
 
+                    let unpack = if dispatch {
+                        if let IdBytes::Bytes(_) = last_variant_id {
+                            arms.push(quote! {
+                                _ => {
+                                    bytes.rewind(checkpoint);
+                                    #no_match
+                                }
+                            });
+                            variant_arms.push(quote! {
+                                _ => #no_match,
+                            });
+                        }
+                        quote! {
+                            let checkpoint = bytes.checkpoint();
+                            match <#id_type>::from_packet(bytes) {
+                                #(#arms)*
+                            }
+                        }
+                    } else {
+                        let err_or_nothing = match last_variant_id {
+                            IdBytes::Bytes(_) => no_match,
+                            IdBytes::Passthrough => quote! {},
+                        };
+                        quote! {
+                            #(#unpackers);*
+                            #err_or_nothing
+                        }
+                    };
+                    let unpack = build_bounded_unpack(&ienum.attrs, unpack);
+
+                    // Variant can be chosen by an id packed elsewhere, unless
+                    // the enum packs more than the id before the variant
+                    let variant_impl = if dispatch && object_unpacker.is_empty() {
+                        quote! {
+                            impl PacketVariant<#id_type> for #enum_name {
+                                fn from_packet_variant(id: &#id_type, bytes: &mut Packet) -> Result<Self, PacketError> {
+                                    match id {
+                                        #(#variant_arms)*
+                                    }
+                                }
+                                fn to_packet_variant(&self, bytes: &mut Packet) -> Result<(), PacketError> {
+                                    match self {
+                                        #(#variant_packers)*
+                                    };
+                                    Ok(())
+                                }
+                            }
+                        }
+                    } else {
+                        quote! {}
+                    };
+                    let pack = build_bounded_pack(&ienum.attrs, quote! {
+                        match self {
+                            #(#packers)*
+                        };
+                        Ok(())
+                    });
 
                     Some(quote! {
                         impl FromToPacket for #enum_name {
                             fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
                                 #object_unpacker
-                                #(#unpackers);*
-                                #err_or_nothing
+                                #unpack
                             }
                             fn to_packet(&self, bytes: &mut Packet) -> Result<(), PacketError> {
                                 #object_pack_length
-                                match self {
-                                    #(#packers)*
-                                };
-                                Ok(())
+                                #pack
                             }
                         }
 
@@ -346,6 +652,8 @@ pub fn implementer(items: &Vec<Item>) -> Vec<proc_macro2::TokenStream> {
                                 }
                             }
                         }
+
+                        #variant_impl
                     })
                 }
                 _ => return None,
@@ -373,6 +681,249 @@ mod tests {
         }
     }
 
+    /// Invalid items give a compile error instead of the implementations
+    fn compile_error(items: &Vec<Item>) -> String {
+        let output = implementer(items);
+        assert_eq!(output.len(), 1);
+        output[0].to_string()
+    }
+
+    #[test]
+    fn test_passthrough_not_last() {
+        let input_file_contents = quote! {
+            /// id_type = u8
+            enum Broken {
+                /// id = _
+                Reserved(u8),
+                Foo = 0x01,
+            }
+        };
+        let res = syn::parse2::<syn::File>(input_file_contents).unwrap();
+        let output = compile_error(&res.items);
+        assert!(output.contains("compile_error"));
+        assert!(output.contains("must be the last variant of Broken"));
+    }
+
+    #[test]
+    fn test_duplicate_id() {
+        let input_file_contents = quote! {
+            /// id_type = u8
+            enum Broken {
+                /// id = 0x01
+                Foo,
+                /// id = 1
+                Bar,
+            }
+        };
+        let res = syn::parse2::<syn::File>(input_file_contents).unwrap();
+        let output = compile_error(&res.items);
+        assert!(output.contains("Variants `Foo` and `Bar` of Broken have the same id 1"));
+    }
+
+    #[test]
+    fn test_id_dispatch() {
+        let input_file_contents = quote! {
+            /// id_type = OpCode
+            /// length_after_id = u8
+            enum Command {
+                /// id = OpCode(0x0003, 0x03)
+                Reset,
+                /// id = OpCode(0x0001, 0x03)
+                SetEventMask(u64),
+            }
+        };
+        let res = syn::parse2::<syn::File>(input_file_contents).unwrap();
+        let output_toks = implementer(&res.items);
+        let output = quote! {
+            #(#output_toks)*
+        };
+        assert_eq!(
+            pretty_string(output),
+            pretty_string(quote! {
+                impl FromToPacket for Command {
+                    fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
+                        let checkpoint = bytes.checkpoint();
+                        match <OpCode>::from_packet(bytes) {
+                            Ok(OpCode(0x0003, 0x03)) => {
+                                bytes.unpack_length::<u8>()?;
+                                bytes.unpack_bounded(|_| Ok(Command::Reset))
+                            }
+                            Ok(OpCode(0x0001, 0x03)) => {
+                                bytes.unpack_length::<u8>()?;
+                                bytes.unpack_bounded(|bytes| Ok(Command::SetEventMask(
+                                    bytes.unpack_field("Command::SetEventMask", |bytes| bytes.unpack())?,
+                                )))
+                            }
+                            _ => {
+                                bytes.rewind(checkpoint);
+                                Err(PacketError::Unspecified(format!("No matching variant found for {}", stringify!(Command))))
+                            }
+                        }
+                    }
+                    fn to_packet(&self, bytes: &mut Packet) -> Result<(), PacketError> {
+                        match self {
+                            Command::Reset => {
+                                bytes.pack::<OpCode>(&OpCode(0x0003, 0x03))?;
+                                bytes.pack_length::<u8>()?.pack_bounded(|_| Ok(()))?;
+                            }
+                            Command::SetEventMask(m0) => {
+                                bytes.pack::<OpCode>(&OpCode(0x0001, 0x03))?;
+                                bytes.pack_length::<u8>()?.pack_bounded(|bytes| {
+                                    bytes.pack(m0)?;
+                                    Ok(())
+                                })?;
+                            }
+                        };
+                        Ok(())
+                    }
+                }
+                impl PacketIdentifier<OpCode> for Command {
+                    fn get_id(&self) -> OpCode {
+                        match self {
+                            Command::Reset => OpCode(0x0003, 0x03),
+                            Command::SetEventMask(m0) => OpCode(0x0001, 0x03),
+                        }
+                    }
+                }
+                impl PacketVariant<OpCode> for Command {
+                    fn from_packet_variant(id: &OpCode, bytes: &mut Packet) -> Result<Self, PacketError> {
+                        match id {
+                            OpCode(0x0003, 0x03) => {
+                                bytes.unpack_length::<u8>()?;
+                                bytes.unpack_bounded(|_| Ok(Command::Reset))
+                            }
+                            OpCode(0x0001, 0x03) => {
+                                bytes.unpack_length::<u8>()?;
+                                bytes.unpack_bounded(|bytes| Ok(Command::SetEventMask(
+                                    bytes.unpack_field("Command::SetEventMask", |bytes| bytes.unpack())?,
+                                )))
+                            }
+                            _ => Err(PacketError::Unspecified(format!("No matching variant found for {}", stringify!(Command)))),
+                        }
+                    }
+                    fn to_packet_variant(&self, bytes: &mut Packet) -> Result<(), PacketError> {
+                        match self {
+                            Command::Reset => {
+                                bytes.pack_length::<u8>()?.pack_bounded(|_| Ok(()))?;
+                            }
+                            Command::SetEventMask(m0) => {
+                                bytes.pack_length::<u8>()?.pack_bounded(|bytes| {
+                                    bytes.pack(m0)?;
+                                    Ok(())
+                                })?;
+                            }
+                        };
+                        Ok(())
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_vec_layout() {
+        let input_file_contents = quote! {
+            struct Lists {
+                pair_length: u8,
+                /// element_length = pair_length
+                pairs: Vec<Pair>,
+                /// count_prefix = u8
+                handles: Vec<u16>,
+                /// count = 2
+                fixed: Vec<u32>,
+            }
+        };
+        let res = syn::parse2::<syn::File>(input_file_contents).unwrap();
+        let output_toks = implementer(&res.items);
+        let output = quote! {
+            #(#output_toks)*
+        };
+        assert_eq!(
+            pretty_string(output),
+            pretty_string(quote! {
+                impl FromToPacket for Lists {
+                    fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
+                        Ok({
+                            let pair_length: u8 = bytes.unpack_field("Lists.pair_length", |bytes| bytes.unpack())?;
+                            let pairs: Vec<Pair> = bytes.unpack_field("Lists.pairs", |bytes| bytes.set_element_length(pair_length as usize).unpack())?;
+                            let handles: Vec<u16> = bytes.unpack_field("Lists.handles", |bytes| bytes.unpack_count::<u8>()?.unpack())?;
+                            let fixed: Vec<u32> = bytes.unpack_field("Lists.fixed", |bytes| bytes.set_count(2).unpack())?;
+                            Lists { pair_length, pairs, handles, fixed }
+                        })
+                    }
+                    fn to_packet(&self, bytes: &mut Packet) -> Result<(), PacketError> {
+                        match self {
+                            Lists { pair_length, pairs, handles, fixed } => {
+                                bytes.pack(pair_length)?;
+                                bytes.set_element_length(*pair_length as usize).pack(pairs)?;
+                                bytes.pack_count::<u8>()?.pack(handles)?;
+                                bytes.set_count(2).pack(fixed)?;
+                            }
+                        };
+                        Ok(())
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_conditional_fields() {
+        let input_file_contents = quote! {
+            struct Info {
+                format: u8,
+                /// variant_by = format
+                data: InfoData,
+                /// present_if = format == 0x02
+                extra: Option<u16>,
+            }
+        };
+        let res = syn::parse2::<syn::File>(input_file_contents).unwrap();
+        let output_toks = implementer(&res.items);
+        let output = quote! {
+            #(#output_toks)*
+        };
+        assert_eq!(
+            pretty_string(output),
+            pretty_string(quote! {
+                impl FromToPacket for Info {
+                    fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
+                        Ok({
+                            let format: u8 = bytes.unpack_field("Info.format", |bytes| bytes.unpack())?;
+                            let data: InfoData = bytes.unpack_field("Info.data", |bytes| bytes.unpack_variant(&format))?;
+                            let extra: Option<u16> = if format == 0x02 {
+                                Some(bytes.unpack_field("Info.extra", |bytes| bytes.unpack())?)
+                            } else {
+                                None
+                            };
+                            Info { format, data, extra }
+                        })
+                    }
+                    fn to_packet(&self, bytes: &mut Packet) -> Result<(), PacketError> {
+                        match self {
+                            Info { format, data, extra } => {
+                                bytes.pack(format)?;
+                                bytes.pack_variant(data, format)?;
+                                let present = {
+                                    let format = format.clone();
+                                    format == 0x02
+                                };
+                                match (present, extra) {
+                                    (true, Some(extra)) => {
+                                        bytes.pack(extra)?;
+                                    }
+                                    (false, None) => {}
+                                    _ => return Err(PacketError::InvalidBytes),
+                                }
+                            }
+                        };
+                        Ok(())
+                    }
+                }
+            })
+        );
+    }
+
     #[test]
     fn test_implementer() {
         let input_file_contents = quote! {
@@ -418,6 +969,14 @@ mod tests {
                 Foo = 0x01,
                 Bar = 0x02,
             }
+
+            /// id_type = u8
+            #[repr(u8)]
+            enum DiscriminantWithCatchAll {
+                Foo = 0x01,
+                /// id = _
+                Reserved(u8),
+            }
         };
         let res = syn::parse2::<syn::File>(input_file_contents).unwrap();
         let output_toks = implementer(&res.items);
@@ -429,28 +988,35 @@ mod tests {
             pretty_string(quote! {
                 impl FromToPacket for MyStruct {
                     fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
-                        bytes.unpack_length::<u16>()?;
-                        Ok(MyStruct {
-                            field1: bytes.set_bits(12).unpack()?,
-                            field2: bytes.set_bits(4).unpack()?,
-                            field3: bytes.unpack_length::<u16>()?.unpack()?,
+                        bytes.unpack_length_with_offset::<u16>(2)?;
+                        bytes.unpack_bounded(|bytes| {
+                            Ok(MyStruct {
+                                field1: bytes.unpack_field("MyStruct.field1", |bytes| bytes.set_bits(12).unpack())?,
+                                field2: bytes.unpack_field("MyStruct.field2", |bytes| bytes.set_bits(4).unpack())?,
+                                field3: bytes.unpack_field("MyStruct.field3", |bytes| bytes.unpack_length::<u16>()?.unpack())?,
+                            })
                         })
                     }
                     fn to_packet(&self, bytes: &mut Packet) -> Result<(), PacketError> {
                         bytes.pack_length_with_offset::<u16>(2)?;
-                        match self {
-                            MyStruct { field1, field2, field3 } => {
-                                bytes.set_bits(12).pack(field1)?;
-                                bytes.set_bits(4).pack(field2)?;
-                                bytes.pack_length::<u16>()?.pack(field3)?;
-                            }
-                        };
-                        Ok(())
+                        bytes.pack_bounded(|bytes| {
+                            match self {
+                                MyStruct { field1, field2, field3 } => {
+                                    bytes.set_bits(12).pack(field1)?;
+                                    bytes.set_bits(4).pack(field2)?;
+                                    bytes.pack_length::<u16>()?.pack(field3)?;
+                                }
+                            };
+                            Ok(())
+                        })
                     }
                 }
                 impl FromToPacket for AnotherStruct {
                     fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
-                        Ok(AnotherStruct(bytes.unpack()?, bytes.unpack()?))
+                        Ok(AnotherStruct(
+                            bytes.unpack_field("AnotherStruct.0", |bytes| bytes.unpack())?,
+                            bytes.unpack_field("AnotherStruct.1", |bytes| bytes.unpack())?,
+                        ))
                     }
                     fn to_packet(&self, bytes: &mut Packet) -> Result<(), PacketError> {
                         match self {
@@ -482,18 +1048,21 @@ mod tests {
                     fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
                         if bytes.next_if_eq::<Vec<u8>>(&vec![0x01, 0x02]) {
                             bytes.unpack_length::<u8>()?;
-                            return Ok(MyEnum::NamedVariant {
-                                field: bytes.unpack()?,
-                                field2: bytes.unpack()?,
-                            });
+                            return bytes.unpack_bounded(|bytes| Ok(MyEnum::NamedVariant {
+                                field: bytes.unpack_field("MyEnum::NamedVariant.field", |bytes| bytes.unpack())?,
+                                field2: bytes.unpack_field("MyEnum::NamedVariant.field2", |bytes| bytes.unpack())?,
+                            }));
                         }
                         if bytes.next_if_eq::<Vec<u8>>(&vec![0x03]) {
                             bytes.unpack_length::<u8>()?;
-                            return Ok(MyEnum::UnnamedVariant(bytes.unpack()?, bytes.unpack()?));
+                            return bytes.unpack_bounded(|bytes| Ok(MyEnum::UnnamedVariant(
+                                bytes.unpack_field("MyEnum::UnnamedVariant.0", |bytes| bytes.unpack())?,
+                                bytes.unpack_field("MyEnum::UnnamedVariant.1", |bytes| bytes.unpack())?,
+                            )));
                         }
                         if bytes.next_if_eq::<Vec<u8>>(&vec![0x04]) {
                             bytes.unpack_length::<u8>()?;
-                            return Ok(MyEnum::UnitVariant);
+                            return bytes.unpack_bounded(|_| Ok(MyEnum::UnitVariant));
                         }
                         Err(
                             PacketError::Unspecified(
@@ -505,19 +1074,23 @@ mod tests {
                         match self {
                             MyEnum::NamedVariant { field, field2 } => {
                                 bytes.pack::<Vec<u8>>(&vec![0x01, 0x02])?;
-                                bytes.pack_length::<u8>()?;
-                                bytes.pack(field)?;
-                                bytes.pack(field2)?;
+                                bytes.pack_length::<u8>()?.pack_bounded(|bytes| {
+                                    bytes.pack(field)?;
+                                    bytes.pack(field2)?;
+                                    Ok(())
+                                })?;
                             },
                             MyEnum::UnnamedVariant(m0, m1) => {
                                 bytes.pack::<Vec<u8>>(&vec![0x03])?;
-                                bytes.pack_length::<u8>()?;
-                                bytes.pack(m0)?;
-                                bytes.pack(m1)?;
+                                bytes.pack_length::<u8>()?.pack_bounded(|bytes| {
+                                    bytes.pack(m0)?;
+                                    bytes.pack(m1)?;
+                                    Ok(())
+                                })?;
                             },
                             MyEnum::UnitVariant => {
                                 bytes.pack::<Vec<u8>>(&vec![0x04])?;
-                                bytes.pack_length::<u8>()?;
+                                bytes.pack_length::<u8>()?.pack_bounded(|_| Ok(()))?;
                             }
                         };
                         Ok(())
@@ -534,10 +1107,14 @@ mod tests {
                 }
                 impl FromToPacket for Status {
                     fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
-                        if bytes.next_if_eq::<u8>(&0x01) {
-                            return Ok(Status::Success);
+                        let checkpoint = bytes.checkpoint();
+                        match <u8>::from_packet(bytes) {
+                            Ok(0x01) => Ok(Status::Success),
+                            _ => {
+                                bytes.rewind(checkpoint);
+                                Ok(Status::Error(bytes.unpack_field("Status::Error", |bytes| bytes.unpack())?))
+                            }
                         }
-                        Ok(Status::Error(bytes.unpack()?))
                     }
                     fn to_packet(&self, bytes: &mut Packet) -> Result<(), PacketError> {
                         match self {
@@ -545,6 +1122,9 @@ mod tests {
                                 bytes.pack::<u8>(&0x01)?;
                             }
                             Status::Error(m0) => {
+                                if [0x01].contains(m0) {
+                                    return Err(PacketError::InvalidBytes);
+                                }
                                 bytes.pack(m0)?;
                             }
                         };
@@ -559,22 +1139,43 @@ mod tests {
                         }
                     }
                 }
+                impl PacketVariant<u8> for Status {
+                    fn from_packet_variant(id: &u8, bytes: &mut Packet) -> Result<Self, PacketError> {
+                        match id {
+                            0x01 => Ok(Status::Success),
+                            _ => Ok(Status::Error(id.clone())),
+                        }
+                    }
+                    fn to_packet_variant(&self, bytes: &mut Packet) -> Result<(), PacketError> {
+                        match self {
+                            Status::Success => {}
+                            Status::Error(m0) => {
+                                if [0x01].contains(m0) {
+                                    return Err(PacketError::InvalidBytes);
+                                }
+                            }
+                        };
+                        Ok(())
+                    }
+                }
 
                 impl FromToPacket for SomeDiscriminantEnum {
                     fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
-                        if bytes.next_if_eq::<u8>(&0x01) {
-                            return Ok(SomeDiscriminantEnum::Foo);
-                        }
-                        if bytes.next_if_eq::<u8>(&0x02) {
-                            return Ok(SomeDiscriminantEnum::Bar);
+                        let checkpoint = bytes.checkpoint();
+                        match <u8>::from_packet(bytes) {
+                            Ok(0x01) => Ok(SomeDiscriminantEnum::Foo),
+                            Ok(0x02) => Ok(SomeDiscriminantEnum::Bar),
+                            _ => {
+                                bytes.rewind(checkpoint);
+                                Err(
+                                    PacketError::Unspecified(
+                                        format!(
+                                            "No matching variant found for {}", stringify!(SomeDiscriminantEnum)
+                                        ),
+                                    ),
+                                )
+                            }
                         }
-                        Err(
-                            PacketError::Unspecified(
-                                format!(
-                                    "No matching variant found for {}", stringify!(SomeDiscriminantEnum)
-                                ),
-                            ),
-                        )
                     }
                     fn to_packet(&self, bytes: &mut Packet) -> Result<(), PacketError> {
                         match self {
@@ -596,6 +1197,78 @@ mod tests {
                         }
                     }
                 }
+                impl PacketVariant<u8> for SomeDiscriminantEnum {
+                    fn from_packet_variant(id: &u8, bytes: &mut Packet) -> Result<Self, PacketError> {
+                        match id {
+                            0x01 => Ok(SomeDiscriminantEnum::Foo),
+                            0x02 => Ok(SomeDiscriminantEnum::Bar),
+                            _ => Err(PacketError::Unspecified(format!("No matching variant found for {}", stringify!(SomeDiscriminantEnum)))),
+                        }
+                    }
+                    fn to_packet_variant(&self, bytes: &mut Packet) -> Result<(), PacketError> {
+                        match self {
+                            SomeDiscriminantEnum::Foo => {}
+                            SomeDiscriminantEnum::Bar => {}
+                        };
+                        Ok(())
+                    }
+                }
+
+                impl FromToPacket for DiscriminantWithCatchAll {
+                    fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
+                        let checkpoint = bytes.checkpoint();
+                        match <u8>::from_packet(bytes) {
+                            Ok(0x01) => Ok(DiscriminantWithCatchAll::Foo),
+                            _ => {
+                                bytes.rewind(checkpoint);
+                                Ok(DiscriminantWithCatchAll::Reserved(
+                                    bytes.unpack_field("DiscriminantWithCatchAll::Reserved", |bytes| bytes.unpack())?,
+                                ))
+                            }
+                        }
+                    }
+                    fn to_packet(&self, bytes: &mut Packet) -> Result<(), PacketError> {
+                        match self {
+                            DiscriminantWithCatchAll::Foo => {
+                                bytes.pack::<u8>(&0x01)?;
+                            }
+                            DiscriminantWithCatchAll::Reserved(m0) => {
+                                if [0x01].contains(m0) {
+                                    return Err(PacketError::InvalidBytes);
+                                }
+                                bytes.pack(m0)?;
+                            }
+                        };
+                        Ok(())
+                    }
+                }
+                impl PacketIdentifier<u8> for DiscriminantWithCatchAll {
+                    fn get_id(&self) -> u8 {
+                        match self {
+                            DiscriminantWithCatchAll::Foo => 0x01,
+                            DiscriminantWithCatchAll::Reserved(m0) => m0.clone(),
+                        }
+                    }
+                }
+                impl PacketVariant<u8> for DiscriminantWithCatchAll {
+                    fn from_packet_variant(id: &u8, bytes: &mut Packet) -> Result<Self, PacketError> {
+                        match id {
+                            0x01 => Ok(DiscriminantWithCatchAll::Foo),
+                            _ => Ok(DiscriminantWithCatchAll::Reserved(id.clone())),
+                        }
+                    }
+                    fn to_packet_variant(&self, bytes: &mut Packet) -> Result<(), PacketError> {
+                        match self {
+                            DiscriminantWithCatchAll::Foo => {}
+                            DiscriminantWithCatchAll::Reserved(m0) => {
+                                if [0x01].contains(m0) {
+                                    return Err(PacketError::InvalidBytes);
+                                }
+                            }
+                        };
+                        Ok(())
+                    }
+                }
             })
         );
     }
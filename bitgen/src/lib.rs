@@ -0,0 +1,11 @@
+//! Generates `FromToPacket`, `Describe` and `Generate` implementations from doc
+//! comment attributes, used by the derives and the `bitgen` binary
+
+mod common;
+mod construct;
+mod destruct;
+pub mod describer;
+pub mod generator;
+pub mod implementer;
+mod implementer_example;
+pub mod validate;
@@ -1,35 +1,82 @@
 use quote::quote;
 use std::fs::*;
+use std::process::ExitCode;
 use syn;
 
-mod common;
-mod construct;
-mod destruct;
-mod implementer;
-mod implementer_example;
+use bitgen::describer::describer;
+use bitgen::generator::generator;
+use bitgen::implementer::implementer;
+use bitgen::validate::validate;
 
-fn generate_files(path: String, output: String) {
-    use implementer::implementer;
-    let read_file = read_to_string(path).unwrap();
-    let res = syn::parse_file(&read_file).unwrap();
-    let impls = implementer(&res.items);
+/// Prints the problems with the items of the file, as `path:line:column:
+/// error: message`
+fn check(path: &str, items: &[syn::Item]) -> bool {
+    match validate(items) {
+        Ok(()) => true,
+        Err(errors) => {
+            for error in errors {
+                let start = error.span().start();
+                eprintln!("{}:{}:{}: error: {}", path, start.line, start.column + 1, error);
+            }
+            false
+        }
+    }
+}
+
+/// Prints what `#[derive(FromToPacket, Describe, Generate)]` generates for the
+/// items of the file
+fn print_impls(items: &Vec<syn::Item>) {
+    let impls = implementer(items);
+    let descriptions = describer(items);
+    let generators = generator(items);
 
     let v = quote! {
         use crate::messages::*;
         use crate::packer::*;
+        use crate::describe::*;
+        use crate::generate::*;
+        use crate::random::Random;
         #(#impls)*
+        #(#descriptions)*
+        #(#generators)*
     };
 
-    println!("Unformatted: {}", v.to_string());
     let f = syn::parse2(v).unwrap();
     let c = prettyplease::unparse(&f);
     println!("{}", c);
-    std::fs::write(output, c.clone()).unwrap();
 }
 
-fn main() {
-    generate_files(
-        "./src/messages.rs".to_string(),
-        "./src/messages_impl.rs".to_string(),
-    );
+/// `bitgen [--check] [path]`
+///
+/// With `--check` only the problems are printed. The implementations are
+/// generated by the derives at compile time, so there's no generated file to
+/// be out of date, only the attributes to check.
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let check_only = args.iter().any(|arg| arg == "--check");
+    args.retain(|arg| arg != "--check");
+    let path = args.pop().unwrap_or("./src/messages.rs".to_string());
+
+    let read_file = match read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(error) => {
+            eprintln!("{}: error: {}", path, error);
+            return ExitCode::FAILURE;
+        }
+    };
+    let res = match syn::parse_file(&read_file) {
+        Ok(res) => res,
+        Err(error) => {
+            let start = error.span().start();
+            eprintln!("{}:{}:{}: error: {}", path, start.line, start.column + 1, error);
+            return ExitCode::FAILURE;
+        }
+    };
+    if !check(&path, &res.items) {
+        return ExitCode::FAILURE;
+    }
+    if !check_only {
+        print_impls(&res.items);
+    }
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn;
+use syn::spanned::Spanned;
+use syn::Attribute;
+use syn::Error;
+use syn::Expr;
+use syn::Fields;
+use syn::Ident;
+use syn::Item;
+use syn::Meta;
+use syn::MetaNameValue;
+use syn::Type;
+
+use crate::common::{find_attr_by_name_type, find_field_reference};
+
+/// Keys of the doc comment attributes the derives understand
+const ATTRIBUTES: &[&str] = &[
+    "id",
+    "id_type",
+    "length_after_id",
+    "prepend_length",
+    "prepend_length_offset",
+    "bits",
+    "count",
+    "count_prefix",
+    "element_length",
+    "bytes_after",
+    "variant_by",
+    "present_if",
+    "name",
+    "unit",
+    "scale",
+    "names",
+    "describe",
+];
+
+/// Types a length or an element count is packed as
+const LENGTH_TYPES: &[&str] = &["u8", "u16", "u32", "u64"];
+
+/// Doc comment attribute, e.g. `/// bits = 12`
+struct DocAttribute {
+    key: Ident,
+    value: Expr,
+
+    /// The doc comment line
+    attr: Attribute,
+}
+
+/// Doc comment lines of the form `key = value`, other lines are documentation
+fn doc_attributes(attrs: &[Attribute]) -> Vec<DocAttribute> {
+    attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(doc) if doc.path.is_ident("doc") => match &doc.value {
+                Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. }) => {
+                    let value = syn::parse_str::<MetaNameValue>(&lit.value()).ok()?;
+                    Some(DocAttribute {
+                        key: value.path.get_ident()?.clone(),
+                        value: value.value,
+                        attr: attr.clone(),
+                    })
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Id with integers as their value, so that `0x01` and `1` are the same
+fn normalized_id(id: &TokenStream) -> String {
+    fn normalize(expr: &Expr) -> String {
+        match expr {
+            Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(int), .. }) => int
+                .base10_parse::<u128>()
+                .map(|value| value.to_string())
+                .unwrap_or_else(|_| int.to_string()),
+            Expr::Call(call) => format!(
+                "{}({})",
+                call.func.to_token_stream(),
+                call.args.iter().map(normalize).collect::<Vec<_>>().join(", ")
+            ),
+            expr => expr.to_token_stream().to_string(),
+        }
+    }
+    match syn::parse2::<Expr>(id.clone()) {
+        Ok(expr) => normalize(&expr),
+        Err(_) => id.to_string(),
+    }
+}
+
+/// Problems found in the items, reported together
+#[derive(Default)]
+struct Problems(Vec<Error>);
+
+impl Problems {
+    fn push(&mut self, span: impl Spanned, message: impl std::fmt::Display) {
+        self.0.push(Error::new(span.span(), message));
+    }
+
+    /// Attributes with an unknown key, or a value of the wrong kind
+    fn check_attributes(&mut self, attrs: &[Attribute]) {
+        for doc in doc_attributes(attrs) {
+            let key = doc.key.to_string();
+            if !ATTRIBUTES.contains(&key.as_str()) {
+                self.push(&doc.attr, format!("Unknown attribute `{}`, expected one of {}", key, ATTRIBUTES.join(", ")));
+                continue;
+            }
+            let type_name = match &doc.value {
+                Expr::Path(path) => path.path.get_ident().map(|ident| ident.to_string()),
+                _ => None,
+            };
+            match key.as_str() {
+                "prepend_length" | "length_after_id" | "count_prefix"
+                    if !type_name.as_deref().is_some_and(|name| LENGTH_TYPES.contains(&name)) =>
+                {
+                    self.push(&doc.attr, format!("`{}` must be one of {}", key, LENGTH_TYPES.join(", ")));
+                }
+                "bits" if !matches!(&doc.value, Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(bits), .. }) if bits.base10_parse::<u32>().is_ok_and(|bits| bits > 0)) => {
+                    self.push(&doc.attr, "`bits` must be a positive integer");
+                }
+                "describe" if !type_name.as_deref().is_some_and(|name| ["hex", "skip", "flags"].contains(&name)) => {
+                    self.push(&doc.attr, "`describe` must be one of hex, skip, flags");
+                }
+                _ => {}
+            }
+        }
+        let keys = doc_attributes(attrs).into_iter().map(|doc| doc.key.to_string()).collect::<Vec<_>>();
+        if keys.iter().any(|key| key == "prepend_length_offset") && !keys.iter().any(|key| key == "prepend_length") {
+            self.push(&attrs[0], "`prepend_length_offset` needs `prepend_length`");
+        }
+    }
+
+    /// Field attributes, and that the bit fields end on a byte
+    fn check_fields(&mut self, fields: &Fields) {
+        let mut names: Vec<Ident> = Vec::new();
+        let mut bits = 0;
+        let mut last_bit_field = None;
+        for field in fields {
+            let docs = doc_attributes(&field.attrs);
+            let find = |key: &str| docs.iter().find(|doc| doc.key == key);
+
+            match find("bits").map(|doc| &doc.value) {
+                Some(Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(value), .. })) => {
+                    bits += value.base10_parse::<u32>().unwrap_or(0);
+                    last_bit_field = Some(field.clone());
+                    if let Some(doc) = find("prepend_length") {
+                        self.push(&doc.attr, "`prepend_length` can't be used on a bit field");
+                    }
+                }
+                _ => self.check_bits_end(&mut bits, &last_bit_field),
+            }
+            self.check_attributes(&field.attrs);
+
+            // Fields are unpacked in order, so only the earlier ones are known
+            for key in ["count", "element_length", "variant_by"] {
+                if let Some(reference) = find_field_reference(&field.attrs, key) {
+                    let is_number = matches!(find(key).map(|doc| &doc.value), Some(Expr::Lit(_)));
+                    if !is_number && !names.contains(&reference) {
+                        self.push(&find(key).unwrap().attr, format!("`{}` is not a field before this one", reference));
+                    }
+                }
+            }
+            if let Some(doc) = find("present_if") {
+                let is_option = matches!(&field.ty, Type::Path(path) if path.path.segments.last().is_some_and(|segment| segment.ident == "Option"));
+                if !is_option {
+                    self.push(&doc.attr, "`present_if` needs an `Option` field");
+                }
+            }
+            if let Some(name) = &field.ident {
+                names.push(name.clone());
+            }
+        }
+        self.check_bits_end(&mut bits, &last_bit_field);
+    }
+
+    /// Bit fields before a byte field or the end must fill whole bytes
+    fn check_bits_end(&mut self, bits: &mut u32, last_bit_field: &Option<syn::Field>) {
+        if !bits.is_multiple_of(8) {
+            if let Some(field) = last_bit_field {
+                self.push(field, format!("Bit fields end at bit {}, not on a byte", bits));
+            }
+        }
+        *bits = 0;
+    }
+
+    fn check_item(&mut self, item: &Item) {
+        match item {
+            Item::Struct(istruct) => {
+                self.check_attributes(&istruct.attrs);
+                if let Fields::Unit = istruct.fields {
+                    if !doc_attributes(&istruct.attrs).iter().any(|doc| doc.key == "id") {
+                        self.push(&istruct.ident, "Missing `id` attribute, required for unit structs");
+                    }
+                    if find_attr_by_name_type(&istruct.attrs, "id_type").is_none() {
+                        self.push(&istruct.ident, "Missing `id_type` attribute, required for unit structs");
+                    }
+                }
+                self.check_fields(&istruct.fields);
+            }
+            Item::Enum(ienum) => {
+                self.check_attributes(&ienum.attrs);
+                let enum_name = &ienum.ident;
+                // Types like `Vec<u8>` aren't expressions, so look for a type
+                if find_attr_by_name_type(&ienum.attrs, "id_type").is_none() {
+                    self.push(enum_name, "Missing `id_type` attribute, required for enums");
+                }
+                let mut ids = HashMap::new();
+                for (index, variant) in ienum.variants.iter().enumerate() {
+                    self.check_attributes(&variant.attrs);
+                    self.check_fields(&variant.fields);
+                    let id = doc_attributes(&variant.attrs)
+                        .into_iter()
+                        .find(|doc| doc.key == "id")
+                        .map(|doc| doc.value)
+                        .or_else(|| variant.discriminant.as_ref().map(|(_, id)| id.clone()));
+                    match id {
+                        None => self.push(&variant.ident, format!("No id found for variant `{}`, add `/// id = ...` or a discriminant", variant.ident)),
+                        Some(Expr::Infer(_)) => {
+                            // Passthrough swallows any id, variants after it would never be decoded
+                            if index != ienum.variants.len() - 1 {
+                                self.push(&variant.ident, format!("Passthrough variant `{}` must be the last variant of {}", variant.ident, enum_name));
+                            }
+                            if variant.fields.is_empty() {
+                                self.push(&variant.ident, format!("Passthrough variant `{}` needs a field for the id", variant.ident));
+                            }
+                        }
+                        // Only the first variant with the same id would ever be decoded
+                        Some(id) => {
+                            if let Some(other) = ids.insert(normalized_id(&id.to_token_stream()), variant.ident.clone()) {
+                                self.push(
+                                    &variant.ident,
+                                    format!("Variants `{}` and `{}` of {} have the same id {}", other, variant.ident, enum_name, id.to_token_stream()),
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Checks the doc comment attributes of the items, with all the problems in
+/// one error
+pub fn validate(items: &[Item]) -> Result<(), Error> {
+    let mut problems = Problems::default();
+    for item in items {
+        problems.check_item(item);
+    }
+    let mut problems = problems.0.into_iter();
+    match problems.next() {
+        Some(mut error) => {
+            error.extend(problems);
+            Err(error)
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+
+    fn problems(input: TokenStream) -> Vec<String> {
+        let file = syn::parse2::<syn::File>(input).unwrap();
+        match validate(&file.items) {
+            Ok(()) => vec![],
+            Err(error) => error.into_iter().map(|error| error.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_valid() {
+        let input = quote! {
+            /// id_type = u8
+            /// length_after_id = u8
+            enum Command {
+                /// name = "Reset"
+                /// id = 0x01
+                Reset,
+                Other = 0x02,
+                /// id = _
+                Unknown(u8, Vec<u8>),
+            }
+
+            struct Acl {
+                /// bits = 12
+                handle: u16,
+                /// bits = 4
+                flags: u8,
+                count: u8,
+                /// count = count
+                values: Vec<u16>,
+                /// prepend_length = u16
+                /// prepend_length_offset = -2
+                msg: Message,
+            }
+        };
+        assert_eq!(problems(input), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_all_problems() {
+        let input = quote! {
+            enum Broken {
+                /// id = 0x01
+                Foo,
+                /// id = 1
+                Bar,
+                /// id = _
+                Reserved(u8),
+                Baz,
+            }
+
+            struct Bits {
+                /// bits = 3
+                a: u8,
+                /// prepend_length = u24
+                b: Vec<u8>,
+                /// bits = 4
+                /// prepend_lenght = u8
+                c: u8,
+                /// element_length = later
+                d: Vec<Data>,
+                later: u8,
+                /// present_if = later == 1
+                e: u8,
+            }
+        };
+        assert_eq!(
+            problems(input),
+            vec![
+                "Missing `id_type` attribute, required for enums",
+                "Variants `Foo` and `Bar` of Broken have the same id 1",
+                "Passthrough variant `Reserved` must be the last variant of Broken",
+                "No id found for variant `Baz`, add `/// id = ...` or a discriminant",
+                "Bit fields end at bit 3, not on a byte",
+                "`prepend_length` must be one of u8, u16, u32, u64",
+                "Unknown attribute `prepend_lenght`, expected one of id, id_type, length_after_id, \
+                 prepend_length, prepend_length_offset, bits, count, count_prefix, element_length, \
+                 bytes_after, variant_by, present_if, name, unit, scale, names, describe",
+                "Bit fields end at bit 4, not on a byte",
+                "`later` is not a field before this one",
+                "`present_if` needs an `Option` field",
+            ]
+        );
+    }
+}
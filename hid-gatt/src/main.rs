@@ -1,38 +1,90 @@
-use core::panic;
 use std::{
     cell::RefCell,
     collections::{BTreeSet, HashMap, HashSet, VecDeque},
     io::{BufReader, BufWriter},
     ops::Add,
-    os::windows::process,
     rc::{Rc, Weak},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
 use bt_only_headers::{
     hcimanager::{AppMsg, HciManager, MsgProcessor},
     messages::H4Packet,
-    socket::{self, MockSocket, Socket},
+    middleware::{Layered, Logger},
+    socket::{self, MockSocket, Socket, SocketError},
 };
 
+/// Longest wait for a packet, the signals are handled at least this often
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 fn main() {
+    env_logger::init();
     let mut socket = MockSocket::new(VecDeque::new());
     let mut queue: VecDeque<AppMsg> = vec![].into();
-    let mut mgr = HciManager::new().unwrap();
-    while let Some(packet) = socket.read().unwrap() {
-        queue.push_front(AppMsg::Recv(packet));
+    let mut mgr = Layered::new(HciManager::new().unwrap()).with(Logger::default());
+
+    // Ctrl-C and service stop shut down the controller before exiting
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_handler = stop.clone();
+    ctrlc::set_handler(move || stop_handler.store(true, Ordering::SeqCst))
+        .expect("Setting the signal handler failed");
+    let mut shutting_down = false;
+
+    // SIGUSR1 dumps the state of the manager, e.g. `pkill -USR1 hid-gatt`,
+    // within `POLL_INTERVAL` also when no packets come
+    let dump = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGUSR1, dump.clone())
+        .expect("Setting the signal handler failed");
+
+    'main: loop {
+        if dump.swap(false, Ordering::SeqCst) {
+            match serde_json::to_string_pretty(&mgr.inner().snapshot()) {
+                Ok(json) => eprintln!("{}", json),
+                Err(e) => log::error!("Serializing the snapshot failed: {}", e),
+            }
+        }
+        if stop.load(Ordering::SeqCst) && !shutting_down {
+            shutting_down = true;
+            queue.push_back(AppMsg::Shutdown);
+        } else {
+            // Wait for a packet until the next deadline of the manager, then
+            // tick it whether a packet came or not
+            let timeout = mgr
+                .inner()
+                .time_to_next_deadline()
+                .map_or(POLL_INTERVAL, |timeout| timeout.min(POLL_INTERVAL));
+            match socket.read_timeout(timeout) {
+                Ok(Some(packet)) => queue.push_back(AppMsg::Recv(packet)),
+                Ok(None) => break,
+                Err(SocketError::Timeout) => {}
+                Err(e) => {
+                    log::error!("Reading from the socket failed: {}", e);
+                    break;
+                }
+            }
+            queue.push_back(AppMsg::Tick);
+        }
         while let Some(msg) = queue.pop_front() {
-            // Process the message
-            queue.append(&mut mgr.process(msg.clone()).unwrap().into());
+            if msg == AppMsg::ShutdownComplete {
+                break 'main;
+            }
+
+            // Process the message, failing connections are disconnected by
+            // the manager so only this message is lost
+            match mgr.process(msg.clone()) {
+                Ok(msgs) => queue.extend(msgs),
+                Err(e) => log::error!("Processing {:?} failed: {}", msg, e),
+            }
 
             // Handle the message in main
-            match msg {
-                AppMsg::Send(packet) => {
-                    socket.write(packet).unwrap();
-                }
-                AppMsg::Recv(packet) => {
-                    panic!("Unexpected packet: {:?}", packet);
+            if let AppMsg::Send(packet) = msg {
+                if let Err(e) = socket.write(packet) {
+                    log::error!("Writing to the socket failed: {}", e);
                 }
-                _ => {}
             }
         }
     }
@@ -0,0 +1,234 @@
+use std::time::Duration;
+
+use crate::messages::*;
+use crate::packer::FromToPacket;
+
+/// Advertising type of `LeSetAdvertisingParameters`, connectable undirected
+const ADV_IND: u8 = 0x00;
+
+/// Advertising type of `LeSetAdvertisingParameters`, connectable directed
+/// with low duty cycle
+const ADV_DIRECT_IND_LOW_DUTY: u8 = 0x04;
+
+/// How advertising is restarted after a disconnect, reset or resume
+///
+/// Advertising starts with the fast interval, and falls back to the slow
+/// interval after `fast_timeout`. Intervals are in 0.625 ms units.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdvertisingPolicy {
+    /// Used as is, except for the intervals and the directed advertising
+    pub parameters: LeSetAdvertisingParameters,
+
+    pub fast_interval: u16,
+    pub fast_timeout: Duration,
+
+    pub slow_interval: u16,
+
+    /// Advertising stops after the slow period, `None` advertises forever
+    pub slow_timeout: Option<Duration>,
+
+    /// Fast period is directed to the last bonded host, if there is one
+    pub directed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdvertisingMsg {
+    /// Stop advertising until resumed
+    Stop,
+
+    /// Start advertising again with the fast interval
+    Resume,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Off,
+    Fast,
+    Slow,
+}
+
+/// Decides when advertising is enabled, owned by `HciManager`
+///
+/// Without a policy the advertising parameters set by the init commands are
+/// kept, and advertising is only re-enabled.
+pub(crate) struct AdvertisingManager {
+    policy: Option<AdvertisingPolicy>,
+
+    /// Advertising is re-enabled after a connection until there are this many
+    /// connections
+    pub(crate) max_connections: usize,
+
+    /// Cleared when the application stops advertising
+    running: bool,
+
+    /// Controller is advertising, as far as the sent commands tell
+    advertising: bool,
+    phase: Phase,
+    deadline: Option<Duration>,
+
+    /// Address type and address of the last host that encrypted the link
+    bonded_host: Option<(u8, BdAddr)>,
+}
+
+impl AdvertisingManager {
+    pub(crate) fn new() -> Self {
+        AdvertisingManager {
+            policy: None,
+            max_connections: 1,
+            running: true,
+            advertising: false,
+            phase: Phase::Off,
+            deadline: None,
+            bonded_host: None,
+        }
+    }
+
+    pub(crate) fn set_policy(&mut self, policy: AdvertisingPolicy) {
+        self.policy = Some(policy);
+    }
+
+    pub(crate) fn set_bonded_host(&mut self, address_type: AddressType, address: BdAddr) {
+        self.bonded_host = Some((address_type.to_bytes()[0], address));
+    }
+
+    pub(crate) fn is_advertising(&self) -> bool {
+        self.advertising
+    }
+
+    pub(crate) fn deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    /// Keep track of advertising enabled by others, e.g. the init commands
+    pub(crate) fn command_sent(&mut self, command: &HciCommand) {
+        if let HciCommand::LeSetAdvertisingEnable(enable) = command {
+            self.advertising = *enable;
+            if !enable {
+                self.phase = Phase::Off;
+                self.deadline = None;
+            }
+        }
+    }
+
+    /// Controller is (re)initialized, returns the init commands to send
+    ///
+    /// They take care of advertising unless there is a policy, which then
+    /// starts it in place of their `LeSetAdvertisingEnable`.
+    pub(crate) fn init(
+        &mut self,
+        init_commands: &[HciCommand],
+        now: Duration,
+        connections: usize,
+    ) -> Vec<HciCommand> {
+        self.advertising = false;
+        self.phase = Phase::Off;
+        self.deadline = None;
+        if self.policy.is_none() {
+            for command in init_commands {
+                self.command_sent(command);
+            }
+            return init_commands.to_vec();
+        }
+        let mut commands = init_commands
+            .iter()
+            .filter(|command| !matches!(command, HciCommand::LeSetAdvertisingEnable(_)))
+            .cloned()
+            .collect::<Vec<_>>();
+        commands.append(&mut self.start(now, connections));
+        commands
+    }
+
+    /// Controller stops advertising when a connection is created
+    pub(crate) fn connected(&mut self, now: Duration, connections: usize) -> Vec<HciCommand> {
+        self.advertising = false;
+        self.phase = Phase::Off;
+        self.deadline = None;
+        self.start(now, connections)
+    }
+
+    pub(crate) fn disconnected(&mut self, now: Duration, connections: usize) -> Vec<HciCommand> {
+        if self.advertising {
+            return vec![];
+        }
+        self.start(now, connections)
+    }
+
+    pub(crate) fn stop(&mut self) -> Vec<HciCommand> {
+        self.running = false;
+        self.disable()
+    }
+
+    pub(crate) fn resume(&mut self, now: Duration, connections: usize) -> Vec<HciCommand> {
+        self.running = true;
+        if self.advertising && self.phase == Phase::Fast {
+            return vec![];
+        }
+        self.start(now, connections)
+    }
+
+    /// Move to the next phase once the current one is over
+    pub(crate) fn tick(&mut self, now: Duration) -> Vec<HciCommand> {
+        if self.deadline.is_none_or(|deadline| deadline > now) {
+            return vec![];
+        }
+        match self.phase {
+            Phase::Fast => self.enter(Phase::Slow, now),
+            _ => self.disable(),
+        }
+    }
+
+    fn start(&mut self, now: Duration, connections: usize) -> Vec<HciCommand> {
+        if !self.running || connections >= self.max_connections {
+            return vec![];
+        }
+        if self.policy.is_none() {
+            self.advertising = true;
+            return vec![HciCommand::LeSetAdvertisingEnable(true)];
+        }
+        self.enter(Phase::Fast, now)
+    }
+
+    fn disable(&mut self) -> Vec<HciCommand> {
+        self.phase = Phase::Off;
+        self.deadline = None;
+        if !self.advertising {
+            return vec![];
+        }
+        self.advertising = false;
+        vec![HciCommand::LeSetAdvertisingEnable(false)]
+    }
+
+    fn enter(&mut self, phase: Phase, now: Duration) -> Vec<HciCommand> {
+        let Some(policy) = &self.policy else {
+            return vec![];
+        };
+        let mut parameters = policy.parameters.clone();
+        let (interval, timeout) = match phase {
+            Phase::Fast => (policy.fast_interval, Some(policy.fast_timeout)),
+            _ => (policy.slow_interval, policy.slow_timeout),
+        };
+        parameters.advertising_interval_min = interval;
+        parameters.advertising_interval_max = interval;
+        parameters.advertising_type = ADV_IND;
+        if let (Phase::Fast, true, Some((address_type, address))) =
+            (phase, policy.directed, &self.bonded_host)
+        {
+            parameters.advertising_type = ADV_DIRECT_IND_LOW_DUTY;
+            parameters.peer_address_type = *address_type;
+            parameters.peer_address = address.clone();
+        }
+
+        // Parameters can't be changed while advertising
+        let mut commands = vec![];
+        if self.advertising {
+            commands.push(HciCommand::LeSetAdvertisingEnable(false));
+        }
+        commands.push(HciCommand::LeSetAdvertisingParameters(parameters));
+        commands.push(HciCommand::LeSetAdvertisingEnable(true));
+
+        self.advertising = true;
+        self.phase = phase;
+        self.deadline = timeout.map(|timeout| now + timeout);
+        commands
+    }
+}
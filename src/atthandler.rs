@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use bt_hci::data::AclPacket;
 
 use crate::c1::c1_rev;
@@ -8,12 +10,19 @@ use crate::packer::*;
 
 use crate::hcimanager::HciError;
 use crate::hcimanager::MsgProcessor;
+use crate::routing::CID_ATT;
+use crate::routing::Interest;
 
 /// Take raw connection handle and returns PairedConnection, or SmpPairingFailure
 pub struct AttHandler {
     connection_handle: ConnectionHandle,
     server_mtu: u16,
     peer_mtu: Option<u16>,
+    encrypted: bool,
+
+    /// Report lengths by attribute handle, for the reports last notified with
+    /// keys down
+    pressed: BTreeMap<u16, usize>,
 }
 
 impl AttHandler {
@@ -22,6 +31,8 @@ impl AttHandler {
             connection_handle: lecon.connection_handle.clone(),
             server_mtu: 244,
             peer_mtu: None,
+            encrypted: false,
+            pressed: BTreeMap::new(),
         }
     }
 
@@ -43,16 +54,27 @@ impl AttHandler {
         match packet {
             AttPdu::ExchangeMtuRequest(peer_mtu) => {
                 self.peer_mtu = Some(peer_mtu);
-                return self.produce_att(vec![AttPdu::ExchangeMtuResponse(self.server_mtu)]);
+                let mut msgs =
+                    self.produce_att(vec![AttPdu::ExchangeMtuResponse(self.server_mtu)])?;
+                msgs.push(self.mtu_changed(peer_mtu));
+                Ok(msgs)
             }
             AttPdu::ExchangeMtuResponse(peer_mtu) => {
                 self.peer_mtu = Some(peer_mtu);
-                return Ok(vec![]);
+                Ok(vec![self.mtu_changed(peer_mtu)])
             }
             _ => Ok(vec![]),
         }
     }
 
+    /// ATT_MTU is the smaller of the two MTUs
+    fn mtu_changed(&self, peer_mtu: u16) -> AppMsg {
+        AppMsg::MtuChanged(
+            self.connection_handle.clone(),
+            self.server_mtu.min(peer_mtu),
+        )
+    }
+
     fn process_event(&mut self, packet: HciEvent) -> Result<Vec<AppMsg>, HciError> {
         use EvtLeMeta::*;
         use HciEvent::*;
@@ -62,14 +84,29 @@ impl AttHandler {
                 // TODO: ..
                 Ok(vec![])
             }
-            EncryptionChange(_) => {
-                // TODO: First HID Report Characteristic Write is allowed after EncryptionChange
+            EncryptionChange(e) if e.connection_handle == self.connection_handle => {
+                // HID reports are allowed after EncryptionChange
+                self.encrypted = e.status == HciStatus::Success && e.encryption_enabled;
                 Ok(vec![])
             }
             _ => Ok(vec![]),
         }
     }
 
+    /// Notify empty reports so that the host doesn't keep the keys down
+    fn release_keys(&mut self) -> Result<Vec<AppMsg>, HciError> {
+        let released = std::mem::take(&mut self.pressed)
+            .into_iter()
+            .map(|(handle, len)| {
+                AttPdu::HandleValueNotification(AttHandleValueNotification {
+                    handle,
+                    value: vec![0; len],
+                })
+            })
+            .collect();
+        self.produce_att(released)
+    }
+
     fn start_negotiate_mtu(&mut self) -> Result<Vec<AppMsg>, HciError> {
         // Start negotiating MTU size
         let mtu_request = AppMsg::Send(H4Packet::Acl(HciAcl {
@@ -97,10 +134,33 @@ impl MsgProcessor for AttHandler {
             // HCI Event handler
             AppMsg::Recv(H4Packet::Event(event)) => self.process_event(event),
 
+            AppMsg::HidReport(connection_handle, report)
+                if connection_handle == self.connection_handle && self.encrypted =>
+            {
+                if report.value.iter().all(|b| *b == 0) {
+                    self.pressed.remove(&report.handle);
+                } else {
+                    self.pressed.insert(report.handle, report.value.len());
+                }
+                self.produce_att(vec![AttPdu::HandleValueNotification(report)])
+            }
+
+            AppMsg::Shutdown => self.release_keys(),
+
             // Other messages
             _ => Ok(vec![]),
         }
     }
+
+    fn interest(&self) -> Interest {
+        Interest {
+            connection: Some(self.connection_handle.clone()),
+            channels: Some(vec![CID_ATT]),
+            // Encryption Change and LE Meta
+            events: Some(vec![0x08, 0x3E]),
+            opcodes: Some(vec![]),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -154,6 +214,7 @@ mod tests {
                 msg: L2CapMessage::Att(AttPdu::ExchangeMtuResponse(244)),
             }))
         );
+        assert_eq!(res2[1], AppMsg::MtuChanged(ConnectionHandle(64), 244));
         let res2 = att_handler
             .process(AppMsg::Recv(H4Packet::Acl(HciAcl {
                 connection_handle: ConnectionHandle(64),
@@ -163,5 +224,6 @@ mod tests {
             })))
             .unwrap();
         assert_eq!(att_handler.peer_mtu, Some(400));
+        assert_eq!(res2, vec![AppMsg::MtuChanged(ConnectionHandle(64), 244)]);
     }
 }
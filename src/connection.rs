@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::messages::*;
+
+/// LE security mode 1 levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize)]
+pub enum SecurityLevel {
+    /// Level 1, no encryption
+    #[default]
+    NoSecurity,
+    /// Level 2, encrypted after unauthenticated pairing (Just Works)
+    Unauthenticated,
+    /// Level 3, encrypted after authenticated pairing
+    Authenticated,
+    /// Level 4, encrypted after authenticated LE Secure Connections pairing
+    SecureConnections,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionParameters {
+    /// Connection interval in 1.25 ms units
+    pub interval: u16,
+    pub latency: u16,
+    /// Supervision timeout in 10 ms units
+    pub supervision_timeout: u16,
+}
+
+/// Progress of the legacy pairing, as seen from the SMP traffic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum PairingStage {
+    #[default]
+    NotStarted,
+    /// Pairing Request and Response
+    FeatureExchange,
+    /// Confirm values
+    Confirm,
+    /// Random values, the link is encrypted with the STK next
+    KeyGeneration,
+    /// Link is encrypted, keys are being distributed
+    KeyDistribution,
+    Complete,
+    Failed,
+}
+
+/// ATT_MTU before the MTU exchange
+pub const DEFAULT_ATT_MTU: u16 = 23;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Connection {
+    pub handle: ConnectionHandle,
+    pub peer_address: BdAddr,
+    pub peer_address_type: AddressType,
+    pub role: Role,
+    pub parameters: ConnectionParameters,
+    pub security_level: SecurityLevel,
+    pub mtu: u16,
+    pub pairing: PairingStage,
+
+    /// ACL packets sent to the controller and not yet reported completed
+    pub acl_in_flight: u16,
+}
+
+impl Connection {
+    pub fn new(e: &LeConnectionComplete) -> Self {
+        Connection {
+            handle: e.connection_handle.clone(),
+            peer_address: e.peer_address.clone(),
+            peer_address_type: e.peer_address_type,
+            role: e.role,
+            parameters: ConnectionParameters {
+                interval: e.connection_interval,
+                latency: e.peripheral_latency,
+                supervision_timeout: e.supervision_timeout,
+            },
+            security_level: SecurityLevel::NoSecurity,
+            mtu: DEFAULT_ATT_MTU,
+            pairing: PairingStage::NotStarted,
+            acl_in_flight: 0,
+        }
+    }
+
+    /// Follow the pairing from the SMP PDUs of either direction
+    pub(crate) fn smp_seen(&mut self, pdu: &SmpPdu) {
+        self.pairing = match pdu {
+            SmpPdu::PairingRequest(_) | SmpPdu::PairingResponse(_) => PairingStage::FeatureExchange,
+            SmpPdu::PairingConfirmation(_) => PairingStage::Confirm,
+            SmpPdu::PairingRandom(_) => PairingStage::KeyGeneration,
+            SmpPdu::PairingFailed(_) => PairingStage::Failed,
+            // Last key the peripheral distributes
+            SmpPdu::CentralIdentification(_) => PairingStage::Complete,
+            _ => self.pairing,
+        };
+    }
+}
+
+/// Open connections keyed by the connection handle
+///
+/// Maintained by `HciManager`, the application gets read-only access.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionRegistry {
+    connections: BTreeMap<ConnectionHandle, Connection>,
+}
+
+impl ConnectionRegistry {
+    pub fn new() -> Self {
+        ConnectionRegistry::default()
+    }
+
+    pub fn get(&self, handle: &ConnectionHandle) -> Option<&Connection> {
+        self.connections.get(handle)
+    }
+
+    pub fn contains(&self, handle: &ConnectionHandle) -> bool {
+        self.connections.contains_key(handle)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Connection> {
+        self.connections.values()
+    }
+
+    pub fn handles(&self) -> Vec<ConnectionHandle> {
+        self.connections.keys().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    pub(crate) fn insert(&mut self, connection: Connection) {
+        self.connections
+            .insert(connection.handle.clone(), connection);
+    }
+
+    pub(crate) fn get_mut(&mut self, handle: &ConnectionHandle) -> Option<&mut Connection> {
+        self.connections.get_mut(handle)
+    }
+
+    pub(crate) fn remove(&mut self, handle: &ConnectionHandle) -> Option<Connection> {
+        self.connections.remove(handle)
+    }
+
+    pub(crate) fn clear(&mut self) -> Vec<Connection> {
+        std::mem::take(&mut self.connections)
+            .into_values()
+            .collect()
+    }
+}
@@ -0,0 +1,328 @@
+//! Human readable, btmon-like descriptions of the messages
+//!
+//! `#[derive(Describe)]` writes every field on its own indented line, enum
+//! variants by name and id, e.g.
+//!
+//! ```text
+//! HCI Event (0x04)
+//!   LE Meta Event (0x3e)
+//!     LE Connection Complete (0x01)
+//!       Status: Success (0x00)
+//!       Handle: 64
+//!       Connection interval: 48.75 msec (0x0027)
+//! ```
+//!
+//! The derive is driven by doc comment attributes:
+//!
+//! - `name = "LE Meta Event"` on a variant or field replaces the name made of
+//!   its identifier
+//! - `unit = "msec"` and `scale = 1.25` on integer fields, written as
+//!   `48.75 msec (0x0027)`
+//! - `names = HciCommand` on an id field writes the name of the variant with
+//!   the id, e.g. `Reset (0x03|0x0003)`
+//! - `describe = hex` writes the field in hex, `describe = skip` leaves it out
+//! - `describe = flags` on a struct of `bool` bit fields writes the names of
+//!   the set flags on one line
+//!
+//! Bytes of `Vec<u8>` and `[u8; N]` fields are hex dumped. Enums also need
+//! `#[derive(FromToPacket)]` for their ids.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::messages::{BdAddr, OpCode};
+use crate::packer::FixedSizeUtf8;
+
+pub use bitgen_derive::Describe;
+
+/// Bytes on one line of a hex dump
+const HEX_DUMP_WIDTH: usize = 16;
+
+pub trait Describe {
+    /// Writes the value, nested values on the indented lines below
+    fn describe(&self, d: &mut Describer) -> fmt::Result;
+
+    /// Writes the value in hex, used for ids and `describe = hex` fields
+    fn describe_hex(&self, d: &mut Describer) -> fmt::Result {
+        self.describe(d)
+    }
+
+    /// Writes the elements of a `Vec` or an array, one per line by index
+    fn describe_slice(items: &[Self], d: &mut Describer) -> fmt::Result
+    where
+        Self: Sized,
+    {
+        for (index, item) in items.iter().enumerate() {
+            d.field(index, |d| item.describe(d))?;
+        }
+        Ok(())
+    }
+}
+
+/// Names of the enum variants by their id, for `names = ...` fields
+pub trait VariantName<T> {
+    fn variant_name(id: &T) -> Option<&'static str>;
+}
+
+/// Writes the indented lines of a description
+pub struct Describer<'a, 'b> {
+    f: &'a mut Formatter<'b>,
+    indent: usize,
+
+    /// Indentation of the current line, nested lines are indented from it
+    line_indent: usize,
+
+    /// Next text starts on a new line
+    line_pending: bool,
+
+    /// Next text on the line follows a field name
+    space_pending: bool,
+
+    /// Nothing is written yet, the first line needs no line break
+    empty: bool,
+}
+
+impl<'a, 'b> Describer<'a, 'b> {
+    pub fn new(f: &'a mut Formatter<'b>) -> Self {
+        Describer {
+            f,
+            indent: 0,
+            line_indent: 0,
+            line_pending: false,
+            space_pending: false,
+            empty: true,
+        }
+    }
+
+    fn new_line(&mut self) -> fmt::Result {
+        if !self.empty {
+            self.f.write_str("\n")?;
+        }
+        self.empty = false;
+        self.line_pending = false;
+        self.space_pending = false;
+        self.line_indent = self.indent;
+        write!(self.f, "{:width$}", "", width = self.indent * 2)
+    }
+
+    /// Writes on the current line
+    pub fn text(&mut self, args: fmt::Arguments) -> fmt::Result {
+        if self.line_pending {
+            self.new_line()?;
+        }
+        if self.space_pending {
+            self.space_pending = false;
+            self.f.write_str(" ")?;
+        }
+        self.empty = false;
+        self.f.write_fmt(args)
+    }
+
+    /// Writes `name: ` on a new line followed by the value, whose nested
+    /// values are indented below it
+    pub fn field(
+        &mut self,
+        name: impl Display,
+        describe: impl FnOnce(&mut Self) -> fmt::Result,
+    ) -> fmt::Result {
+        self.new_line()?;
+        write!(self.f, "{}:", name)?;
+        self.space_pending = true;
+        self.indent += 1;
+        let result = describe(self);
+        self.indent -= 1;
+        result
+    }
+
+    /// Writes the value on the indented lines below the current one
+    pub fn nested(&mut self, describe: impl FnOnce(&mut Self) -> fmt::Result) -> fmt::Result {
+        let indent = self.indent;
+        self.indent = self.line_indent + 1;
+        self.line_pending = true;
+        let result = describe(self);
+        self.line_pending = false;
+        self.indent = indent;
+        result
+    }
+
+    /// Writes the variant name followed by its id, e.g. `Success (0x00)`
+    pub fn variant<T: Describe>(&mut self, name: &str, id: &T) -> fmt::Result {
+        self.text(format_args!("{} (", name))?;
+        id.describe_hex(self)?;
+        self.text(format_args!(")"))
+    }
+
+    /// Writes the id with the name of the variant it belongs to, or only the
+    /// id if there is no such variant
+    pub fn named_id<E: VariantName<T>, T: Describe>(&mut self, id: &T) -> fmt::Result {
+        match E::variant_name(id) {
+            Some(name) => self.variant(name, id),
+            None => id.describe_hex(self),
+        }
+    }
+
+    /// Writes the value in the unit followed by the raw value, e.g.
+    /// `48.75 msec (0x0027)`
+    pub fn unit<T: Describe + Copy + Into<f64>>(
+        &mut self,
+        value: T,
+        scale: f64,
+        unit: &str,
+    ) -> fmt::Result {
+        self.text(format_args!("{} {} (", value.into() * scale, unit))?;
+        value.describe_hex(self)?;
+        self.text(format_args!(")"))
+    }
+
+    /// Writes the names of the set flags, e.g. `EncKey IdKey`
+    pub fn flags(&mut self, flags: &[(&str, bool)]) -> fmt::Result {
+        let mut set = flags.iter().filter(|(_, set)| *set).map(|(name, _)| name);
+        match set.next() {
+            Some(first) => {
+                self.text(format_args!("{}", first))?;
+                for name in set {
+                    self.text(format_args!(" {}", name))?;
+                }
+                Ok(())
+            }
+            None => self.text(format_args!("None")),
+        }
+    }
+
+    /// Writes the bytes in hex, longer dumps on the lines below
+    pub fn hex_dump(&mut self, bytes: &[u8]) -> fmt::Result {
+        if bytes.len() <= HEX_DUMP_WIDTH {
+            return self.hex_line(bytes);
+        }
+        self.nested(|d| {
+            for line in bytes.chunks(HEX_DUMP_WIDTH) {
+                d.line_pending = true;
+                d.hex_line(line)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn hex_line(&mut self, bytes: &[u8]) -> fmt::Result {
+        for (index, byte) in bytes.iter().enumerate() {
+            let separator = if index == 0 { "" } else { " " };
+            self.text(format_args!("{}{:02x}", separator, byte))?;
+        }
+        Ok(())
+    }
+}
+
+macro_rules! impl_describe {
+    ($type:ty) => {
+        impl Describe for $type {
+            fn describe(&self, d: &mut Describer) -> fmt::Result {
+                d.text(format_args!("{}", self))
+            }
+            fn describe_hex(&self, d: &mut Describer) -> fmt::Result {
+                d.text(format_args!(
+                    "0x{:0width$x}",
+                    self,
+                    width = 2 * std::mem::size_of::<$type>()
+                ))
+            }
+        }
+    };
+}
+
+impl_describe!(u16);
+impl_describe!(u32);
+impl_describe!(u64);
+impl_describe!(u128);
+impl_describe!(i8);
+impl_describe!(i16);
+impl_describe!(i32);
+impl_describe!(i64);
+impl_describe!(i128);
+
+impl Describe for u8 {
+    fn describe(&self, d: &mut Describer) -> fmt::Result {
+        d.text(format_args!("{}", self))
+    }
+    fn describe_hex(&self, d: &mut Describer) -> fmt::Result {
+        d.text(format_args!("0x{:02x}", self))
+    }
+    fn describe_slice(items: &[Self], d: &mut Describer) -> fmt::Result {
+        d.hex_dump(items)
+    }
+}
+
+impl Describe for bool {
+    fn describe(&self, d: &mut Describer) -> fmt::Result {
+        d.text(format_args!("{}", self))
+    }
+}
+
+impl<T: Describe> Describe for Vec<T> {
+    fn describe(&self, d: &mut Describer) -> fmt::Result {
+        T::describe_slice(self, d)
+    }
+}
+
+impl<T: Describe, const N: usize> Describe for [T; N] {
+    fn describe(&self, d: &mut Describer) -> fmt::Result {
+        T::describe_slice(self, d)
+    }
+}
+
+impl<T: Describe> Describe for Option<T> {
+    fn describe(&self, d: &mut Describer) -> fmt::Result {
+        match self {
+            Some(value) => value.describe(d),
+            None => d.text(format_args!("None")),
+        }
+    }
+    fn describe_hex(&self, d: &mut Describer) -> fmt::Result {
+        match self {
+            Some(value) => value.describe_hex(d),
+            None => d.text(format_args!("None")),
+        }
+    }
+}
+
+impl<A: Describe, B: Describe> Describe for (A, B) {
+    fn describe(&self, d: &mut Describer) -> fmt::Result {
+        self.0.describe_hex(d)?;
+        d.text(format_args!(", "))?;
+        self.1.describe_hex(d)
+    }
+}
+
+impl<const N: usize> Describe for FixedSizeUtf8<N> {
+    fn describe(&self, d: &mut Describer) -> fmt::Result {
+        d.text(format_args!("{:?}", self.get()))
+    }
+}
+
+/// Group and command as btmon writes them, e.g. `0x03|0x0003`
+impl Describe for OpCode {
+    fn describe(&self, d: &mut Describer) -> fmt::Result {
+        d.text(format_args!("0x{:02x}|0x{:04x}", self.1, self.0))
+    }
+}
+
+impl Display for OpCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.describe(&mut Describer::new(f))
+    }
+}
+
+/// Most significant byte first, e.g. `C0:11:22:33:44:55`
+impl Describe for BdAddr {
+    fn describe(&self, d: &mut Describer) -> fmt::Result {
+        for (index, byte) in self.0.iter().rev().enumerate() {
+            let separator = if index == 0 { "" } else { ":" };
+            d.text(format_args!("{}{:02X}", separator, byte))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for BdAddr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.describe(&mut Describer::new(f))
+    }
+}
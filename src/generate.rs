@@ -0,0 +1,139 @@
+//! Random values of the messages, for property tests
+//!
+//! `#[derive(Generate)]` makes values that pack and unpack back to
+//! themselves. It follows the same doc comment attributes as
+//! `#[derive(FromToPacket)]`:
+//!
+//! - `bits = N` fields get values that fit in the bits
+//! - passthrough variants (`id = _`) get ids no other variant has
+//! - fields referred by `count`, `element_length` and `variant_by` are set from
+//!   the field referring to them, e.g. `pair_length` from the size of an
+//!   element of `values`
+//! - `present_if` fields are `Some` only if the condition holds
+//! - elements of `element_length` fields differ but have the same size, each
+//!   made with `generate_like` of the first one
+
+use crate::packer::{FixedSizeUtf8, FromToPacket};
+use crate::random::Random;
+
+pub use bitgen_derive::Generate;
+
+/// Most elements in a generated `Vec`, enough to cover more than one element
+/// and small enough to keep the packets within their length prefixes
+const MAX_ELEMENTS: usize = 4;
+
+pub trait Generate: Sized {
+    /// Random value which packs and unpacks back to itself
+    fn generate(random: &mut dyn Random) -> Self;
+
+    /// Random value which fits in the bits of a `bits = N` field
+    fn generate_bits(random: &mut dyn Random, _bits: u32) -> Self {
+        Self::generate(random)
+    }
+
+    /// Random value which packs to as many bytes as `self`, e.g. a `Vec` of
+    /// the same length. Any value will do for types of a fixed size.
+    fn generate_like(&self, random: &mut dyn Random) -> Self {
+        Self::generate(random)
+    }
+}
+
+/// Elements of the same size, for `Vec` fields with an `element_length`
+pub fn generate_repeated<T: Generate + FromToPacket>(random: &mut dyn Random) -> Vec<T> {
+    let first = T::generate(random);
+    let length = first.to_bytes().len();
+    let mut elements = vec![first];
+    for _ in 1..1 + random.below(MAX_ELEMENTS) {
+        let element = elements[0].generate_like(random);
+        assert_eq!(
+            element.to_bytes().len(),
+            length,
+            "Element of a different size"
+        );
+        elements.push(element);
+    }
+    elements
+}
+
+/// Elements for `Vec` fields with a fixed `count`
+pub fn generate_count<T: Generate>(random: &mut dyn Random, count: usize) -> Vec<T> {
+    (0..count).map(|_| T::generate(random)).collect()
+}
+
+macro_rules! impl_generate {
+    ($type:ty) => {
+        impl Generate for $type {
+            fn generate(random: &mut dyn Random) -> Self {
+                let mut bytes = [0; std::mem::size_of::<$type>()];
+                random.fill(&mut bytes);
+                Self::from_le_bytes(bytes)
+            }
+            fn generate_bits(random: &mut dyn Random, bits: u32) -> Self {
+                match bits {
+                    0 => 0,
+                    bits if bits >= <$type>::BITS => Self::generate(random),
+                    bits => Self::generate(random) & ((1 << bits) - 1),
+                }
+            }
+        }
+    };
+}
+
+impl_generate!(u8);
+impl_generate!(u16);
+impl_generate!(u32);
+impl_generate!(u64);
+impl_generate!(u128);
+impl_generate!(i8);
+impl_generate!(i16);
+impl_generate!(i32);
+impl_generate!(i64);
+impl_generate!(i128);
+
+impl Generate for bool {
+    fn generate(random: &mut dyn Random) -> Self {
+        random.below(2) == 1
+    }
+}
+
+impl<T: Generate> Generate for Vec<T> {
+    fn generate(random: &mut dyn Random) -> Self {
+        let count = random.below(MAX_ELEMENTS + 1);
+        generate_count(random, count)
+    }
+    fn generate_like(&self, random: &mut dyn Random) -> Self {
+        self.iter()
+            .map(|element| element.generate_like(random))
+            .collect()
+    }
+}
+
+impl<T: Generate, const N: usize> Generate for [T; N] {
+    fn generate(random: &mut dyn Random) -> Self {
+        std::array::from_fn(|_| T::generate(random))
+    }
+    fn generate_like(&self, random: &mut dyn Random) -> Self {
+        std::array::from_fn(|index| self[index].generate_like(random))
+    }
+}
+
+impl<A: Generate, B: Generate> Generate for (A, B) {
+    fn generate(random: &mut dyn Random) -> Self {
+        (A::generate(random), B::generate(random))
+    }
+    fn generate_like(&self, random: &mut dyn Random) -> Self {
+        (self.0.generate_like(random), self.1.generate_like(random))
+    }
+}
+
+/// Letters and digits, as the zero padding ends the string
+impl<const N: usize> Generate for FixedSizeUtf8<N> {
+    fn generate(random: &mut dyn Random) -> Self {
+        const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+        let length = random.below(N.min(16) + 1);
+        let string = (0..length)
+            .map(|_| CHARS[random.below(CHARS.len())] as char)
+            .collect::<String>();
+        FixedSizeUtf8::new(&string)
+    }
+}
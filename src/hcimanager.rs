@@ -1,10 +1,12 @@
 use std::{
+    any::Any,
     cell::RefCell,
-    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Debug,
     io::{BufReader, BufWriter},
     ops::Add,
-    os::windows::process,
     rc::{Rc, Weak},
+    time::Duration,
 };
 
 const BLUETOOTH_BASE_UUID: &str = "00000000-0000-1000-8000-00805F9B34FB";
@@ -18,15 +20,79 @@ use crate::{
 };
 
 use crate::{
+    advertising::{AdvertisingManager, AdvertisingMsg, AdvertisingPolicy},
+    clock::{Clock, SystemClock},
+    connection::{
+        Connection, ConnectionParameters, ConnectionRegistry, PairingStage, SecurityLevel,
+    },
     pairinghandler::PairingHandler,
+    random::{Random, SystemRandom},
+    routing::Interest,
+    snapshot::{
+        CommandsSnapshot, ConnectionSnapshot, PendingCommandSnapshot, ProcessorSnapshot, Snapshot,
+        TimerSnapshot,
+    },
     socket::{Socket, SocketError},
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum HciError {
     SocketError(SocketError),
-    PacketError,
+    PacketError(crate::packer::PacketError),
     Unknown(String),
+
+    /// Command got no Command Complete or Command Status in time, retries
+    /// included
+    CommandTimeout(OpCode),
+
+    /// Peer or controller didn't follow the protocol
+    Protocol(ProtocolError),
+}
+
+impl std::fmt::Display for HciError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HciError::SocketError(e) => write!(f, "Socket error: {}", e),
+            HciError::PacketError(e) => write!(f, "Packet error: {}", e),
+            HciError::Unknown(e) => write!(f, "Unknown error: {}", e),
+            HciError::CommandTimeout(opcode) => write!(f, "Command {:?} timed out", opcode),
+            HciError::Protocol(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for HciError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    Hci,
+    L2cap,
+    Att,
+    Smp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolError {
+    /// `None` if the error is not about a connection
+    pub connection: Option<ConnectionHandle>,
+    pub layer: Layer,
+
+    /// Opcode of the offending PDU, or code of the event
+    pub pdu: Option<u8>,
+    pub reason: String,
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} protocol error", self.layer)?;
+        if let Some(connection) = &self.connection {
+            write!(f, " on connection 0x{:04X}", connection.0)?;
+        }
+        if let Some(pdu) = self.pdu {
+            write!(f, " in PDU 0x{:02X}", pdu)?;
+        }
+        write!(f, ": {}", self.reason)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -39,6 +105,116 @@ pub enum AppMsg {
     DisconnectComplete(ConnectionHandle),
     Pairing(ConnectionHandle),
     PairingComplete(ConnectionHandle),
+
+    /// Send the init sequence to the controller
+    InitHciManager,
+
+    /// Reset the controller and replay the init sequence, e.g. when the
+    /// controller stops responding
+    RecoverController,
+
+    /// Controller was reset, all connections were dropped
+    ConnectionsLost(Vec<ConnectionHandle>),
+
+    /// Check deadlines against the clock, should be sent periodically by the
+    /// main loop
+    Tick,
+
+    /// Command sent by the processor failed, delivered only to the processor
+    /// that issued the command
+    CommandFailed(HciError),
+
+    /// Timers owned by the processor, see `TimerMsg`
+    Timer(TimerMsg),
+
+    /// ATT_MTU of the connection was negotiated
+    MtuChanged(ConnectionHandle, u16),
+
+    /// Notify the HID report to the connection, dropped until the connection
+    /// is encrypted
+    HidReport(ConnectionHandle, AttHandleValueNotification),
+
+    /// Stop and resume advertising, see `AdvertisingPolicy`
+    Advertising(AdvertisingMsg),
+
+    /// Message defined by the application, passed to every processor
+    User(UserMsg),
+
+    /// Processor of the connection failed, the connection is being
+    /// disconnected
+    ConnectionFailed(ConnectionHandle, HciError),
+
+    /// Release the keys, disconnect the hosts, stop advertising and reset the
+    /// controller. Processors flush their persistent state on this.
+    Shutdown,
+
+    /// Controller was reset by `AppMsg::Shutdown`, the socket can be closed
+    ShutdownComplete,
+}
+
+/// Payload of `AppMsg::User`, any type with `Debug` and `PartialEq`
+///
+/// Cloning shares the payload, processors downcast it to the type they know.
+#[derive(Clone)]
+pub struct UserMsg(Rc<dyn Payload>);
+
+impl UserMsg {
+    pub fn new<T: Any + Debug + PartialEq>(payload: T) -> Self {
+        UserMsg(Rc::new(payload))
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.as_any().downcast_ref()
+    }
+
+    pub fn is<T: Any>(&self) -> bool {
+        self.0.as_any().is::<T>()
+    }
+}
+
+impl Debug for UserMsg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl PartialEq for UserMsg {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.eq_payload(other.0.as_any())
+    }
+}
+
+impl Eq for UserMsg {}
+
+trait Payload: Debug {
+    fn as_any(&self) -> &dyn Any;
+    fn eq_payload(&self, other: &dyn Any) -> bool;
+}
+
+impl<T: Any + Debug + PartialEq> Payload for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_payload(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<T>() == Some(self)
+    }
+}
+
+/// Timer id chosen by the processor, ids of different processors don't clash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimerId(pub u32);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimerMsg {
+    /// Returned by the processor to (re)start a timer
+    Start(TimerId, Duration),
+
+    /// Returned by the processor to stop a timer
+    Cancel(TimerId),
+
+    /// Delivered only to the processor that started the timer
+    Expired(TimerId),
 }
 
 impl From<SocketError> for HciError {
@@ -47,38 +223,716 @@ impl From<SocketError> for HciError {
     }
 }
 
+impl From<crate::packer::PacketError> for HciError {
+    fn from(err: crate::packer::PacketError) -> Self {
+        HciError::PacketError(err)
+    }
+}
+
 pub trait MsgProcessor {
     fn process(&mut self, msg: AppMsg) -> Result<Vec<AppMsg>, HciError>;
+
+    /// Messages the processor is given, read once when the processor is added
+    fn interest(&self) -> Interest {
+        Interest::all()
+    }
+
+    /// Shown in `HciManager::snapshot`
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+impl<P: MsgProcessor + ?Sized> MsgProcessor for Box<P> {
+    fn process(&mut self, msg: AppMsg) -> Result<Vec<AppMsg>, HciError> {
+        (**self).process(msg)
+    }
+
+    fn interest(&self) -> Interest {
+        (**self).interest()
+    }
+
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProcessorId(pub usize);
+
+struct ProcessorEntry {
+    id: ProcessorId,
+
+    /// Connection the processor belongs to, `None` for processors added by
+    /// the application
+    connection: Option<ConnectionHandle>,
+    interest: Interest,
+    processor: Box<dyn MsgProcessor>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandTimeoutConfig {
+    /// Time to wait for Command Complete or Command Status
+    pub timeout: Duration,
+
+    /// How many times idempotent commands are re-sent before giving up
+    pub retries: u8,
+}
+
+impl Default for CommandTimeoutConfig {
+    fn default() -> Self {
+        CommandTimeoutConfig {
+            timeout: Duration::from_secs(2),
+            retries: 2,
+        }
+    }
+}
+
+struct Timer {
+    owner: ProcessorId,
+    id: TimerId,
+    deadline: Duration,
+}
+
+/// Time the hosts have to answer the disconnects on shutdown
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Progress of `AppMsg::Shutdown`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shutdown {
+    /// Waiting for the connections to close, until the deadline
+    Disconnecting(Duration),
+
+    /// Waiting for the reset to complete
+    Resetting,
+    Done,
+}
+
+struct PendingCommand {
+    command: HciCommand,
+
+    /// `None` if the command was sent by the manager itself
+    issuer: Option<ProcessorId>,
+    deadline: Duration,
+    retries_left: u8,
+}
+
+/// Commands that can be re-sent without side effects if the controller
+/// swallowed them
+fn is_idempotent(command: &HciCommand) -> bool {
+    use HciCommand::*;
+    !matches!(
+        command,
+        Disconnect(_)
+            | LeReadLocalP256PublicKey
+            | LeLongTermKeyRequestReply(_)
+            | LeLongTermKeyRequestNegativeReply(_)
+    )
 }
 
 pub struct HciManager {
-    processors: Vec<Box<dyn MsgProcessor>>,
+    processors: Vec<ProcessorEntry>,
+    next_processor_id: usize,
     allowed_hci_command_packets: u8,
-    unpaired_connections: BTreeSet<ConnectionHandle>,
-    paired_connections: BTreeSet<ConnectionHandle>,
+    connections: ConnectionRegistry,
+
+    /// Commands sent on `AppMsg::InitHciManager` and replayed after a reset,
+    /// should end with enabling the advertising
+    init_commands: Vec<HciCommand>,
+
+    /// Commands sent by the manager itself, one at a time
+    command_queue: VecDeque<HciCommand>,
+    command_in_flight: Option<OpCode>,
+
+    clock: Box<dyn Clock>,
+    random: Box<dyn Random>,
+    advertising: AdvertisingManager,
+
+    command_timeout: CommandTimeoutConfig,
+
+    /// Commands waiting for Command Complete or Command Status, oldest first
+    pending_commands: Vec<PendingCommand>,
+
+    timers: Vec<Timer>,
+
+    shutdown: Option<Shutdown>,
 }
 
 impl HciManager {
     pub fn new() -> Result<Self, HciError> {
         let allowed_hci_command_packets = 0;
-        let unpaired_connections = BTreeSet::new();
-        let paired_connections = BTreeSet::new();
         let processors = Vec::new();
 
         Ok(HciManager {
             processors,
+            next_processor_id: 0,
             allowed_hci_command_packets,
-            unpaired_connections,
-            paired_connections,
+            connections: ConnectionRegistry::new(),
+            init_commands: Vec::new(),
+            command_queue: VecDeque::new(),
+            command_in_flight: None,
+            clock: Box::new(SystemClock::new()),
+            random: Box::new(SystemRandom),
+            advertising: AdvertisingManager::new(),
+            command_timeout: CommandTimeoutConfig::default(),
+            pending_commands: Vec::new(),
+            timers: Vec::new(),
+            shutdown: None,
         })
     }
+
+    pub fn set_init_commands(&mut self, commands: Vec<HciCommand>) {
+        self.init_commands = commands;
+    }
+
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn set_random(&mut self, random: Box<dyn Random>) {
+        self.random = random;
+    }
+
+    /// Number of simultaneous connections, defaults to 1
+    ///
+    /// Set above 1 only if the controller supports advertising in the
+    /// peripheral role (see LE Supported States).
+    pub fn set_max_connections(&mut self, max_connections: usize) {
+        self.advertising.max_connections = max_connections;
+    }
+
+    /// Restart advertising with fast and slow intervals, without a policy
+    /// advertising is only re-enabled with the parameters of the init commands
+    pub fn set_advertising_policy(&mut self, policy: AdvertisingPolicy) {
+        self.advertising.set_policy(policy);
+    }
+
+    pub fn set_command_timeout(&mut self, config: CommandTimeoutConfig) {
+        self.command_timeout = config;
+    }
+
+    /// Currently open connections
+    pub fn connections(&self) -> &ConnectionRegistry {
+        &self.connections
+    }
+
+    /// Internal state for debugging, can be serialized e.g. to JSON
+    pub fn snapshot(&self) -> Snapshot {
+        let ms = |d: Duration| d.as_millis() as u64;
+        Snapshot {
+            now_ms: ms(self.clock.now()),
+            connections: self
+                .connections
+                .iter()
+                .map(ConnectionSnapshot::from)
+                .collect(),
+            commands: CommandsSnapshot {
+                credits: self.allowed_hci_command_packets,
+                in_flight: self.command_in_flight.as_ref().map(crate::snapshot::opcode),
+                queued: self
+                    .command_queue
+                    .iter()
+                    .map(|c| format!("{:?}", c))
+                    .collect(),
+                pending: self
+                    .pending_commands
+                    .iter()
+                    .map(|p| PendingCommandSnapshot {
+                        opcode: crate::snapshot::opcode(&p.command.get_id()),
+                        command: format!("{:?}", p.command),
+                        issuer: p.issuer.map(|id| id.0),
+                        deadline_ms: ms(p.deadline),
+                        retries_left: p.retries_left,
+                    })
+                    .collect(),
+            },
+            processors: self
+                .processors
+                .iter()
+                .map(|p| ProcessorSnapshot {
+                    id: p.id.0,
+                    name: p.processor.name().to_string(),
+                    connection: p.connection.as_ref().map(|h| h.0),
+                    interest: (&p.interest).into(),
+                })
+                .collect(),
+            timers: self
+                .timers
+                .iter()
+                .map(|t| TimerSnapshot {
+                    processor: t.owner.0,
+                    id: t.id.0,
+                    deadline_ms: ms(t.deadline),
+                })
+                .collect(),
+            advertising: self.advertising.is_advertising(),
+            shutting_down: self.shutdown.is_some(),
+        }
+    }
+
+    /// Add application processor, it lives as long as the manager
+    pub fn add_processor(&mut self, processor: Box<dyn MsgProcessor>) -> ProcessorId {
+        self.push_processor(None, processor)
+    }
+
+    fn push_processor(
+        &mut self,
+        connection: Option<ConnectionHandle>,
+        processor: Box<dyn MsgProcessor>,
+    ) -> ProcessorId {
+        let id = ProcessorId(self.next_processor_id);
+        self.next_processor_id += 1;
+        self.processors.push(ProcessorEntry {
+            id,
+            connection,
+            interest: processor.interest(),
+            processor,
+        });
+        id
+    }
+
+    /// Register the connection and create its processors
+    fn open_connection(&mut self, e: &LeConnectionComplete) -> Vec<AppMsg> {
+        let att_handler = AttHandler::new(e.clone());
+        let pairing_handler = PairingHandler::new(
+            e.clone(),
+            // TODO: Own address from the init commands
+            BdAddr([6, 51, 116, 214, 86, 211]),
+            AddressType::Random,
+            self.random.next_u128(),
+            self.random.next_u128(),
+            self.random.next_u64(),
+        );
+        self.start_connection(
+            e,
+            vec![
+                (Box::new(att_handler), AppMsg::InitAttHandler),
+                (Box::new(pairing_handler), AppMsg::InitPairingHandler),
+            ],
+        )
+    }
+
+    /// Register the connection with its processors and their init messages
+    ///
+    /// A failed init fails the connection like any other error of its
+    /// processors, the processors after it are not created.
+    fn start_connection(
+        &mut self,
+        e: &LeConnectionComplete,
+        processors: Vec<(Box<dyn MsgProcessor>, AppMsg)>,
+    ) -> Vec<AppMsg> {
+        let handle = e.connection_handle.clone();
+        self.connections.insert(Connection::new(e));
+        let mut msgs = vec![];
+        for (mut processor, init) in processors {
+            let result = processor.process(init);
+            let id = self.push_processor(Some(handle.clone()), processor);
+            match result {
+                Ok(out) => msgs.append(&mut self.processor_output(id, out)),
+                Err(error) => {
+                    msgs.append(&mut self.connection_failed(handle, error));
+                    break;
+                }
+            }
+        }
+
+        let commands = self
+            .advertising
+            .connected(self.clock.now(), self.connections.len());
+        msgs.append(&mut self.queue_commands(commands));
+        msgs
+    }
+
+    /// Forget the connection and destroy its processors
+    fn close_connection(&mut self, handle: &ConnectionHandle) -> Vec<AppMsg> {
+        if self.connections.remove(handle).is_none() {
+            return vec![];
+        }
+        self.processors
+            .retain(|p| p.connection.as_ref() != Some(handle));
+        self.drop_orphans();
+
+        let commands = self
+            .advertising
+            .disconnected(self.clock.now(), self.connections.len());
+        self.queue_commands(commands)
+    }
+
+    /// Remove timers and pending commands of processors that no longer exist
+    fn drop_orphans(&mut self) {
+        let processors = &self.processors;
+        let exists = |id: &ProcessorId| processors.iter().any(|p| p.id == *id);
+        self.timers.retain(|t| exists(&t.owner));
+        self.pending_commands
+            .retain(|p| p.issuer.as_ref().is_none_or(exists));
+    }
+
+    /// Earliest time at which `AppMsg::Tick` has something to do
+    pub fn next_deadline(&self) -> Option<Duration> {
+        let timers = self.timers.iter().map(|t| t.deadline);
+        let commands = self.pending_commands.iter().map(|p| p.deadline);
+        let shutdown = match self.shutdown {
+            Some(Shutdown::Disconnecting(deadline)) => Some(deadline),
+            _ => None,
+        };
+        timers
+            .chain(commands)
+            .chain(self.advertising.deadline())
+            .chain(shutdown)
+            .min()
+    }
+
+    /// Time left until `next_deadline`, zero when it has passed
+    pub fn time_to_next_deadline(&self) -> Option<Duration> {
+        let now = self.clock.now();
+        self.next_deadline()
+            .map(|deadline| deadline.saturating_sub(now))
+    }
+
+    /// Bookkeeping for the messages returned by the processor
+    ///
+    /// Timer requests are consumed here, rest of the messages are passed on.
+    /// MTU changes are recorded in the connection registry.
+    fn processor_output(&mut self, id: ProcessorId, msgs: Vec<AppMsg>) -> Vec<AppMsg> {
+        self.track_commands(Some(id), &msgs);
+        let now = self.clock.now();
+        msgs.into_iter()
+            .filter(|msg| match msg {
+                AppMsg::Timer(TimerMsg::Start(timer, after)) => {
+                    self.timers.retain(|t| !(t.owner == id && t.id == *timer));
+                    self.timers.push(Timer {
+                        owner: id,
+                        id: *timer,
+                        deadline: now + *after,
+                    });
+                    false
+                }
+                AppMsg::Timer(TimerMsg::Cancel(timer)) => {
+                    self.timers.retain(|t| !(t.owner == id && t.id == *timer));
+                    false
+                }
+                AppMsg::MtuChanged(handle, mtu) => {
+                    if let Some(c) = self.connections.get_mut(handle) {
+                        c.mtu = *mtu;
+                    }
+                    true
+                }
+                AppMsg::Send(H4Packet::Acl(acl)) => {
+                    if let Some(c) = self.connections.get_mut(&acl.connection_handle) {
+                        c.acl_in_flight += 1;
+                        if let L2CapMessage::Smp(pdu) = &acl.msg {
+                            c.smp_seen(pdu);
+                        }
+                    }
+                    true
+                }
+                _ => true,
+            })
+            .collect()
+    }
+
+    /// Process the message only in the given processor
+    fn deliver_to(&mut self, id: ProcessorId, msg: AppMsg) -> Result<Vec<AppMsg>, HciError> {
+        match self.processors.iter_mut().find(|p| p.id == id) {
+            Some(entry) => {
+                let result = entry.processor.process(msg);
+                let connection = entry.connection.clone();
+                self.processor_result(id, connection, result)
+            }
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Errors of the per-connection processors fail only their connection,
+    /// errors of the application processors are returned
+    fn processor_result(
+        &mut self,
+        id: ProcessorId,
+        connection: Option<ConnectionHandle>,
+        result: Result<Vec<AppMsg>, HciError>,
+    ) -> Result<Vec<AppMsg>, HciError> {
+        match (result, connection) {
+            (Ok(out), _) => Ok(self.processor_output(id, out)),
+            (Err(error), Some(handle)) => Ok(self.connection_failed(handle, error)),
+            (Err(error), None) => Err(error),
+        }
+    }
+
+    /// Destroy the processors of the connection and disconnect it, state of
+    /// the failed processor can't be trusted anymore
+    fn connection_failed(&mut self, handle: ConnectionHandle, error: HciError) -> Vec<AppMsg> {
+        let count = self.processors.len();
+        self.processors
+            .retain(|p| p.connection.as_ref() != Some(&handle));
+        if self.processors.len() == count {
+            // Already failed by another processor of the connection
+            return vec![];
+        }
+        self.drop_orphans();
+
+        log::error!("Connection 0x{:04X} failed: {}", handle.0, error);
+        let mut msgs = vec![AppMsg::ConnectionFailed(handle.clone(), error)];
+        msgs.append(
+            &mut self.queue_commands(vec![HciCommand::Disconnect(CmdDisconnect {
+                connection_handle: handle,
+                // Remote User Terminated Connection
+                reason: 0x13,
+            })]),
+        );
+        msgs
+    }
+
+    /// Deliver expired timers to their owners, in deadline order
+    ///
+    /// Timers cancelled or restarted by an earlier expiration in the same
+    /// round are skipped.
+    fn fire_timers(&mut self, error: &mut Option<HciError>) -> Vec<AppMsg> {
+        let now = self.clock.now();
+        let mut expired: Vec<(Duration, ProcessorId, TimerId)> = self
+            .timers
+            .iter()
+            .filter(|t| t.deadline <= now)
+            .map(|t| (t.deadline, t.owner, t.id))
+            .collect();
+        expired.sort();
+
+        let mut msgs = vec![];
+        for (deadline, owner, id) in expired {
+            let Some(index) = self
+                .timers
+                .iter()
+                .position(|t| t.owner == owner && t.id == id && t.deadline == deadline)
+            else {
+                continue;
+            };
+            self.timers.remove(index);
+            let result = self.deliver_to(owner, AppMsg::Timer(TimerMsg::Expired(id)));
+            msgs.append(&mut first_error(error, result));
+        }
+        msgs
+    }
+
+    /// Start deadlines for the commands in the messages
+    fn track_commands(&mut self, issuer: Option<ProcessorId>, msgs: &[AppMsg]) {
+        for msg in msgs {
+            if let AppMsg::Send(H4Packet::Command(command)) = msg {
+                if issuer.is_some() {
+                    self.advertising.command_sent(command);
+                }
+                self.pending_commands.push(PendingCommand {
+                    command: command.clone(),
+                    issuer,
+                    deadline: self.clock.now() + self.command_timeout.timeout,
+                    retries_left: self.command_timeout.retries,
+                });
+            }
+        }
+    }
+
+    /// The controller answers the commands in the order they were sent, so
+    /// the answer is for the oldest pending command with the opcode, whoever
+    /// issued it. Re-sent commands move behind the others to keep the order.
+    fn command_answered(&mut self, opcode: OpCode) {
+        if let Some(index) = self
+            .pending_commands
+            .iter()
+            .position(|p| p.command.get_id() == opcode)
+        {
+            self.pending_commands.remove(index);
+        }
+    }
+
+    /// Re-send or fail the commands past their deadline
+    fn check_command_deadlines(&mut self, error: &mut Option<HciError>) -> Vec<AppMsg> {
+        let now = self.clock.now();
+        let mut msgs = vec![];
+        let mut resent = vec![];
+        let mut failed = vec![];
+        for mut pending in std::mem::take(&mut self.pending_commands) {
+            if pending.deadline > now {
+                self.pending_commands.push(pending);
+            } else if pending.retries_left > 0 && is_idempotent(&pending.command) {
+                pending.retries_left -= 1;
+                pending.deadline = now + self.command_timeout.timeout;
+                msgs.push(AppMsg::Send(H4Packet::Command(pending.command.clone())));
+                resent.push(pending);
+            } else {
+                failed.push((pending.issuer, pending.command.get_id()));
+            }
+        }
+        self.pending_commands.append(&mut resent);
+
+        for (issuer, opcode) in failed {
+            log::warn!("Command timed out: {:?}", opcode);
+            match issuer {
+                // Own command, the controller is not responding
+                None if self.shutdown.is_some() => msgs.append(&mut self.force_shutdown()),
+                None => msgs.append(&mut self.recover()),
+                Some(id) => {
+                    let failure = AppMsg::CommandFailed(HciError::CommandTimeout(opcode));
+                    let result = self.deliver_to(id, failure);
+                    msgs.append(&mut first_error(error, result));
+                }
+            }
+        }
+        msgs
+    }
+
+    /// Queue the init commands, with the advertising of the policy if any
+    fn queue_init_commands(&mut self) -> Vec<AppMsg> {
+        let commands = self.advertising.init(
+            &self.init_commands,
+            self.clock.now(),
+            self.connections.len(),
+        );
+        self.queue_commands(commands)
+    }
+
+    fn queue_commands(&mut self, commands: Vec<HciCommand>) -> Vec<AppMsg> {
+        self.command_queue.extend(commands);
+        self.send_next_command()
+    }
+
+    /// Send next queued command if previous one has completed
+    fn send_next_command(&mut self) -> Vec<AppMsg> {
+        if self.command_in_flight.is_some() {
+            return vec![];
+        }
+        match self.command_queue.pop_front() {
+            Some(cmd) => {
+                self.command_in_flight = Some(cmd.get_id());
+                let msgs = vec![AppMsg::Send(H4Packet::Command(cmd))];
+                self.track_commands(None, &msgs);
+                msgs
+            }
+            None => vec![],
+        }
+    }
+
+    fn command_done(&mut self, opcode: OpCode) -> Vec<AppMsg> {
+        if self.command_in_flight != Some(opcode) {
+            return vec![];
+        }
+        self.command_in_flight = None;
+        self.send_next_command()
+    }
+
+    /// Drop all connections and their processors, then reset the controller
+    /// and replay the init sequence
+    fn recover(&mut self) -> Vec<AppMsg> {
+        let lost = self.connections.clear();
+        self.processors.retain(|p| p.connection.is_none());
+        self.drop_orphans();
+
+        self.pending_commands.clear();
+        self.command_queue.clear();
+        self.command_in_flight = None;
+        self.command_queue.push_back(HciCommand::Reset);
+
+        let mut msgs = vec![AppMsg::ConnectionsLost(
+            lost.into_iter().map(|c| c.handle).collect(),
+        )];
+        msgs.append(&mut self.queue_init_commands());
+        msgs
+    }
+
+    /// Stop advertising and disconnect every connection, the controller is
+    /// reset once they are closed
+    fn start_shutdown(&mut self) -> Vec<AppMsg> {
+        if self.shutdown.is_some() {
+            return vec![];
+        }
+        log::info!("Shutting down");
+        self.shutdown = Some(Shutdown::Disconnecting(self.clock.now() + SHUTDOWN_TIMEOUT));
+        let mut commands = self.advertising.stop();
+        for handle in self.connections.handles() {
+            commands.push(power_off_disconnect(handle));
+        }
+        self.queue_commands(commands)
+    }
+
+    /// Reset the controller once the connections are closed, or the hosts
+    /// didn't answer in time
+    fn check_shutdown(&mut self) -> Vec<AppMsg> {
+        let Some(Shutdown::Disconnecting(deadline)) = self.shutdown else {
+            return vec![];
+        };
+        if !self.connections.is_empty() {
+            if deadline > self.clock.now() {
+                return vec![];
+            }
+            log::warn!("Hosts didn't answer the disconnects, resetting anyway");
+            return self.force_shutdown();
+        }
+        self.shutdown = Some(Shutdown::Resetting);
+        self.queue_commands(vec![HciCommand::Reset])
+    }
+
+    /// Reset without waiting for the queued commands, or give up if the
+    /// reset itself is not answered
+    fn force_shutdown(&mut self) -> Vec<AppMsg> {
+        match self.shutdown {
+            Some(Shutdown::Resetting) => {
+                log::error!("Controller didn't answer the reset");
+                self.shutdown = Some(Shutdown::Done);
+                return vec![AppMsg::ShutdownComplete];
+            }
+            Some(Shutdown::Done) => return vec![],
+            _ => {}
+        }
+        self.shutdown = Some(Shutdown::Resetting);
+
+        let lost = self.connections.clear();
+        self.processors.retain(|p| p.connection.is_none());
+        self.drop_orphans();
+
+        self.pending_commands.clear();
+        self.command_queue.clear();
+        self.command_in_flight = None;
+
+        let mut msgs = vec![AppMsg::ConnectionsLost(
+            lost.into_iter().map(|c| c.handle).collect(),
+        )];
+        msgs.append(&mut self.queue_commands(vec![HciCommand::Reset]));
+        msgs
+    }
+}
+
+fn power_off_disconnect(connection_handle: ConnectionHandle) -> HciCommand {
+    HciCommand::Disconnect(CmdDisconnect {
+        connection_handle,
+        // Remote Device Terminated Connection due to Power Off
+        reason: 0x15,
+    })
+}
+
+/// Output of the result, its error is kept if it's the first one so the rest
+/// of the message can still be handled
+fn first_error(error: &mut Option<HciError>, result: Result<Vec<AppMsg>, HciError>) -> Vec<AppMsg> {
+    result.unwrap_or_else(|e| {
+        error.get_or_insert(e);
+        vec![]
+    })
 }
 
 impl MsgProcessor for HciManager {
     fn process(&mut self, msg: AppMsg) -> Result<Vec<AppMsg>, HciError> {
+        let mut outputs = vec![];
+        for entry in self.processors.iter_mut() {
+            if !entry.interest.matches(&msg) {
+                continue;
+            }
+            let result = entry.processor.process(msg.clone());
+            outputs.push((entry.id, entry.connection.clone(), result));
+        }
+        // First error of the application processors, returned after the
+        // manager has handled the message so that it keeps up with the
+        // controller
+        let mut error = None;
         let mut msgs = vec![];
-        for processor in self.processors.iter_mut() {
-            msgs.append(&mut processor.process(msg.clone())?.into())
+        for (id, connection, result) in outputs {
+            let result = self.processor_result(id, connection, result);
+            msgs.append(&mut first_error(&mut error, result));
         }
 
         //
@@ -89,47 +943,131 @@ impl MsgProcessor for HciManager {
                 match evt {
                     CommandComplete(e) => {
                         self.allowed_hci_command_packets = e.num_hci_command_packets;
+                        self.command_answered(e.command_opcode);
+                        msgs.append(&mut self.command_done(e.command_opcode));
+                        if self.shutdown == Some(Shutdown::Resetting)
+                            && e.command_opcode == HciCommand::Reset.get_id()
+                        {
+                            log::info!("Shutdown complete");
+                            self.shutdown = Some(Shutdown::Done);
+                            msgs.push(AppMsg::ShutdownComplete);
+                        }
                     }
 
                     CommandStatus(e) => {
                         self.allowed_hci_command_packets = e.num_hci_command_packets;
+                        self.command_answered(e.command_opcode);
+                        msgs.append(&mut self.command_done(e.command_opcode));
+                    }
+                    DisconnectComplete(e) => {
+                        msgs.append(&mut self.close_connection(&e.connection_handle));
+                    }
+                    EncryptionChange(e) if e.status == HciStatus::Success => {
+                        if let Some(c) = self.connections.get_mut(&e.connection_handle) {
+                            if e.encryption_enabled {
+                                self.advertising
+                                    .set_bonded_host(c.peer_address_type, c.peer_address.clone());
+                            }
+                            if e.encryption_enabled && c.pairing == PairingStage::KeyGeneration {
+                                c.pairing = PairingStage::KeyDistribution;
+                            }
+                            // Only Just Works pairing is supported
+                            c.security_level = if e.encryption_enabled {
+                                SecurityLevel::Unauthenticated
+                            } else {
+                                SecurityLevel::NoSecurity
+                            };
+                        }
+                    }
+                    NumberOfCompletedPackets(e) => {
+                        for h in &e.handles {
+                            if let Some(c) = self.connections.get_mut(&h.connection_handle) {
+                                c.acl_in_flight =
+                                    c.acl_in_flight.saturating_sub(h.num_completed_packets);
+                            }
+                        }
+                    }
+                    HardwareError(e) => {
+                        log::error!("Hardware error: {}", e);
+                        msgs.append(&mut self.recover());
+                    }
+                    DataBufferOverflow(e) => {
+                        log::error!("Data buffer overflow: {}", e);
+                        msgs.append(&mut self.recover());
                     }
                     LeMeta(EvtLeMeta::LeConnectionComplete(e)) => {
-                        println!("LE Connection Complete: {:?}", e);
-                        let mut att_handler = AttHandler::new(e.clone());
-                        msgs.append(&mut att_handler.process(AppMsg::InitAttHandler)?);
-                        self.processors.push(Box::new(att_handler));
-
-                        let mut pairing_handler = PairingHandler::new(
-                            e.clone(),
-                            // TODO: Real randoms
-                            BdAddr([6, 51, 116, 214, 86, 211]),
-                            AddressType::Random,
-                            49055469533520638048878300062363381969,
-                            282559536878159528170380446798965774951,
-                            723151060346651216,
-                        );
-                        msgs.append(&mut pairing_handler.process(AppMsg::InitPairingHandler)?);
-                        self.processors.push(Box::new(pairing_handler));
+                        log::info!("LE Connection Complete: {}", e);
+                        if e.status == HciStatus::Success {
+                            msgs.append(&mut self.open_connection(&e));
+                            if self.shutdown.is_some() {
+                                let handle = e.connection_handle.clone();
+                                msgs.append(
+                                    &mut self.queue_commands(vec![power_off_disconnect(handle)]),
+                                );
+                            }
+                        }
+                    }
+                    LeMeta(EvtLeMeta::LeConnectionUpdateComplete(e))
+                        if e.status == HciStatus::Success =>
+                    {
+                        if let Some(c) = self.connections.get_mut(&e.connection_handle) {
+                            c.parameters = ConnectionParameters {
+                                interval: e.interval,
+                                latency: e.latency,
+                                supervision_timeout: e.timeout,
+                            };
+                        }
                     }
                     _ => {}
                 }
             }
+            AppMsg::Recv(H4Packet::Acl(HciAcl {
+                connection_handle,
+                msg: L2CapMessage::Smp(pdu),
+                ..
+            })) => {
+                if let Some(c) = self.connections.get_mut(&connection_handle) {
+                    c.smp_seen(&pdu);
+                }
+            }
             AppMsg::Recv(_) => {}
             AppMsg::Disconnect(_) => {}
             AppMsg::DisconnectComplete(handle) => {
-                self.paired_connections.remove(&handle);
+                msgs.append(&mut self.close_connection(&handle));
+            }
+            AppMsg::InitHciManager => {
+                msgs.append(&mut self.queue_init_commands());
             }
-            AppMsg::Pairing(handle) => {
-                self.unpaired_connections.insert(handle);
+            AppMsg::Advertising(AdvertisingMsg::Stop) => {
+                let commands = self.advertising.stop();
+                msgs.append(&mut self.queue_commands(commands));
             }
-            AppMsg::PairingComplete(handle) => {
-                self.paired_connections.insert(handle);
+            AppMsg::Advertising(AdvertisingMsg::Resume) => {
+                let commands = self
+                    .advertising
+                    .resume(self.clock.now(), self.connections.len());
+                msgs.append(&mut self.queue_commands(commands));
+            }
+            AppMsg::RecoverController => {
+                msgs.append(&mut self.recover());
+            }
+            AppMsg::Tick => {
+                msgs.append(&mut self.check_command_deadlines(&mut error));
+                msgs.append(&mut self.fire_timers(&mut error));
+                let commands = self.advertising.tick(self.clock.now());
+                msgs.append(&mut self.queue_commands(commands));
+            }
+            AppMsg::Shutdown => {
+                msgs.append(&mut self.start_shutdown());
             }
             _ => {}
         }
+        msgs.append(&mut self.check_shutdown());
 
-        Ok(msgs)
+        match error {
+            Some(error) => Err(error),
+            None => Ok(msgs),
+        }
     }
 }
 
@@ -139,3 +1077,62 @@ struct AttHanlder {
 }
 
 struct PairedConnection {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fails its init, or outputs the init message when `fails` is false
+    struct Init {
+        fails: bool,
+    }
+
+    impl MsgProcessor for Init {
+        fn process(&mut self, msg: AppMsg) -> Result<Vec<AppMsg>, HciError> {
+            match self.fails {
+                true => Err(HciError::Unknown("Init failed".to_string())),
+                false => Ok(vec![msg]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_failed_connection_init() {
+        let mut mgr = HciManager::new().unwrap();
+        let e = LeConnectionComplete {
+            status: HciStatus::Success,
+            connection_handle: ConnectionHandle(0x40),
+            role: Role::Peripheral,
+            peer_address_type: AddressType::Public,
+            peer_address: BdAddr([38, 14, 214, 232, 194, 80]),
+            connection_interval: 48,
+            peripheral_latency: 0,
+            supervision_timeout: 960,
+            central_clock_accuracy: ClockAccuracy::Ppm250,
+        };
+        let msgs = mgr.start_connection(
+            &e,
+            vec![
+                (Box::new(Init { fails: true }), AppMsg::InitAttHandler),
+                (Box::new(Init { fails: false }), AppMsg::InitPairingHandler),
+            ],
+        );
+
+        // Disconnected without creating the rest of the processors
+        assert_eq!(
+            msgs,
+            vec![
+                AppMsg::ConnectionFailed(
+                    ConnectionHandle(0x40),
+                    HciError::Unknown("Init failed".to_string())
+                ),
+                AppMsg::Send(H4Packet::Command(HciCommand::Disconnect(CmdDisconnect {
+                    connection_handle: ConnectionHandle(0x40),
+                    reason: 0x13,
+                }))),
+            ]
+        );
+        assert!(mgr.processors.is_empty());
+        assert!(mgr.connections().contains(&ConnectionHandle(0x40)));
+    }
+}
@@ -1,9 +1,21 @@
+// Generated code refers to this crate by name, also from within it
+extern crate self as bt_only_headers;
+
+pub mod advertising;
 pub mod atthandler;
 pub mod c1;
+pub mod clock;
+pub mod connection;
+pub mod describe;
+pub mod generate;
 pub mod hcimanager;
 pub mod messages;
-pub mod messages_impl;
+pub mod middleware;
 pub mod packer;
 pub mod pairinghandler;
+pub mod random;
+pub mod reflect;
+pub mod routing;
+pub mod snapshot;
 pub mod socket;
 // mod parrot;
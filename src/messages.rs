@@ -1,19 +1,28 @@
-use crate::packer::FixedSizeUtf8;
+use facet::Facet;
+
+use crate::describe::Describe;
+use crate::generate::Generate;
+use crate::packer::{FixedSizeUtf8, FromToPacket};
 
 /// id_type = u8
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(u8)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub enum H4Packet {
+    /// name = "HCI Command"
     /// id = 0x01
     Command(HciCommand),
+    /// name = "HCI Event"
     /// id = 0x04
     Event(HciEvent),
+    /// name = "ACL Data"
     /// id = 0x02
     Acl(HciAcl),
 }
 
 /// length_after_id = u8
 /// id_type = OpCode
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(u8)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub enum HciCommand {
     /// id = OpCode(0x0006, 0x01)
     Disconnect(CmdDisconnect),
@@ -22,11 +31,15 @@ pub enum HciCommand {
     Reset,
 
     /// id = OpCode(0x0001, 0x03)
-    SetEventMask(u64),
+    SetEventMask(
+        /// describe = hex
+        u64,
+    ),
 
     /// id = OpCode(0x0002, 0x04)
     ReadLocalSupportedCommands,
 
+    /// name = "Read BD ADDR"
     /// id = OpCode(0x0009, 0x04)
     ReadBdAddr,
 
@@ -45,40 +58,54 @@ pub enum HciCommand {
     /// id = OpCode(0x0014, 0x03)
     ReadLocalName(CmdReadLocalName),
 
+    /// name = "LE Set Event Mask"
     /// id = OpCode(0x0001, 0x08)
-    LeSetEventMask(u64),
+    LeSetEventMask(
+        /// describe = hex
+        u64,
+    ),
 
+    /// name = "LE Read Buffer Size"
     /// id = OpCode(0x0002, 0x08)
     LeReadBufferSize,
 
+    /// name = "LE Set Random Address"
     /// id = OpCode(0x0005, 0x08)
     LeSetRandomAddress(BdAddr),
 
+    /// name = "LE Set Advertising Parameters"
     /// id = OpCode(0x0006, 0x08)
     LeSetAdvertisingParameters(LeSetAdvertisingParameters),
 
+    /// name = "LE Set Advertising Data"
     /// id = OpCode(0x0008, 0x08)
     LeSetAdvertisingData(LeSetAdvertisingData),
 
+    /// name = "LE Read Local P-256 Public Key"
     /// id = OpCode(0x0025, 0x08)
     LeReadLocalP256PublicKey,
 
+    /// name = "LE Set Advertising Enable"
     /// id = OpCode(0x000A, 0x08)
     LeSetAdvertisingEnable(bool),
 
+    /// name = "LE Set Data Length"
     /// id = OpCode(0x0022, 0x08)
     LeSetDataLength(LeSetDataLength),
 
+    /// name = "LE Long Term Key Request Reply"
     /// id = OpCode(0x001A, 0x08)
     LeLongTermKeyRequestReply(LeLongTermKeyRequestReply),
 
+    /// name = "LE Long Term Key Request Negative Reply"
     /// id = OpCode(0x001B, 0x08)
     LeLongTermKeyRequestNegativeReply(ConnectionHandle),
 }
 
 /// id_type = u8
 /// length_after_id = u8
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(u8)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub enum HciEvent {
     /// id = 0x05
     DisconnectComplete(EvtDisconnectComplete),
@@ -86,9 +113,17 @@ pub enum HciEvent {
     /// id = 0x08
     EncryptionChange(EvtEncryptionChange),
 
+    /// id = 0x10
+    HardwareError(EvtHardwareError),
+
+    /// name = "Number of Completed Packets"
     /// id = 0x13
     NumberOfCompletedPackets(EvtNumberOfCompletedPackets),
 
+    /// id = 0x1A
+    DataBufferOverflow(EvtDataBufferOverflow),
+
+    /// name = "LE Meta Event"
     /// id = 0x3e
     LeMeta(EvtLeMeta),
 
@@ -102,19 +137,23 @@ pub enum HciEvent {
     VendorSpecific(Vec<u8>),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct HciAcl {
+    /// name = "Handle"
     /// bits = 12
     pub connection_handle: ConnectionHandle,
 
+    /// name = "Packet boundary"
     /// bits = 2
     pub pb: PacketBoundaryFlag,
 
+    /// name = "Broadcast"
     /// bits = 2
     pub bc: BroadcastFlag,
 
     /// ACL Data length
     ///
+    /// name = "L2CAP"
     /// prepend_length = u16
     pub msg: L2CapMessage,
 }
@@ -124,12 +163,17 @@ pub struct HciAcl {
 /// id_type = u16
 /// prepend_length = u16
 /// prepend_length_offset = -2
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(u8)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub enum L2CapMessage {
+    /// name = "SMP"
     /// id = 0x0006
     Smp(SmpPdu),
+    /// name = "ATT"
     /// id = 0x0004
     Att(AttPdu),
+    /// Channels without a variant, the id of a variant fails to pack
+    ///
     /// id = _
     Unknown(u16, Vec<u8>),
 }
@@ -139,11 +183,17 @@ pub enum L2CapMessage {
 /// https://www.bluetooth.com/wp-content/uploads/Files/Specification/HTML/Core-60/out/en/host/attribute-protocol--att-.html
 ///
 /// id_type = u8
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(u8)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub enum AttPdu {
+    /// id = 0x01
+    ErrorResponse(AttErrorResponse),
+
+    /// name = "Exchange MTU Request"
     /// id = 0x02
     ExchangeMtuRequest(u16),
 
+    /// name = "Exchange MTU Response"
     /// id = 0x03
     ExchangeMtuResponse(u16),
 
@@ -171,6 +221,36 @@ pub enum AttPdu {
     /// id = 0x0B
     ReadResponse(AttReadResponse),
 
+    /// id = 0x0C
+    ReadBlobRequest(AttReadBlobRequest),
+
+    /// id = 0x0D
+    ReadBlobResponse(AttReadResponse),
+
+    /// id = 0x0E
+    ReadMultipleRequest(AttReadMultipleRequest),
+
+    /// id = 0x0F
+    ReadMultipleResponse(AttReadResponse),
+
+    /// id = 0x10
+    ReadByGroupTypeRequest(AttReadByTypeRequest),
+
+    /// id = 0x11
+    ReadByGroupTypeResponse(AttReadByGroupTypeResponse),
+
+    /// id = 0x12
+    WriteRequest(AttWriteRequest),
+
+    /// id = 0x13
+    WriteResponse,
+
+    /// id = 0x16
+    PrepareWriteRequest(AttPrepareWrite),
+
+    /// id = 0x17
+    PrepareWriteResponse(AttPrepareWrite),
+
     /// id = 0x18
     ExecuteWriteRequest(AttExecuteWriteRequest),
 
@@ -180,12 +260,36 @@ pub enum AttPdu {
     /// id = 0x1B
     HandleValueNotification(AttHandleValueNotification),
 
+    /// id = 0x1D
+    HandleValueIndication(AttHandleValueNotification),
+
+    /// id = 0x1E
+    HandleValueConfirmation,
+
+    /// id = 0x20
+    ReadMultipleVariableRequest(AttReadMultipleRequest),
+
+    /// id = 0x21
+    ReadMultipleVariableResponse(AttReadMultipleVariableResponse),
+
+    /// id = 0x23
+    MultipleHandleValueNotification(AttMultipleHandleValueNotification),
+
+    /// id = 0x52
+    WriteCommand(AttWriteRequest),
+
+    /// id = 0xD2
+    SignedWriteCommand(AttSignedWriteCommand),
+
+    /// Opcodes without a variant, the opcode of a variant fails to pack
+    ///
     /// id = _
     Unknown(u8, Vec<u8>),
 }
 
 /// id_type = u8
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(u8)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub enum SmpPdu {
     /// id = 0x01
     PairingRequest(SmpPairingReqRes),
@@ -210,99 +314,244 @@ pub enum SmpPdu {
 }
 
 /// id_type = u8
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(u8)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub enum EvtLeMeta {
+    /// name = "LE Connection Complete"
     /// id = 0x01
     LeConnectionComplete(LeConnectionComplete),
 
+    /// name = "LE Advertising Report"
     /// id = 0x02
     LeAdvertisingReport(Vec<u8>),
 
+    /// name = "LE Connection Update Complete"
     /// id = 0x03
     LeConnectionUpdateComplete(LeConnectionUpdateComplete),
 
+    /// name = "LE Read Remote Features Page 0 Complete"
     /// id = 0x04
     LeReadRemoteFeaturesPage0Complete(LeReadRemoteFeaturesPage0Complete),
 
+    /// name = "LE Long Term Key Request"
     /// id = 0x05
     LeLongTermKeyRequest(LeLongTermKeyRequest),
 
+    /// name = "LE Data Length Change"
     /// id = 0x07
     LeDataLengthChange(LeDataLengthChange),
 
+    /// name = "LE Read Local P-256 Public Key Complete"
     /// id = 0x08
     LeReadLocalP256PublicKeyComplete(LeReadLocalP256PublicKeyComplete),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub struct AttErrorResponse {
+    /// Opcode of the request that failed
+    ///
+    /// names = AttPdu
+    pub request_opcode: u8,
+    /// describe = hex
+    pub handle: u16,
+    pub error_code: AttErrorCode,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct AttFindInformationRequest {
+    /// describe = hex
     pub starting_handle: u16,
+    /// describe = hex
     pub ending_handle: u16,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct AttFindInformationResponse {
     pub format: u8,
-    pub information: Vec<(u16, u16)>,
+    /// variant_by = format
+    pub information: AttInformationData,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Handle and UUID pairs, the UUID size is given by the format
+///
+/// id_type = u8
+#[repr(u8)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub enum AttInformationData {
+    /// name = "UUID-16"
+    /// id = 0x01
+    Uuid16(Vec<(u16, u16)>),
+
+    /// name = "UUID-128"
+    /// id = 0x02
+    Uuid128(Vec<(u16, u128)>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct AttFindByTypeValueRequest {
+    /// describe = hex
     pub starting_handle: u16,
+    /// describe = hex
     pub ending_handle: u16,
+    /// name = "UUID"
+    /// describe = hex
     pub uuid: u16,
     pub value: Vec<u8>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct AttFindByTypeValueResponse {
     pub handles_information: Vec<(u16, u16)>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct AttReadByTypeRequest {
+    /// describe = hex
     pub starting_handle: u16,
+    /// describe = hex
     pub ending_handle: u16,
 
     /// 2 or 16 bytes
+    ///
+    /// name = "UUID"
     pub uuid: Vec<u8>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct AttReadByTypeResponse {
     /// The Length parameter shall be set to the size of one attribute handle-value pair.
+    ///
+    /// describe = skip
     pub pair_length: u8,
 
-    // Following can't work, because Vec reads to the end of the buffer, falling back to Vec<u8> instead
-    // values: Vec<(u16, Vec<u8>)>,
-    /// (Handle, value pairs)
-    pub values: Vec<u8>,
+    /// element_length = pair_length
+    pub values: Vec<AttAttributeData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub struct AttAttributeData {
+    /// describe = hex
+    pub handle: u16,
+    pub value: Vec<u8>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct AttReadRequest {
+    /// describe = hex
     pub handle: u16,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct AttReadResponse {
     pub value: Vec<u8>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub struct AttReadBlobRequest {
+    /// describe = hex
+    pub handle: u16,
+    /// describe = hex
+    pub offset: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub struct AttReadMultipleRequest {
+    /// Two or more handles
+    pub handles: Vec<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub struct AttReadByGroupTypeResponse {
+    /// The Length parameter shall be set to the size of one attribute handle,
+    /// end group handle and value triplet.
+    ///
+    /// describe = skip
+    pub length: u8,
+
+    /// element_length = length
+    pub values: Vec<AttGroupData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub struct AttGroupData {
+    /// describe = hex
+    pub handle: u16,
+    /// describe = hex
+    pub end_group_handle: u16,
+    pub value: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub struct AttReadMultipleVariableResponse {
+    pub values: Vec<AttLengthValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub struct AttLengthValue {
+    /// prepend_length = u16
+    pub value: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub struct AttMultipleHandleValueNotification {
+    pub values: Vec<AttHandleLengthValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub struct AttHandleLengthValue {
+    /// describe = hex
+    pub handle: u16,
+    /// prepend_length = u16
+    pub value: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub struct AttWriteRequest {
+    /// describe = hex
+    pub handle: u16,
+    pub value: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub struct AttSignedWriteCommand {
+    /// describe = hex
+    pub handle: u16,
+    /// bytes_after = 12
+    pub value: Vec<u8>,
+    /// Authentication Signature
+    ///
+    /// describe = hex
+    pub signature: [u8; 12],
+}
+
+/// Request and response are the same
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub struct AttPrepareWrite {
+    /// describe = hex
+    pub handle: u16,
+    /// describe = hex
+    pub offset: u16,
+    pub value: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct AttExecuteWriteRequest {
+    /// describe = hex
     pub flags: u8,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct AttHandleValueNotification {
+    /// describe = hex
     pub handle: u16,
     pub value: Vec<u8>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct SmpPairingReqRes {
+    /// name = "IO capability"
     pub io_capability: IOCapability,
+    /// name = "OOB data"
     pub oob_data_flag: OOBDataFlag,
     pub authentication_requirements: AuthenticationRequirements,
     pub max_encryption_key_size: u8,
@@ -310,72 +559,93 @@ pub struct SmpPairingReqRes {
     pub responder_key_distribution: KeyDistributionFlags,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct SmpPairingConfirmation {
+    /// describe = hex
     pub confirm_value: u128,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct SmpPairingRandom {
+    /// describe = hex
     pub random_value: u128,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct SmpEncryptionInformation {
+    /// describe = hex
     pub long_term_key: u128,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct SmpCentralIdentification {
+    /// name = "EDIV"
+    /// describe = hex
     pub encrypted_diversifier: u16,
+    /// name = "Rand"
+    /// describe = hex
     pub random_number: u64,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct CmdDisconnect {
+    /// name = "Handle"
     pub connection_handle: ConnectionHandle,
+    /// describe = hex
     pub reason: u8,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct CmdReadLocalName {
     pub status: HciStatus,
     pub name: FixedSizeUtf8<248>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct LeSetAdvertisingParameters {
+    /// name = "Min advertising interval"
+    /// unit = "msec"
+    /// scale = 0.625
     pub advertising_interval_min: u16,
+    /// name = "Max advertising interval"
+    /// unit = "msec"
+    /// scale = 0.625
     pub advertising_interval_max: u16,
     pub advertising_type: u8,
     pub own_address_type: u8,
     pub peer_address_type: u8,
     pub peer_address: BdAddr,
+    /// describe = hex
     pub advertising_channel_map: u8,
     pub advertising_filter_policy: u8,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct LeSetAdvertisingData {
+    /// describe = skip
     pub advertising_data_length: u8,
     pub advertising_data: [u8; 31],
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct LeSetDataLength {
+    /// name = "Handle"
     pub connection_handle: ConnectionHandle,
     pub tx_octets: u16,
+    /// unit = "usec"
     pub tx_time: u16,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct LeLongTermKeyRequestReply {
+    /// name = "Handle"
     pub connection_handle: ConnectionHandle,
+    /// describe = hex
     pub long_term_key: u128,
 }
 
 /// HCI OpCode
-#[derive(Debug, Clone, PartialEq, Eq, Copy, Default, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Default, Hash, Facet, FromToPacket, Generate)]
 pub struct OpCode(
     /// Command (OCF)
     /// bits = 10
@@ -387,7 +657,8 @@ pub struct OpCode(
 
 /// id_type = u8
 #[allow(non_camel_case_types)]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(u8)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub enum CmdScanEnable {
     /// id = 0x00
     NoScans,
@@ -399,97 +670,156 @@ pub enum CmdScanEnable {
     InquiryScanEnabled_PageScanEnabled,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct LeConnectionComplete {
     pub status: HciStatus,
+    /// name = "Handle"
     pub connection_handle: ConnectionHandle,
     pub role: Role,
     pub peer_address_type: AddressType,
     pub peer_address: BdAddr,
+    /// unit = "msec"
+    /// scale = 1.25
     pub connection_interval: u16,
     pub peripheral_latency: u16,
+    /// unit = "msec"
+    /// scale = 10.0
     pub supervision_timeout: u16,
     pub central_clock_accuracy: ClockAccuracy,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct LeConnectionUpdateComplete {
     pub status: HciStatus,
+    /// name = "Handle"
     pub connection_handle: ConnectionHandle,
+    /// name = "Connection interval"
+    /// unit = "msec"
+    /// scale = 1.25
     pub interval: u16,
+    /// name = "Peripheral latency"
     pub latency: u16,
+    /// name = "Supervision timeout"
+    /// unit = "msec"
+    /// scale = 10.0
     pub timeout: u16,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct LeReadRemoteFeaturesPage0Complete {
     pub status: HciStatus,
+    /// name = "Handle"
     pub connection_handle: ConnectionHandle,
+    /// name = "LE features"
+    /// describe = hex
     pub le_features: u64,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct LeLongTermKeyRequest {
+    /// name = "Handle"
     pub connection_handle: ConnectionHandle,
+    /// name = "Rand"
+    /// describe = hex
     pub random_number: u64,
+    /// name = "EDIV"
+    /// describe = hex
     pub encrypted_diversifier: u16,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct LeDataLengthChange {
+    /// name = "Handle"
     pub connection_handle: ConnectionHandle,
     pub max_tx_octets: u16,
+    /// unit = "usec"
     pub max_tx_time: u16,
     pub max_rx_octets: u16,
+    /// unit = "usec"
     pub max_rx_time: u16,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct LeReadLocalP256PublicKeyComplete {
     pub status: HciStatus,
     pub public_key: [u8; 64],
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct EvtDisconnectComplete {
     pub status: HciStatus,
+    /// name = "Handle"
     pub connection_handle: ConnectionHandle,
+    /// describe = hex
     pub reason: u8,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct EvtEncryptionChange {
     pub status: HciStatus,
+    /// name = "Handle"
     pub connection_handle: ConnectionHandle,
     pub encryption_enabled: bool,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub struct EvtHardwareError {
+    /// Vendor specific hardware code
+    ///
+    /// describe = hex
+    pub hardware_code: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub struct EvtDataBufferOverflow {
+    /// 0x00 = Synchronous, 0x01 = ACL
+    ///
+    /// describe = hex
+    pub link_type: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct EvtNumberOfCompletedPackets {
-    pub num_hci_command_packets: u8,
+    /// count_prefix = u8
+    pub handles: Vec<CompletedPackets>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub struct CompletedPackets {
+    /// name = "Handle"
     pub connection_handle: ConnectionHandle,
     pub num_completed_packets: u16,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct EvtCommandComplete {
     /// The number of HCI Command packets which are allowed to be sent to
     /// the Controller from the Host.
+    ///
+    /// name = "Num HCI command packets"
     pub num_hci_command_packets: u8,
+    /// names = HciCommand
     pub command_opcode: OpCode,
-    pub status: HciStatus,
+    /// No return parameters for the NOP opcode, sent when the controller
+    /// can accept commands
+    ///
+    /// present_if = command_opcode != OpCode(0x0000, 0x00)
+    pub status: Option<HciStatus>,
     pub data: Vec<u8>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub struct EvtCommandStatus {
     pub status: HciStatus,
+    /// name = "Num HCI command packets"
     pub num_hci_command_packets: u8,
+    /// names = HciCommand
     pub command_opcode: OpCode,
 }
 
 /// id_type = u8
-#[derive(Debug, Clone, PartialEq, Eq, Copy, Default)]
+#[repr(u8)]
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Default, Facet, FromToPacket, Describe, Generate)]
 pub enum PacketBoundaryFlag {
     #[default]
     FirstNonFlushable = 0b00,
@@ -499,66 +829,114 @@ pub enum PacketBoundaryFlag {
 }
 
 /// id_type = u8
-#[derive(Debug, Clone, PartialEq, Eq, Copy, Default)]
+#[repr(u8)]
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Default, Facet, FromToPacket, Describe, Generate)]
 pub enum BroadcastFlag {
     #[default]
     PointToPoint = 0b00,
+    /// name = "BR/EDR Broadcast"
     BdEdrBroadcast = 0b01,
 }
 
 /// id_type = u8
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub enum HciStatus {
     /// id = 0x00
     Success,
+    /// Error codes, 0x00 fails to pack
+    ///
     /// id = _
     Failure(u8),
 }
 
 /// id_type = u8
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub enum Role {
     Central = 0,
     Peripheral = 1,
+    /// Values reserved for future use, an assigned value fails to pack
+    ///
+    /// id = _
+    Reserved(u8),
 }
 
 /// id_type = u8
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub enum AddressType {
     Public = 0,
     Random = 1,
+    /// Values reserved for future use, an assigned value fails to pack
+    ///
+    /// id = _
+    Reserved(u8),
 }
 
 /// id_type = u8
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub enum ClockAccuracy {
+    /// name = "500 ppm"
     Ppm500 = 0,
+    /// name = "250 ppm"
     Ppm250 = 1,
+    /// name = "150 ppm"
     Ppm150 = 2,
+    /// name = "100 ppm"
     Ppm100 = 3,
+    /// name = "75 ppm"
     Ppm75 = 4,
+    /// name = "50 ppm"
     Ppm50 = 5,
+    /// name = "30 ppm"
     Ppm30 = 6,
+    /// name = "20 ppm"
     Ppm20 = 7,
+    /// Values reserved for future use, an assigned value fails to pack
+    ///
+    /// id = _
+    Reserved(u8),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Default, PartialOrd, Ord, Hash)]
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Default,
+    PartialOrd,
+    Ord,
+    Hash,
+    Facet,
+    FromToPacket,
+    Describe,
+    Generate,
+)]
 pub struct ConnectionHandle(pub u16); // max value 0x0EFF
 
-#[derive(Debug, Clone, PartialEq, Eq, Default, PartialOrd, Ord, Hash)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, Default, PartialOrd, Ord, Hash, Facet, FromToPacket, Generate,
+)]
 pub struct BdAddr(pub [u8; 6]);
 
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
+/// describe = flags
+#[derive(Debug, Clone, PartialEq, Eq, Default, Facet, FromToPacket, Describe, Generate)]
 pub struct KeyDistributionFlags {
+    /// name = "EncKey"
     /// bits = 1
     pub enc_key: bool,
 
+    /// name = "IdKey"
     /// bits = 1
     pub id_key: bool,
 
+    /// name = "Sign"
     /// bits = 1
     pub sign_key: bool,
 
+    /// name = "LinkKey"
     /// bits = 1
     pub link_key: bool,
 
@@ -566,16 +944,22 @@ pub struct KeyDistributionFlags {
     pub _reserved: u8,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
+/// describe = flags
+#[derive(Debug, Clone, PartialEq, Eq, Default, Facet, FromToPacket, Describe, Generate)]
 pub struct AuthenticationRequirements {
+    /// name = "Bonding"
     /// bits = 2
     pub bonding: bool,
+    /// name = "MITM"
     /// bits = 1
     pub mitm_protection: bool,
+    /// name = "SC"
     /// bits = 1
     pub secure_connections: bool,
+    /// name = "Keypresses"
     /// bits = 1
     pub keypress_notification: bool,
+    /// name = "CT2"
     /// bits = 1
     pub ct2: bool,
 
@@ -584,22 +968,68 @@ pub struct AuthenticationRequirements {
 }
 
 /// id_type = u8
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(u8)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub enum IOCapability {
     DisplayOnly = 0x00,
+    /// name = "Display Yes/No"
     DisplayYesNo = 0x01,
     KeyboardOnly = 0x02,
     NoInputNoOutput = 0x03,
     KeyboardDisplay = 0x04,
-    // Reserved(u8),
+    /// Values reserved for future use, an assigned value fails to pack
+    ///
+    /// id = _
+    Reserved(u8),
 }
 
 /// id_type = u8
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(u8)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub enum OOBDataFlag {
+    /// name = "OOB Not Available"
     OobNotAvailable = 0x00,
+    /// name = "OOB Available"
     OobAvailable = 0x01,
-    // Reserved(u8),
+    /// Values reserved for future use, an assigned value fails to pack
+    ///
+    /// id = _
+    Reserved(u8),
+}
+
+/// ATT Error Codes
+///
+/// https://www.bluetooth.com/wp-content/uploads/Files/Specification/HTML/Core-60/out/en/host/attribute-protocol--att-.html#UUID-ca29cc91-2c0e-c7d2-d1e1-0cf2b2b2d8a5
+///
+/// id_type = u8
+#[repr(u8)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
+pub enum AttErrorCode {
+    InvalidHandle = 0x01,
+    ReadNotPermitted = 0x02,
+    WriteNotPermitted = 0x03,
+    /// name = "Invalid PDU"
+    InvalidPdu = 0x04,
+    InsufficientAuthentication = 0x05,
+    RequestNotSupported = 0x06,
+    InvalidOffset = 0x07,
+    InsufficientAuthorization = 0x08,
+    PrepareQueueFull = 0x09,
+    AttributeNotFound = 0x0A,
+    AttributeNotLong = 0x0B,
+    EncryptionKeySizeTooShort = 0x0C,
+    InvalidAttributeValueLength = 0x0D,
+    UnlikelyError = 0x0E,
+    InsufficientEncryption = 0x0F,
+    UnsupportedGroupType = 0x10,
+    InsufficientResources = 0x11,
+    DatabaseOutOfSync = 0x12,
+    ValueNotAllowed = 0x13,
+    /// Application and profile errors, or reserved, the codes above fail to
+    /// pack
+    ///
+    /// id = _
+    Other(u8),
 }
 
 /// SMP Pairing failures
@@ -607,9 +1037,11 @@ pub enum OOBDataFlag {
 /// https://www.bluetooth.com/wp-content/uploads/Files/Specification/HTML/Core-60/out/en/host/security-manager-specification.html#UUID-edc160cf-62e1-c774-f84c-da67aaf4aa50
 ///
 /// id_type = u8
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(u8)]
+#[derive(Debug, Clone, PartialEq, Eq, Facet, FromToPacket, Describe, Generate)]
 pub enum SmpPairingFailure {
     PasskeyEntryFailed = 0x01,
+    /// name = "OOB Not Available"
     OobNotAvailable = 0x02,
     AuthenticationRequirements = 0x03,
     ConfirmValueFailed = 0x04,
@@ -619,10 +1051,16 @@ pub enum SmpPairingFailure {
     UnspecifiedReason = 0x08,
     RepeatedAttempts = 0x09,
     InvalidParameters = 0x0A,
+    /// name = "DHKey Check Failed"
     DhKeyCheckFailed = 0x0B,
     NumericComparisonFailed = 0x0C,
+    /// name = "BR/EDR Pairing In Progress"
     BrEdrPairingInProgress = 0x0D,
     CrossTransportKeyDerivationGenerationNotAllowed = 0x0E,
     KeyRejected = 0x0F,
     Busy = 0x10,
+    /// Values reserved for future use, an assigned value fails to pack
+    ///
+    /// id = _
+    Reserved(u8),
 }
@@ -0,0 +1,505 @@
+use std::{
+    cell::RefCell,
+    collections::{HashSet, VecDeque},
+    rc::Rc,
+    time::Duration,
+};
+
+use log::{Level, log};
+
+use crate::{
+    clock::Clock,
+    hcimanager::{AppMsg, HciError, MsgProcessor},
+    messages::*,
+    packer::FromToPacket,
+    routing::{Interest, event_connection},
+};
+
+/// What the middleware does with an incoming message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Intercept {
+    /// Give the message to the next middleware, or to the processor
+    Pass(AppMsg),
+
+    /// Stop the message here and output these instead
+    Block(Vec<AppMsg>),
+}
+
+/// Cross-cutting behaviour around a processor, see `Layered`
+pub trait Middleware {
+    fn incoming(&mut self, msg: AppMsg) -> Intercept {
+        Intercept::Pass(msg)
+    }
+
+    fn outgoing(&mut self, msgs: Vec<AppMsg>) -> Vec<AppMsg> {
+        msgs
+    }
+}
+
+/// Processor wrapped in middleware
+///
+/// Incoming messages go through the middleware in the order they were added,
+/// outgoing messages in the reverse order. Output of a blocked message goes
+/// only through the middleware before the one that blocked it.
+pub struct Layered<P: MsgProcessor> {
+    middleware: Vec<Box<dyn Middleware>>,
+    processor: P,
+}
+
+impl<P: MsgProcessor> Layered<P> {
+    pub fn new(processor: P) -> Self {
+        Layered {
+            middleware: Vec::new(),
+            processor,
+        }
+    }
+
+    pub fn with(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.processor
+    }
+
+    pub fn inner_mut(&mut self) -> &mut P {
+        &mut self.processor
+    }
+
+    fn outgoing(&mut self, before: usize, msgs: Vec<AppMsg>) -> Vec<AppMsg> {
+        self.middleware[..before]
+            .iter_mut()
+            .rev()
+            .fold(msgs, |msgs, middleware| middleware.outgoing(msgs))
+    }
+}
+
+impl<P: MsgProcessor> MsgProcessor for Layered<P> {
+    fn process(&mut self, msg: AppMsg) -> Result<Vec<AppMsg>, HciError> {
+        let mut msg = msg;
+        for index in 0..self.middleware.len() {
+            match self.middleware[index].incoming(msg) {
+                Intercept::Pass(next) => msg = next,
+                Intercept::Block(msgs) => return Ok(self.outgoing(index, msgs)),
+            }
+        }
+        let msgs = self.processor.process(msg)?;
+        Ok(self.outgoing(self.middleware.len(), msgs))
+    }
+
+    fn interest(&self) -> Interest {
+        self.processor.interest()
+    }
+
+    fn name(&self) -> &'static str {
+        self.processor.name()
+    }
+}
+
+/// Logs the described packets, `>` from the controller and `<` to it
+///
+/// Other messages are logged at trace level.
+pub struct Logger {
+    level: Level,
+}
+
+impl Logger {
+    pub fn new(level: Level) -> Self {
+        Logger { level }
+    }
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Self::new(Level::Debug)
+    }
+}
+
+impl Middleware for Logger {
+    fn incoming(&mut self, msg: AppMsg) -> Intercept {
+        match &msg {
+            AppMsg::Recv(packet) => log!(self.level, "> {}", packet),
+            // Logged when it comes out
+            AppMsg::Send(_) => {}
+            other => log!(Level::Trace, "{:?}", other),
+        }
+        Intercept::Pass(msg)
+    }
+
+    fn outgoing(&mut self, msgs: Vec<AppMsg>) -> Vec<AppMsg> {
+        for msg in &msgs {
+            match msg {
+                AppMsg::Send(packet) => log!(self.level, "< {}", packet),
+                other => log!(Level::Trace, "{:?}", other),
+            }
+        }
+        msgs
+    }
+}
+
+/// Disconnects the blocked peers as soon as they connect
+///
+/// Only the events opening and closing their connections get through, so the
+/// processor still sees the controller stop advertising for the connection
+/// and can restart it after.
+pub struct PeerFilter {
+    blocked: HashSet<BdAddr>,
+    connections: HashSet<ConnectionHandle>,
+
+    /// Connections to disconnect once the processor has seen them open
+    disconnect: Vec<ConnectionHandle>,
+}
+
+impl PeerFilter {
+    /// Disconnect reason, Remote User Terminated Connection
+    const REASON: u8 = 0x13;
+
+    pub fn new(blocked: impl IntoIterator<Item = BdAddr>) -> Self {
+        PeerFilter {
+            blocked: blocked.into_iter().collect(),
+            connections: HashSet::new(),
+            disconnect: Vec::new(),
+        }
+    }
+
+    fn is_blocked(&self, msg: &AppMsg) -> bool {
+        let handle = match msg {
+            AppMsg::Send(H4Packet::Acl(acl)) | AppMsg::Recv(H4Packet::Acl(acl)) => {
+                Some(&acl.connection_handle)
+            }
+            AppMsg::Recv(H4Packet::Event(event)) => event_connection(event),
+            AppMsg::HidReport(handle, _) | AppMsg::MtuChanged(handle, _) => Some(handle),
+            _ => None,
+        };
+        handle.is_some_and(|handle| self.connections.contains(handle))
+    }
+}
+
+impl Middleware for PeerFilter {
+    fn incoming(&mut self, msg: AppMsg) -> Intercept {
+        use EvtLeMeta::*;
+        use HciEvent::*;
+        match &msg {
+            AppMsg::Recv(H4Packet::Event(LeMeta(LeConnectionComplete(e))))
+                if e.status == HciStatus::Success && self.blocked.contains(&e.peer_address) =>
+            {
+                log::info!("Blocked peer {} connected", e.peer_address);
+                self.connections.insert(e.connection_handle.clone());
+                self.disconnect.push(e.connection_handle.clone());
+                Intercept::Pass(msg)
+            }
+            AppMsg::Recv(H4Packet::Event(DisconnectComplete(e))) => {
+                self.connections.remove(&e.connection_handle);
+                Intercept::Pass(msg)
+            }
+            _ if self.is_blocked(&msg) => Intercept::Block(vec![]),
+            _ => Intercept::Pass(msg),
+        }
+    }
+
+    fn outgoing(&mut self, msgs: Vec<AppMsg>) -> Vec<AppMsg> {
+        let mut msgs: Vec<AppMsg> = msgs
+            .into_iter()
+            .filter(|msg| !self.is_blocked(msg))
+            .collect();
+        msgs.extend(self.disconnect.drain(..).map(|handle| {
+            AppMsg::Send(H4Packet::Command(HciCommand::Disconnect(CmdDisconnect {
+                connection_handle: handle,
+                reason: Self::REASON,
+            })))
+        }));
+        msgs
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recorded {
+    Incoming(AppMsg),
+    Outgoing(AppMsg),
+}
+
+/// Records the messages, clones share the same recording
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    records: Rc<RefCell<Vec<Recorded>>>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Recorder::default()
+    }
+
+    pub fn records(&self) -> Vec<Recorded> {
+        self.records.borrow().clone()
+    }
+
+    pub fn clear(&self) {
+        self.records.borrow_mut().clear();
+    }
+
+    /// Packets in the format of the hcidump test fixtures
+    ///
+    /// `<` is a packet sent to the controller, `>` is received from it.
+    pub fn to_hcidump(&self) -> String {
+        let mut dump = String::new();
+        for record in self.records.borrow().iter() {
+            let (direction, packet) = match record {
+                Recorded::Outgoing(AppMsg::Send(packet)) => ('<', packet),
+                Recorded::Incoming(AppMsg::Recv(packet)) => ('>', packet),
+                _ => continue,
+            };
+            for (index, line) in packet.to_bytes().chunks(20).enumerate() {
+                let hex: Vec<String> = line.iter().map(|b| format!("{:02X}", b)).collect();
+                let prefix = if index == 0 { direction } else { ' ' };
+                dump.push_str(&format!("{} {}\n", prefix, hex.join(" ")));
+            }
+        }
+        dump
+    }
+}
+
+impl Middleware for Recorder {
+    fn incoming(&mut self, msg: AppMsg) -> Intercept {
+        self.records
+            .borrow_mut()
+            .push(Recorded::Incoming(msg.clone()));
+        Intercept::Pass(msg)
+    }
+
+    fn outgoing(&mut self, msgs: Vec<AppMsg>) -> Vec<AppMsg> {
+        self.records
+            .borrow_mut()
+            .extend(msgs.iter().cloned().map(Recorded::Outgoing));
+        msgs
+    }
+}
+
+/// Lets through at most `max` of the selected messages in any `period`, rest
+/// of them are dropped
+pub struct RateLimiter {
+    clock: Box<dyn Clock>,
+    max: usize,
+    period: Duration,
+    select: Box<dyn Fn(&AppMsg) -> bool>,
+
+    /// Times the selected messages were let through, oldest first
+    passed: VecDeque<Duration>,
+}
+
+impl RateLimiter {
+    pub fn new(
+        clock: Box<dyn Clock>,
+        max: usize,
+        period: Duration,
+        select: impl Fn(&AppMsg) -> bool + 'static,
+    ) -> Self {
+        RateLimiter {
+            clock,
+            max,
+            period,
+            select: Box::new(select),
+            passed: VecDeque::new(),
+        }
+    }
+}
+
+impl Middleware for RateLimiter {
+    fn incoming(&mut self, msg: AppMsg) -> Intercept {
+        if !(self.select)(&msg) {
+            return Intercept::Pass(msg);
+        }
+        let now = self.clock.now();
+        while self
+            .passed
+            .front()
+            .is_some_and(|passed| *passed + self.period <= now)
+        {
+            self.passed.pop_front();
+        }
+        if self.passed.len() >= self.max {
+            log::debug!("Rate limited {:?}", msg);
+            return Intercept::Block(vec![]);
+        }
+        self.passed.push_back(now);
+        Intercept::Pass(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::VirtualClock;
+
+    /// Outputs the messages it is given
+    struct Echo;
+
+    impl MsgProcessor for Echo {
+        fn process(&mut self, msg: AppMsg) -> Result<Vec<AppMsg>, HciError> {
+            Ok(vec![msg])
+        }
+    }
+
+    /// Appends its digit to the handles of disconnect messages both ways,
+    /// blocks the handle 0
+    struct Tag(u16);
+
+    impl Middleware for Tag {
+        fn incoming(&mut self, msg: AppMsg) -> Intercept {
+            match msg {
+                AppMsg::Disconnect(ConnectionHandle(0)) => Intercept::Block(vec![]),
+                AppMsg::Disconnect(ConnectionHandle(h)) => {
+                    Intercept::Pass(AppMsg::Disconnect(ConnectionHandle(h * 10 + self.0)))
+                }
+                other => Intercept::Pass(other),
+            }
+        }
+
+        fn outgoing(&mut self, msgs: Vec<AppMsg>) -> Vec<AppMsg> {
+            msgs.into_iter()
+                .map(|msg| match msg {
+                    AppMsg::Disconnect(ConnectionHandle(h)) => {
+                        AppMsg::Disconnect(ConnectionHandle(h * 10 + self.0))
+                    }
+                    other => other,
+                })
+                .collect()
+        }
+    }
+
+    fn connection_complete(handle: u16, peer: u8) -> AppMsg {
+        AppMsg::Recv(H4Packet::Event(HciEvent::LeMeta(
+            EvtLeMeta::LeConnectionComplete(LeConnectionComplete {
+                status: HciStatus::Success,
+                connection_handle: ConnectionHandle(handle),
+                role: Role::Peripheral,
+                peer_address_type: AddressType::Public,
+                peer_address: BdAddr([peer; 6]),
+                connection_interval: 48,
+                peripheral_latency: 0,
+                supervision_timeout: 960,
+                central_clock_accuracy: ClockAccuracy::Ppm250,
+            }),
+        )))
+    }
+
+    #[test]
+    fn test_layered_order() {
+        let mut layered = Layered::new(Echo).with(Tag(1)).with(Tag(2));
+        assert_eq!(
+            layered
+                .process(AppMsg::Disconnect(ConnectionHandle(5)))
+                .unwrap(),
+            vec![AppMsg::Disconnect(ConnectionHandle(51221))]
+        );
+    }
+
+    #[test]
+    fn test_peer_filter() {
+        let mut layered = Layered::new(Echo).with(PeerFilter::new([BdAddr([1; 6])]));
+
+        // Connection is seen by the processor, nothing it sends to it gets out
+        assert_eq!(
+            layered.process(connection_complete(0x40, 1)).unwrap(),
+            vec![AppMsg::Send(H4Packet::Command(HciCommand::Disconnect(
+                CmdDisconnect {
+                    connection_handle: ConnectionHandle(0x40),
+                    reason: 0x13,
+                }
+            )))]
+        );
+        let other = connection_complete(0x41, 2);
+        assert_eq!(layered.process(other.clone()).unwrap(), vec![other]);
+
+        let acl = |handle| {
+            AppMsg::Recv(H4Packet::Acl(HciAcl {
+                connection_handle: ConnectionHandle(handle),
+                bc: BroadcastFlag::PointToPoint,
+                pb: PacketBoundaryFlag::FirstNonFlushable,
+                msg: L2CapMessage::Att(AttPdu::ExchangeMtuRequest(244)),
+            }))
+        };
+        assert_eq!(layered.process(acl(0x40)).unwrap(), vec![]);
+        assert_eq!(layered.process(acl(0x41)).unwrap(), vec![acl(0x41)]);
+
+        // Handle may be reused after the disconnect
+        let disconnected = AppMsg::Recv(H4Packet::Event(HciEvent::DisconnectComplete(
+            EvtDisconnectComplete {
+                status: HciStatus::Success,
+                connection_handle: ConnectionHandle(0x40),
+                reason: 0x16,
+            },
+        )));
+        assert_eq!(
+            layered.process(disconnected.clone()).unwrap(),
+            vec![disconnected]
+        );
+        assert_eq!(layered.process(acl(0x40)).unwrap(), vec![acl(0x40)]);
+    }
+
+    #[test]
+    fn test_recorder() {
+        let recorder = Recorder::new();
+        let mut layered = Layered::new(Echo).with(recorder.clone()).with(Tag(1));
+        layered
+            .process(AppMsg::Disconnect(ConnectionHandle(0)))
+            .unwrap();
+        layered
+            .process(AppMsg::Disconnect(ConnectionHandle(2)))
+            .unwrap();
+        assert_eq!(
+            recorder.records(),
+            vec![
+                Recorded::Incoming(AppMsg::Disconnect(ConnectionHandle(0))),
+                Recorded::Incoming(AppMsg::Disconnect(ConnectionHandle(2))),
+                Recorded::Outgoing(AppMsg::Disconnect(ConnectionHandle(211))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recorder_hcidump() {
+        let recorder = Recorder::new();
+        let mut layered = Layered::new(Echo).with(recorder.clone());
+        layered.process(connection_complete(0x40, 1)).unwrap();
+        layered
+            .process(AppMsg::Send(H4Packet::Command(HciCommand::Reset)))
+            .unwrap();
+        assert_eq!(
+            recorder.to_hcidump(),
+            "> 04 3E 13 01 00 40 00 01 00 01 01 01 01 01 01 30 00 00 00 C0\n  \
+             03 01\n\
+             < 01 03 0C 00\n"
+        );
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let clock = VirtualClock::new();
+        let is_report = |msg: &AppMsg| matches!(msg, AppMsg::HidReport(..));
+        let limiter = RateLimiter::new(
+            Box::new(clock.clone()),
+            2,
+            Duration::from_millis(10),
+            is_report,
+        );
+        let mut layered = Layered::new(Echo).with(limiter);
+        let report = AppMsg::HidReport(
+            ConnectionHandle(0x40),
+            AttHandleValueNotification {
+                handle: 0x001A,
+                value: vec![0; 8],
+            },
+        );
+        let mut send = |msg: &AppMsg| layered.process(msg.clone()).unwrap().len();
+
+        assert_eq!(send(&report), 1);
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(send(&report), 1);
+        assert_eq!(send(&report), 0);
+        assert_eq!(send(&AppMsg::Tick), 1, "Other messages are not limited");
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(send(&report), 1);
+        assert_eq!(send(&report), 0);
+    }
+}
@@ -1,7 +1,8 @@
 use std::{
-    collections::HashSet,
+    borrow::Cow,
     error::Error,
     fmt::{Display, Formatter},
+    ops::Range,
 };
 
 /// Set bits from a byte array into a target byte array based on the specified
@@ -37,13 +38,14 @@ fn set_bits_le(val: &mut [u8], bits: (usize, usize), value: &[u8]) {
     }
 }
 
-/// Extract bits from a byte array based on the specified bit range, assumes
-/// little-endian byte order.
-fn get_bits_le(val: &[u8], bits: (usize, usize)) -> Vec<u8> {
+/// Extract bits from a byte array based on the specified bit range into
+/// `out`, assumes little-endian byte order. Bits that don't fit in `out` are
+/// dropped.
+fn get_bits_le(val: &[u8], bits: (usize, usize), out: &mut [u8]) {
     let (start, end) = bits;
     let num_bits = end - start + 1;
 
-    let mut result = vec![0u8; val.len()]; // same length as input
+    out.fill(0);
 
     for i in 0..num_bits {
         let val_bit_pos = start + i;
@@ -59,18 +61,23 @@ fn get_bits_le(val: &[u8], bits: (usize, usize)) -> Vec<u8> {
         let res_byte = i / 8;
         let res_bit = i % 8;
 
-        if res_byte < result.len() {
-            result[res_byte] |= bit << res_bit;
+        if res_byte < out.len() {
+            out[res_byte] |= bit << res_bit;
         }
     }
-
-    result
 }
 
 #[cfg(test)]
 mod tests2 {
     use super::*;
 
+    /// Bits as bytes of the same length as the input
+    fn get_bits(val: &[u8], bits: (usize, usize)) -> Vec<u8> {
+        let mut out = vec![0u8; val.len()];
+        get_bits_le(val, bits, &mut out);
+        out
+    }
+
     #[test]
     fn test_set_bits_example1() {
         let mut bytes = vec![0x00, 0x00];
@@ -110,13 +117,13 @@ mod tests2 {
         // 0xbeefu16 in little endian is [0xef, 0xbe]
         let bytes = 0xBEEFu16.to_le_bytes();
         // Extract all 16 bits
-        assert_eq!(get_bits_le(&bytes, (0, 15)), vec![0xef, 0xbe]);
+        assert_eq!(get_bits(&bytes, (0, 15)), vec![0xef, 0xbe]);
         // Extract lower 8 bits (should be [0xef])
-        assert_eq!(get_bits_le(&bytes, (0, 7)), vec![0xef, 0x00]);
+        assert_eq!(get_bits(&bytes, (0, 7)), vec![0xef, 0x00]);
         // Extract upper 8 bits (should be [0xbe])
-        assert_eq!(get_bits_le(&bytes, (8, 15)), vec![0xbe, 0x00]);
+        assert_eq!(get_bits(&bytes, (8, 15)), vec![0xbe, 0x00]);
         // Extract bits 4..11 (should be 0xee, i.e. 0b11101110)
-        assert_eq!(get_bits_le(&bytes, (4, 11)), vec![0xee, 0x00]);
+        assert_eq!(get_bits(&bytes, (4, 11)), vec![0xee, 0x00]);
     }
 
     #[test]
@@ -124,20 +131,20 @@ mod tests2 {
         let bytes = 0x123456789abcdef0123456789abcdef0_u128.to_le_bytes();
         // [f0, de, bc, 9a, 78, 56, 34, 12, f0, de, bc, 9a, 78, 56, 34, 12]
         assert_eq!(
-            u128::from_le_bytes(get_bits_le(&bytes, (0, 64)).try_into().unwrap()),
+            u128::from_le_bytes(get_bits(&bytes, (0, 64)).try_into().unwrap()),
             0x123456789abcdef0_u128
         );
         assert_eq!(
-            u128::from_le_bytes(get_bits_le(&bytes, (64, 127)).try_into().unwrap()),
+            u128::from_le_bytes(get_bits(&bytes, (64, 127)).try_into().unwrap()),
             0x123456789abcdef0_u128
         );
         assert_eq!(
-            u128::from_le_bytes(get_bits_le(&bytes, (0, 127)).try_into().unwrap()),
+            u128::from_le_bytes(get_bits(&bytes, (0, 127)).try_into().unwrap()),
             0x123456789abcdef0123456789abcdef0_u128
         );
         assert_eq!(
             u128::from_le_bytes(
-                get_bits_le(&bytes, (8 * 8 + 4, 8 * 11 + 3))
+                get_bits(&bytes, (8 * 8 + 4, 8 * 11 + 3))
                     .try_into()
                     .unwrap()
             ),
@@ -148,22 +155,36 @@ mod tests2 {
     #[test]
     fn test_bit_range_bigger_than_input() {
         let bytes = 0xFF_u8.to_le_bytes();
-        let result = get_bits_le(&bytes, (0, 14));
+        let result = get_bits(&bytes, (0, 14));
         assert_eq!(result, vec![0xFF]);
     }
 
     #[test]
     fn test_bit_range_smaller_than_input() {
         let bytes = 0x1234_u16.to_le_bytes();
-        let result = get_bits_le(&bytes, (0, 3));
+        let result = get_bits(&bytes, (0, 3));
         assert_eq!(result, vec![0x04, 0x00]);
     }
 }
 
+pub use bitgen_derive::FromToPacket;
+
 pub trait PacketIdentifier<T: FromToPacket> {
     fn get_id(&self) -> T;
 }
 
+/// Enum whose variant is chosen by an id packed before it, e.g. by a field
+/// with `variant_by`
+pub trait PacketVariant<T: FromToPacket>: PacketIdentifier<T> {
+    /// Unpack the variant with the id, the id itself isn't unpacked
+    fn from_packet_variant(id: &T, bytes: &mut Packet) -> Result<Self, PacketError>
+    where
+        Self: Sized;
+
+    /// Pack the variant without its id
+    fn to_packet_variant(&self, bytes: &mut Packet) -> Result<(), PacketError>;
+}
+
 pub trait FromToPacket {
     fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError>
     where
@@ -173,12 +194,19 @@ pub trait FromToPacket {
     fn to_bytes(&self) -> Vec<u8> {
         let mut packet = Packet::new();
         self.to_packet(&mut packet).unwrap();
-        packet.get_bytes().to_vec()
+        packet.into_bytes()
+    }
+
+    /// Pack into `buffer`, replacing its contents
+    ///
+    /// Doesn't allocate once the buffer has grown to the size of the packet.
+    fn write_to(&self, buffer: &mut Vec<u8>) -> Result<(), PacketError> {
+        self.to_packet(&mut Packet::with_buffer(buffer))
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct FixedSizeUtf8<const N: usize>(String);
+pub struct FixedSizeUtf8<const N: usize>(pub(crate) String);
 
 impl<const N: usize> FixedSizeUtf8<N> {
     pub fn new(string: &str) -> Self {
@@ -192,7 +220,7 @@ impl<const N: usize> FixedSizeUtf8<N> {
 
 impl<const N: usize> FromToPacket for FixedSizeUtf8<N> {
     fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
-        let bytes = bytes.unpack_bytes(N)?;
+        let bytes = bytes.unpack_array::<N>()?;
         let bytes = bytes.split(|&b| b == 0).next().unwrap_or(&[]);
         let string = String::from_utf8(bytes.to_vec()).map_err(|_| PacketError::InvalidBytes)?;
         Ok(FixedSizeUtf8(string))
@@ -203,8 +231,8 @@ impl<const N: usize> FromToPacket for FixedSizeUtf8<N> {
             return Err(PacketError::InvalidBytes);
         }
         bytes.pack_bytes(str)?;
-        if str.len() < N {
-            bytes.pack_bytes(vec![0; N - str.len()])?;
+        for _ in str.len()..N {
+            bytes.pack_bytes(&[0])?;
         }
         Ok(())
     }
@@ -212,7 +240,7 @@ impl<const N: usize> FromToPacket for FixedSizeUtf8<N> {
 
 impl FromToPacket for bool {
     fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
-        let res = bytes.unpack_bytes(1)?;
+        let res = bytes.unpack_array::<1>()?;
         Ok(res[0] == 1_u8)
     }
     fn to_packet(&self, bytes: &mut Packet) -> Result<(), PacketError> {
@@ -223,13 +251,10 @@ impl FromToPacket for bool {
 
 impl<const T: usize> FromToPacket for [u8; T] {
     fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
-        let mut result = [0u8; T];
-        let output = bytes.unpack_bytes(T)?;
-        result.copy_from_slice(&output);
-        Ok(result)
+        bytes.unpack_array::<T>()
     }
     fn to_packet(&self, bytes: &mut Packet) -> Result<(), PacketError> {
-        bytes.pack_bytes(*self)?;
+        bytes.pack_bytes(self)?;
         Ok(())
     }
 }
@@ -244,23 +269,23 @@ impl<const T: usize> FromToPacket for [u8; T] {
 //     }
 // }
 
+/// Reads to the end of the packet, unless the element count is set with
+/// `set_count` or `unpack_count`
+///
+/// With `set_element_length` every element is unpacked from its own slice of
+/// that many bytes, e.g. to read an element ending in `Vec<u8>`.
 impl<T> FromToPacket for Vec<T>
 where
     T: FromToPacket,
 {
     fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
-        let mut vec = Vec::new();
-        while bytes.position < bytes.data.len() {
-            let item = T::from_packet(bytes)?;
-            vec.push(item);
-        }
-        Ok(vec)
+        bytes.unpack_elements(Vec::new(), |mut vec, bytes| {
+            vec.push(T::from_packet(bytes)?);
+            Ok(vec)
+        })
     }
     fn to_packet(&self, bytes: &mut Packet) -> Result<(), PacketError> {
-        for item in self {
-            item.to_packet(bytes)?;
-        }
-        Ok(())
+        bytes.pack_elements(self.len(), |index, bytes| self[index].to_packet(bytes))
     }
 }
 
@@ -283,40 +308,31 @@ macro_rules! tuple_impls {
 }
 
 macro_rules! impl_from_to_bytes {
-    ($type:ty, $size:expr) => {
+    ($type:ty) => {
         impl FromToPacket for $type {
             fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
-                // Pad bytes to the size of the type
-                let mut opbytes = bytes.unpack_bytes($size)?.clone();
-                opbytes.resize($size, 0);
-
-                Ok(Self::from_le_bytes(
-                    opbytes
-                        .as_slice()
-                        .try_into()
-                        .map_err(|_| PacketError::NotEnoughBytes)?,
-                ))
+                Ok(Self::from_le_bytes(bytes.unpack_array()?))
             }
             fn to_packet(&self, bytes: &mut Packet) -> Result<(), PacketError> {
-                bytes.pack_bytes(self.to_le_bytes())?;
+                bytes.pack_bytes(&self.to_le_bytes())?;
                 Ok(())
             }
         }
     };
 }
 
-impl_from_to_bytes!(u8, 1);
-impl_from_to_bytes!(u16, 2);
-impl_from_to_bytes!(u32, 4);
-impl_from_to_bytes!(u64, 8);
-impl_from_to_bytes!(u128, 16);
-impl_from_to_bytes!(i8, 1);
-impl_from_to_bytes!(i16, 2);
-impl_from_to_bytes!(i32, 4);
-impl_from_to_bytes!(i64, 8);
-impl_from_to_bytes!(i128, 16);
-impl_from_to_bytes!(f32, 4);
-impl_from_to_bytes!(f64, 8);
+impl_from_to_bytes!(u8);
+impl_from_to_bytes!(u16);
+impl_from_to_bytes!(u32);
+impl_from_to_bytes!(u64);
+impl_from_to_bytes!(u128);
+impl_from_to_bytes!(i8);
+impl_from_to_bytes!(i16);
+impl_from_to_bytes!(i32);
+impl_from_to_bytes!(i64);
+impl_from_to_bytes!(i128);
+impl_from_to_bytes!(f32);
+impl_from_to_bytes!(f64);
 
 tuple_impls! { A B }
 tuple_impls! { A B C }
@@ -335,7 +351,69 @@ pub enum PacketError {
     NotEnoughBytes,
     InvalidInstruction,
     InvalidBytes,
+
+    /// Length prefix covers more bytes than are left
+    Truncated {
+        length: usize,
+        remaining: usize,
+    },
+
+    /// Value used fewer bytes than its length prefix covers
+    TrailingBytes {
+        length: usize,
+        consumed: usize,
+    },
     Unspecified(String),
+
+    /// Error while unpacking a field, with where it happened
+    Field(Box<FieldError>),
+}
+
+impl PacketError {
+    /// The error without the field it happened in
+    pub fn kind(&self) -> &PacketError {
+        match self {
+            PacketError::Field(field) => &field.error,
+            error => error,
+        }
+    }
+}
+
+/// Where in the packet unpacking failed
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FieldError {
+    /// Underlying error, never `PacketError::Field`
+    pub error: PacketError,
+
+    /// Byte offset of the innermost field in the packet
+    pub offset: usize,
+
+    /// Bit offset within the byte, for bit fields
+    pub bit_offset: usize,
+
+    /// Fields from the outermost, e.g. `["H4Packet::Acl", "HciAcl.msg"]`
+    pub path: Vec<Cow<'static, str>>,
+
+    /// Bytes from the offset, at most `FieldError::MAX_BYTES`
+    pub bytes: Vec<u8>,
+}
+
+impl FieldError {
+    pub const MAX_BYTES: usize = 16;
+}
+
+impl Display for FieldError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at offset {}", self.error, self.offset)?;
+        if self.bit_offset != 0 {
+            write!(f, " bit {}", self.bit_offset)?;
+        }
+        write!(f, " in {}:", self.path.join(" > "))?;
+        for byte in &self.bytes {
+            write!(f, " {:02X}", byte)?;
+        }
+        Ok(())
+    }
 }
 
 impl Display for PacketError {
@@ -344,7 +422,14 @@ impl Display for PacketError {
             PacketError::NotEnoughBytes => write!(f, "Not enough bytes"),
             PacketError::InvalidInstruction => write!(f, "Invalid instruction"),
             PacketError::InvalidBytes => write!(f, "Invalid bytes"),
+            PacketError::Truncated { length, remaining } => {
+                write!(f, "Length {} but only {} bytes left", length, remaining)
+            }
+            PacketError::TrailingBytes { length, consumed } => {
+                write!(f, "Length {} but only {} bytes used", length, consumed)
+            }
             PacketError::Unspecified(msg) => write!(f, "Unspecified: {}", msg),
+            PacketError::Field(field) => field.fmt(f),
         }
     }
 }
@@ -355,79 +440,151 @@ impl Error for PacketError {
             PacketError::NotEnoughBytes => "Not enough bytes",
             PacketError::InvalidInstruction => "Invalid instruction",
             PacketError::InvalidBytes => "Invalid bytes",
+            PacketError::Truncated { .. } => "Truncated",
+            PacketError::TrailingBytes { .. } => "Trailing bytes",
             PacketError::Unspecified(msg) => msg,
+            PacketError::Field(_) => "Invalid field",
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+/// Length packed with `pack_length`, patched once the value after it is packed
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct LengthPosition {
     position: usize,
     offset: i32,
     bytes: usize,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
-pub struct Packet {
+/// Position in a `Packet`, see `Packet::checkpoint`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
     position: usize,
     bit_position: usize,
     bits: usize,
-    data: Vec<u8>,
-    length_positions: HashSet<LengthPosition>,
 }
 
-impl AsMut<Packet> for Packet {
+/// Bytes of a `Packet`
+#[derive(Debug, PartialEq, Eq)]
+enum Data<'a> {
+    /// Bytes to unpack, borrowed from the caller
+    Borrowed(&'a [u8]),
+    Owned(Vec<u8>),
+
+    /// Buffer of the caller to pack into
+    Buffer(&'a mut Vec<u8>),
+}
+
+impl Data<'_> {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Data::Borrowed(bytes) => bytes,
+            Data::Owned(bytes) => bytes,
+            Data::Buffer(bytes) => bytes,
+        }
+    }
+
+    /// Bytes to pack into, borrowed bytes are copied first
+    fn to_mut(&mut self) -> &mut Vec<u8> {
+        if let Data::Borrowed(bytes) = self {
+            *self = Data::Owned(bytes.to_vec());
+        }
+        match self {
+            Data::Owned(bytes) => bytes,
+            Data::Buffer(bytes) => bytes,
+            Data::Borrowed(_) => unreachable!(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Packet<'a> {
+    position: usize,
+    bit_position: usize,
+    bits: usize,
+    data: Data<'a>,
+
+    /// Length from `pack_length`, patched by the next pack
+    length_position: Option<LengthPosition>,
+
+    /// Element count of the next `Vec`
+    count: Option<usize>,
+
+    /// Size of the element count packed before the next `Vec`, 0 for none
+    count_prefix: usize,
+
+    /// Size of every element of the next `Vec`
+    element_length: Option<usize>,
+
+    /// Bytes covered by the length from `unpack_length`, used by the next
+    /// unpack
+    length_view: Option<Range<usize>>,
+
+    /// End of the innermost view being unpacked, outer ends are kept by
+    /// `unpack_bounded`
+    view_end: Option<usize>,
+}
+
+impl<'a> AsMut<Packet<'a>> for Packet<'a> {
     fn as_mut(&mut self) -> &mut Self {
         self
     }
 }
 
-impl Packet {
-    pub fn new() -> Self {
-        Packet {
-            position: 0,
-            bit_position: 0,
-            bits: 0,
-            data: Vec::new(),
-            length_positions: HashSet::new(),
-        }
+impl Default for Packet<'_> {
+    fn default() -> Self {
+        Packet::new()
     }
+}
 
-    pub fn from_slice(bytes: &[u8]) -> Self {
+impl<'a> Packet<'a> {
+    fn with_data(data: Data<'a>) -> Self {
         Packet {
             position: 0,
             bit_position: 0,
             bits: 0,
-            data: bytes.to_vec(),
-            length_positions: HashSet::new(),
+            data,
+            length_position: None,
+            count: None,
+            count_prefix: 0,
+            element_length: None,
+            length_view: None,
+            view_end: None,
         }
     }
 
-    pub fn get_bytes(&mut self) -> &[u8] {
-        // TODO: This doesn't need to mutate `data`, it could return a copy of
-        // the data with length positions updated with the values
-        for length_position in &self.length_positions {
-            // Length of bytes from position to end of data, minus the length and offset
-            let length = (self.data.len() as i32 - 1)
-                - (length_position.position as i32 + length_position.bytes as i32 - 1)
-                + length_position.offset;
+    pub fn new() -> Self {
+        Packet::with_data(Data::Owned(Vec::new()))
+    }
 
-            let len_bytes = length.to_le_bytes();
+    /// Unpack from `bytes` without copying them
+    pub fn from_slice(bytes: &'a [u8]) -> Self {
+        Packet::with_data(Data::Borrowed(bytes))
+    }
+
+    /// Pack into `buffer`, it's cleared but keeps its capacity
+    pub fn with_buffer(buffer: &'a mut Vec<u8>) -> Self {
+        buffer.clear();
+        Packet::with_data(Data::Buffer(buffer))
+    }
+
+    pub fn get_bytes(&self) -> &[u8] {
+        self.data.as_slice()
+    }
 
-            self.data[length_position.position
-                ..length_position.position + length_position.bytes.min(len_bytes.len())]
-                .copy_from_slice(&len_bytes[0..length_position.bytes.min(len_bytes.len())]);
+    /// Packed bytes, copied only if they are borrowed
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self.data {
+            Data::Owned(bytes) => bytes,
+            data => data.as_slice().to_vec(),
         }
-        &self.data
     }
 
     pub fn next_if_eq<T>(&mut self, bytes_eq: &T) -> bool
     where
         T: FromToPacket + Eq,
     {
-        let pos = self.position;
-        let bits = self.bits;
-        let bit_position = self.bit_position;
+        let checkpoint = self.checkpoint();
 
         if let Ok(val) = T::from_packet(self) {
             if val == *bytes_eq {
@@ -436,153 +593,400 @@ impl Packet {
         }
 
         // Reset position and bit position if unpack fails
-        self.position = pos;
-        self.bit_position = bit_position;
-        self.bits = bits;
+        self.rewind(checkpoint);
         false
     }
 
+    /// Current position, to unpack again from it with `rewind`
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            position: self.position,
+            bit_position: self.bit_position,
+            bits: self.bits,
+        }
+    }
+
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.position = checkpoint.position;
+        self.bit_position = checkpoint.bit_position;
+        self.bits = checkpoint.bits;
+    }
+
     pub fn set_bits(&mut self, size: usize) -> &mut Self {
         self.bits = size;
         self
     }
 
+    /// Pack the length of the next packed value before it
     pub fn pack_length<T: FromToPacket + Default>(&mut self) -> Result<&mut Self, PacketError> {
-        // Only allow packing length if no instructions are present
-        if self.bits != 0 {
+        self.pack_length_with_offset::<T>(0)
+    }
+
+    /// Pack the length of the next packed value plus `offset`
+    pub fn pack_length_with_offset<T: FromToPacket + Default>(
+        &mut self,
+        offset: i32,
+    ) -> Result<&mut Self, PacketError> {
+        // Only allow packing length if bit width is 0, and one length at a time
+        if self.bits != 0 || self.length_position.is_some() {
             return Err(PacketError::InvalidInstruction);
         }
 
         let bytes = std::mem::size_of::<T>();
-        self.length_positions.insert(LengthPosition {
-            position: self.position,
-            offset: 0,
+        if bytes > std::mem::size_of::<u64>() {
+            return Err(PacketError::InvalidInstruction);
+        }
+        let position = self.position;
+        self.pack_bytes(&[0; 8][..bytes])?;
+        self.length_position = Some(LengthPosition {
+            position,
+            offset,
             bytes,
         });
-
-        self.pack_bytes(vec![0; bytes])?;
         Ok(self)
     }
 
-    pub fn pack_length_with_offset<T: FromToPacket + Default>(
+    /// Pack with `f`, patching the length from the preceding `pack_length`
+    /// with the bytes packed, if any
+    pub fn pack_bounded(
+        &mut self,
+        f: impl FnOnce(&mut Packet) -> Result<(), PacketError>,
+    ) -> Result<(), PacketError> {
+        let Some(length_position) = self.length_position.take() else {
+            return f(self);
+        };
+        f(self)?;
+
+        // Bytes from after the length to the end of the value, plus the offset
+        let start = length_position.position + length_position.bytes;
+        let length = (self.get_bytes().len() - start) as i64 + length_position.offset as i64;
+        let length = u64::try_from(length).map_err(|_| PacketError::InvalidBytes)?;
+        let length_bytes = length.to_le_bytes();
+        if length_bytes[length_position.bytes..]
+            .iter()
+            .any(|b| *b != 0)
+        {
+            return Err(PacketError::InvalidBytes);
+        }
+        self.data.to_mut()[length_position.position..start]
+            .copy_from_slice(&length_bytes[..length_position.bytes]);
+        Ok(())
+    }
+
+    /// Unpack the length of the rest of the value
+    ///
+    /// The next unpack only sees the bytes the length covers, and must use all
+    /// of them.
+    pub fn unpack_length<T: FromToPacket + TryInto<usize>>(
+        &mut self,
+    ) -> Result<&mut Self, PacketError> {
+        self.unpack_length_with_offset::<T>(0)
+    }
+
+    /// Unpack the length packed with `pack_length_with_offset`
+    pub fn unpack_length_with_offset<T: FromToPacket + TryInto<usize>>(
         &mut self,
         offset: i32,
     ) -> Result<&mut Self, PacketError> {
-        // Only allow packing length if bit width is 0
+        // Only allow unpacking length if bit width is 0
         if self.bits != 0 {
             return Err(PacketError::InvalidInstruction);
         }
 
-        let bytes = std::mem::size_of::<T>();
-        self.length_positions.insert(LengthPosition {
-            position: self.position,
-            offset,
-            bytes,
-        });
+        let length: usize = T::from_packet(self)?
+            .try_into()
+            .map_err(|_| PacketError::InvalidBytes)?;
+        let length = usize::try_from(length as i64 - offset as i64)
+            .map_err(|_| PacketError::InvalidBytes)?;
+        let remaining = self.end() - self.position;
+        if length > remaining {
+            return Err(PacketError::Truncated { length, remaining });
+        }
+        self.length_view = Some(self.position..self.position + length);
+        Ok(self)
+    }
 
-        self.pack_bytes(vec![0; bytes])?;
+    /// Unpack with `f` within the bytes covered by the preceding
+    /// `unpack_length`, if any
+    pub fn unpack_bounded<T>(
+        &mut self,
+        f: impl FnOnce(&mut Packet) -> Result<T, PacketError>,
+    ) -> Result<T, PacketError> {
+        let Some(view) = self.length_view.take() else {
+            return f(self);
+        };
+        let outer_end = self.view_end.replace(view.end);
+        let value = f(self);
+        self.view_end = outer_end;
+        let value = value?;
+        if self.position != view.end || self.bit_position != 0 {
+            return Err(PacketError::TrailingBytes {
+                length: view.len(),
+                consumed: self.position - view.start,
+            });
+        }
+        Ok(value)
+    }
+
+    /// Next unpack leaves the last `length` bytes of the view to the fields
+    /// after it, e.g. to a signature after a value of any length
+    pub fn set_bytes_after(&mut self, length: usize) -> Result<&mut Self, PacketError> {
+        if self.bits != 0 {
+            return Err(PacketError::InvalidInstruction);
+        }
+        let end = self
+            .end()
+            .checked_sub(length)
+            .filter(|end| *end >= self.position)
+            .ok_or(PacketError::NotEnoughBytes)?;
+        self.length_view = Some(self.position..end);
         Ok(self)
     }
 
-    pub fn unpack_length<T: FromToPacket>(&mut self) -> Result<&mut Self, PacketError> {
-        // Only allow packing length if bit width is 0
+    /// End of the bytes the current view can use
+    fn end(&self) -> usize {
+        self.view_end.unwrap_or(self.get_bytes().len())
+    }
+
+    /// Next `Vec` has exactly this many elements
+    pub fn set_count(&mut self, count: usize) -> &mut Self {
+        self.count = Some(count);
+        self
+    }
+
+    /// Pack the element count of the next `Vec` before it
+    pub fn pack_count<T: FromToPacket + Default>(&mut self) -> Result<&mut Self, PacketError> {
         if self.bits != 0 {
             return Err(PacketError::InvalidInstruction);
         }
+        self.count_prefix = std::mem::size_of::<T>();
+        Ok(self)
+    }
 
-        // Ignore the length, for now at least
-        T::from_packet(self)?;
+    /// Unpack the element count of the next `Vec`
+    pub fn unpack_count<T: FromToPacket + TryInto<usize>>(
+        &mut self,
+    ) -> Result<&mut Self, PacketError> {
+        if self.bits != 0 {
+            return Err(PacketError::InvalidInstruction);
+        }
+        let count = T::from_packet(self)?
+            .try_into()
+            .map_err(|_| PacketError::InvalidBytes)?;
+        self.count = Some(count);
         Ok(self)
     }
 
+    /// Every element of the next `Vec` is this many bytes
+    pub fn set_element_length(&mut self, length: usize) -> &mut Self {
+        self.element_length = Some(length);
+        self
+    }
+
+    /// Unpack with `f` from the next `length` bytes, all of them must be used
+    fn unpack_element<T>(
+        &mut self,
+        length: usize,
+        f: impl FnOnce(&mut Packet) -> Result<T, PacketError>,
+    ) -> Result<T, PacketError> {
+        let mut element = Packet::from_slice(self.unpack_slice(length)?);
+        let value = f(&mut element)?;
+        if element.position != length || element.bit_position != 0 {
+            return Err(PacketError::InvalidBytes);
+        }
+        Ok(value)
+    }
+
+    /// Unpack the elements of the next `Vec` into `elements`, one per call of
+    /// `f`, see `Vec::from_packet`
+    pub(crate) fn unpack_elements<V>(
+        &mut self,
+        mut elements: V,
+        mut f: impl FnMut(V, &mut Packet) -> Result<V, PacketError>,
+    ) -> Result<V, PacketError> {
+        let count = self.count.take();
+        let element_length = self.element_length.take();
+        if count.is_none() && element_length == Some(0) {
+            return Err(PacketError::InvalidBytes);
+        }
+        let mut len = 0;
+        while count.map_or(self.position < self.end(), |count| len < count) {
+            let start = (self.position, self.bit_position);
+            elements = match element_length {
+                Some(length) => self.unpack_element(length, |element| f(elements, element))?,
+                None => f(elements, self)?,
+            };
+            // Elements of no bytes would never reach the end
+            if count.is_none() && (self.position, self.bit_position) == start {
+                return Err(PacketError::InvalidBytes);
+            }
+            len += 1;
+        }
+        Ok(elements)
+    }
+
+    /// Pack the `len` elements of the next `Vec`, `f` packs the element at an
+    /// index, see `Vec::to_packet`
+    pub(crate) fn pack_elements(
+        &mut self,
+        len: usize,
+        mut f: impl FnMut(usize, &mut Packet) -> Result<(), PacketError>,
+    ) -> Result<(), PacketError> {
+        let count = self.count.take();
+        let count_prefix = std::mem::take(&mut self.count_prefix);
+        let element_length = self.element_length.take();
+        if count.is_some_and(|count| count != len) {
+            return Err(PacketError::InvalidBytes);
+        }
+        if count_prefix > 0 {
+            let len = len.to_le_bytes();
+            if len[count_prefix..].iter().any(|b| *b != 0) {
+                return Err(PacketError::InvalidBytes);
+            }
+            self.pack_bytes(&len[..count_prefix])?;
+        }
+        for index in 0..len {
+            let start = self.position;
+            f(index, self)?;
+            if element_length.is_some_and(|length| self.position - start != length) {
+                return Err(PacketError::InvalidBytes);
+            }
+        }
+        Ok(())
+    }
+
+    /// Unpack the field `path` with `f`, adding where it failed to the error
+    pub fn unpack_field<T>(
+        &mut self,
+        path: &'static str,
+        f: impl FnOnce(&mut Packet) -> Result<T, PacketError>,
+    ) -> Result<T, PacketError> {
+        self.unpack_field_with(|| Cow::Borrowed(path), f)
+    }
+
+    /// `unpack_field` with the path made only if unpacking fails
+    pub fn unpack_field_with<T>(
+        &mut self,
+        path: impl FnOnce() -> Cow<'static, str>,
+        f: impl FnOnce(&mut Packet) -> Result<T, PacketError>,
+    ) -> Result<T, PacketError> {
+        let offset = self.position;
+        let bit_offset = self.bit_position;
+        f(self).map_err(|error| match error {
+            PacketError::Field(mut field) => {
+                field.path.insert(0, path());
+                PacketError::Field(field)
+            }
+            error => {
+                let end = self.end().clamp(offset, offset + FieldError::MAX_BYTES);
+                PacketError::Field(Box::new(FieldError {
+                    error,
+                    offset,
+                    bit_offset,
+                    path: vec![path()],
+                    bytes: self.get_bytes()[offset..end].to_vec(),
+                }))
+            }
+        })
+    }
+
     pub fn unpack<T: FromToPacket>(&mut self) -> Result<T, PacketError> {
-        T::from_packet(self)
+        self.unpack_bounded(T::from_packet)
+    }
+
+    /// Unpack the variant of `T` with the id unpacked before it
+    pub fn unpack_variant<I: FromToPacket, T: PacketVariant<I>>(
+        &mut self,
+        id: &I,
+    ) -> Result<T, PacketError> {
+        self.unpack_bounded(|bytes| T::from_packet_variant(id, bytes))
+    }
+
+    /// Pack the variant without its id, which must be `id`
+    pub fn pack_variant<I: FromToPacket + PartialEq, T: PacketVariant<I>>(
+        &mut self,
+        value: &T,
+        id: &I,
+    ) -> Result<&mut Self, PacketError> {
+        if value.get_id() != *id {
+            return Err(PacketError::InvalidBytes);
+        }
+        self.pack_bounded(|bytes| value.to_packet_variant(bytes))?;
+        Ok(self)
     }
 
     pub fn pack<T: FromToPacket>(&mut self, bytes: &T) -> Result<&mut Self, PacketError> {
-        bytes.to_packet(self)?;
+        self.pack_bounded(|packet| bytes.to_packet(packet))?;
         Ok(self)
     }
 
-    fn pack_bytes<T: Into<Vec<u8>>>(&mut self, bytes: T) -> Result<&mut Self, PacketError> {
-        let bytes: Vec<u8> = bytes.into();
+    pub(crate) fn pack_bytes(&mut self, bytes: &[u8]) -> Result<&mut Self, PacketError> {
+        let position = self.position;
         if self.bits != 0 {
             // Use instruction.bits as *length* of the instruction
-            let byte_count = (self.bit_position + self.bits + 7) / 8;
-
-            // Resize to accommodate the instruction size
-            if self.data.len() < self.position + byte_count {
-                self.data.resize(self.position + byte_count, 0);
-            }
+            let byte_count = (self.bit_position + self.bits).div_ceil(8);
 
             // Note: bit_position is guaranteed to be 0-7
             let end_bit = self.bit_position + self.bits - 1;
 
-            // Get n-amount of bits from the `bytes` to be stored in the `data` array
-            let input_bytes = get_bits_le(&bytes, (0, self.bits - 1));
-            let mut mut_bytes = &mut self.data[self.position..self.position + byte_count];
-            set_bits_le(&mut mut_bytes, (self.bit_position, end_bit), &input_bytes);
-            self.progress_bits();
-
-            // for i in 0..byte_count {
-            //     let byte_index = self.position + i;
-
-            //     // Calculate the start and end bits for the current byte (zero-indexed)
-            //     let end_bit_in_byte = if i == byte_count - 1 { end_bit % 8 } else { 7 };
-            //     let start_bit_in_byte = if i == 0 { self.bit_position } else { 0 };
-
-            //     // Zero out the bits in the byte (between start_bit and end_bit)
-            //     let mask =
-            //         ((1 << (end_bit_in_byte - start_bit_in_byte + 1)) - 1) << start_bit_in_byte;
-            //     self.data[byte_index] &= !mask;
+            // Resize to accommodate the instruction size
+            let data = self.data.to_mut();
+            if data.len() < position + byte_count {
+                data.resize(position + byte_count, 0);
+            }
 
-            //     // Store the section from input_bytes into the data array
-            //     let input_byte = input_bytes[i];
-            //     let input_mask = (1 << (end_bit_in_byte - start_bit_in_byte + 1)) - 1;
-            //     let extracted_bits = input_byte & input_mask;
-            //     self.data[byte_index] |= extracted_bits << start_bit_in_byte;
-            // }
+            // Store n-amount of bits from the `bytes` in the `data` array
+            set_bits_le(
+                &mut data[position..position + byte_count],
+                (self.bit_position, end_bit),
+                bytes,
+            );
+            self.progress_bits();
         } else {
             let size = bytes.len();
-            if self.data.len() < self.position + size {
-                self.data.resize(self.position + size, 0);
+            let data = self.data.to_mut();
+            if data.len() < position + size {
+                data.resize(position + size, 0);
             }
 
             // No instruction, just copy the bytes
-            self.data[self.position..self.position + size].copy_from_slice(&bytes);
+            data[position..position + size].copy_from_slice(bytes);
             self.progress_bytes(size);
         }
         Ok(self)
     }
 
-    fn unpack_bytes(&mut self, size: usize) -> Result<Vec<u8>, PacketError> {
-        let val = self.try_unpack_bytes(size)?;
+    /// Unpack `N` bytes, or the bits from `set_bits` padded to `N` bytes
+    fn unpack_array<const N: usize>(&mut self) -> Result<[u8; N], PacketError> {
+        let mut out = [0u8; N];
         if self.bits != 0 {
+            let byte_count = (self.bit_position + self.bits).div_ceil(8);
+            if self.position + byte_count > self.end() {
+                return Err(PacketError::NotEnoughBytes);
+            }
+            get_bits_le(
+                &self.get_bytes()[self.position..self.position + byte_count],
+                (self.bit_position, self.bit_position + self.bits - 1),
+                &mut out,
+            );
             self.progress_bits();
         } else {
-            self.progress_bytes(size);
+            out.copy_from_slice(self.unpack_slice(N)?);
         }
-        Ok(val)
+        Ok(out)
     }
 
-    fn try_unpack_bytes(&self, size: usize) -> Result<Vec<u8>, PacketError> {
+    /// Unpack the next `size` bytes without copying them
+    pub(crate) fn unpack_slice(&mut self, size: usize) -> Result<&[u8], PacketError> {
         if self.bits != 0 {
-            let byte_count = (self.bit_position + self.bits + 7) / 8;
-            if self.position + byte_count > self.data.len() {
-                return Err(PacketError::NotEnoughBytes);
-            }
-            let out = get_bits_le(
-                &self.data[self.position..self.position + byte_count],
-                (self.bit_position, self.bit_position + self.bits - 1),
-            );
-            return Ok(out);
+            return Err(PacketError::InvalidInstruction);
         }
-        if self.position + size > self.data.len() {
+        let start = self.position;
+        if start + size > self.end() {
             return Err(PacketError::NotEnoughBytes);
         }
-        // No instruction, just copy the bytes
-        let bytes = &self.data[self.position..self.position + size];
-        Ok(bytes.to_vec())
+        self.progress_bytes(size);
+        Ok(&self.get_bytes()[start..start + size])
     }
 
     fn progress_bits(&mut self) {
@@ -691,9 +1095,12 @@ mod tests {
     #[test]
     fn test_pack_length() {
         let mut packet = Packet::new();
-        // Length from *current* position to the end of the packet,
-        packet.pack_length::<u8>().unwrap();
-        packet.pack_bytes(&[0xA, 0xB, 0xC]).unwrap();
+        // Length of the value packed after it
+        packet
+            .pack_length::<u8>()
+            .unwrap()
+            .pack(&[0xA_u8, 0xB, 0xC])
+            .unwrap();
         assert_eq!(packet.get_bytes(), &[0x03, 0xA, 0xB, 0xC]);
     }
 
@@ -702,11 +1109,14 @@ mod tests {
         let mut packet = Packet::new();
         packet.pack_bytes(&[0x1, 0x2, 0x3]).unwrap();
 
-        // Length from *current* position to the end of the packet
-        packet.pack_length::<u16>().unwrap();
-        packet.pack_bytes(&[0xA, 0xB, 0xC]).unwrap();
+        // Length of the value packed after it
+        packet
+            .pack_length::<u16>()
+            .unwrap()
+            .pack(&[0xA_u8, 0xB, 0xC])
+            .unwrap();
 
-        // It should be 3 bytes from the current position to the end of the packet
+        // It should be the 3 bytes of the value
         assert_eq!(
             packet.get_bytes(),
             &[
@@ -722,11 +1132,14 @@ mod tests {
         let mut packet = Packet::new();
         packet.pack_bytes(&[0x1, 0x2, 0x3]).unwrap();
 
-        // Length from *current* position to the end of the packet
-        packet.pack_length_with_offset::<u16>(-2).unwrap();
-        packet.pack_bytes(&[0xA, 0xB, 0xC]).unwrap();
+        // Length of the value packed after it, minus 2
+        packet
+            .pack_length_with_offset::<u16>(-2)
+            .unwrap()
+            .pack(&[0xA_u8, 0xB, 0xC])
+            .unwrap();
 
-        // It should be 3 bytes from the current position to the end of the packet
+        // It should be the 3 bytes of the value minus 2
         assert_eq!(
             packet.get_bytes(),
             &[
@@ -737,16 +1150,126 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_pack_length_nested() {
+        // Lengths cover only their value, not what is packed after it
+        let mut packet = Packet::new();
+        packet
+            .pack_length::<u8>()
+            .unwrap()
+            .pack_bounded(|p| {
+                p.pack::<u8>(&0xA)?;
+                p.pack_length::<u8>()?.pack(&[0xB_u8, 0xC])?;
+                Ok(())
+            })
+            .unwrap();
+        packet.pack::<u8>(&0xD).unwrap();
+        assert_eq!(packet.get_bytes(), &[0x04, 0xA, 0x02, 0xB, 0xC, 0xD]);
+
+        // Length must fit its type
+        let mut packet = Packet::new();
+        assert_eq!(
+            packet.pack_length::<u8>().unwrap().pack(&vec![0_u8; 256]),
+            Err(PacketError::InvalidBytes)
+        );
+    }
+
+    #[test]
+    fn test_pack_with_buffer() {
+        let mut buffer = Vec::with_capacity(8);
+        buffer.push(0xFF);
+        let capacity = buffer.capacity();
+
+        let mut packet = Packet::with_buffer(&mut buffer);
+        packet.pack::<u8>(&0x01).unwrap();
+        packet
+            .pack_length::<u8>()
+            .unwrap()
+            .pack::<u16>(&0x0302)
+            .unwrap();
+        assert_eq!(packet.get_bytes(), &[0x01, 0x02, 0x02, 0x03]);
+
+        // Buffer is cleared and reused
+        assert_eq!(buffer, &[0x01, 0x02, 0x02, 0x03]);
+        assert_eq!(buffer.capacity(), capacity);
+        0x0504_u16.write_to(&mut buffer).unwrap();
+        assert_eq!(buffer, &[0x04, 0x05]);
+        assert_eq!(buffer.capacity(), capacity);
+    }
+
+    #[test]
+    fn test_unpack_borrowed() {
+        let bytes = [0x01, 0x02, 0x03];
+        let mut packet = Packet::from_slice(&bytes);
+        assert_eq!(packet.unpack::<u8>(), Ok(0x01));
+        assert_eq!(packet.get_bytes().as_ptr(), bytes.as_ptr());
+
+        // Packing into borrowed bytes copies them first
+        packet.pack::<u8>(&0xFF).unwrap();
+        assert_eq!(packet.get_bytes(), &[0x01, 0xFF, 0x03]);
+        assert_eq!(bytes, [0x01, 0x02, 0x03]);
+    }
+
     #[test]
     fn test_unpack_length() {
         let mut packet = Packet::from_slice(&[0x03, 0xA, 0xB, 0xC]);
         packet.unpack_length::<u8>().unwrap();
-        let value = packet.unpack::<u8>().unwrap();
-        assert_eq!(value, 0xA);
+        let value = packet.unpack::<Vec<u8>>().unwrap();
+        assert_eq!(value, vec![0xA, 0xB, 0xC]);
 
         assert_eq!(packet.get_bytes(), &[0x3, 0xA, 0xB, 0xC]);
     }
 
+    #[test]
+    fn test_unpack_length_bounds_vec() {
+        // Vec stops at the end of the length, not at the end of the packet
+        let mut packet = Packet::from_slice(&[0x02, 0xA, 0xB, 0xC]);
+        assert_eq!(
+            packet.unpack_length::<u8>().unwrap().unpack::<Vec<u8>>(),
+            Ok(vec![0xA, 0xB])
+        );
+        assert_eq!(packet.unpack::<u8>(), Ok(0xC));
+
+        // Reads can't cross the end of the length
+        let mut packet = Packet::from_slice(&[0x01, 0xA, 0xB]);
+        assert_eq!(
+            packet.unpack_length::<u8>().unwrap().unpack::<u16>(),
+            Err(PacketError::NotEnoughBytes)
+        );
+    }
+
+    #[test]
+    fn test_unpack_length_mismatch() {
+        let mut packet = Packet::from_slice(&[0x03, 0xA, 0xB, 0xC]);
+        assert_eq!(
+            packet.unpack_length::<u8>().unwrap().unpack::<u16>(),
+            Err(PacketError::TrailingBytes {
+                length: 3,
+                consumed: 2
+            })
+        );
+
+        let mut packet = Packet::from_slice(&[0x04, 0xA, 0xB, 0xC]);
+        assert_eq!(
+            packet.unpack_length::<u8>().map(|_| ()),
+            Err(PacketError::Truncated {
+                length: 4,
+                remaining: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_unpack_length_with_offset() {
+        // Length doesn't cover the u16 after it
+        let mut packet = Packet::from_slice(&[0x01, 0x00, 0x01, 0x02, 0xA]);
+        packet.unpack_length_with_offset::<u16>(-2).unwrap();
+        assert_eq!(
+            packet.unpack_bounded(|p| Ok((p.unpack::<u16>()?, p.unpack::<u8>()?))),
+            Ok((0x0201, 0xA))
+        );
+    }
+
     #[test]
     fn test_pack_24bit_field() {
         let mut packet = Packet::new();
@@ -766,4 +1289,163 @@ mod tests {
         assert_eq!(packet.set_bits(1).next_if_eq(&[0x01]), true);
         assert_eq!(packet.set_bits(6).unpack::<u8>(), Ok(0b0011_1111));
     }
+
+    #[test]
+    fn test_unpack_field() {
+        let mut packet = Packet::from_slice(&[0x01, 0x02]);
+        let error = packet
+            .unpack_field("Outer.inner", |p| {
+                p.unpack::<u8>()?;
+                p.unpack_field("Inner.value", |p| p.unpack::<u16>())
+            })
+            .unwrap_err();
+        assert_eq!(
+            error,
+            PacketError::Field(Box::new(FieldError {
+                error: PacketError::NotEnoughBytes,
+                offset: 1,
+                bit_offset: 0,
+                path: vec!["Outer.inner".into(), "Inner.value".into()],
+                bytes: vec![0x02],
+            }))
+        );
+        assert_eq!(error.kind(), &PacketError::NotEnoughBytes);
+        assert_eq!(
+            error.to_string(),
+            "Not enough bytes at offset 1 in Outer.inner > Inner.value: 02"
+        );
+    }
+
+    #[test]
+    fn test_vec_count() {
+        let mut packet = Packet::from_slice(&[0x01, 0x02, 0x03]);
+        assert_eq!(
+            packet.set_count(2).unpack::<Vec<u8>>(),
+            Ok(vec![0x01, 0x02])
+        );
+        assert_eq!(packet.unpack::<u8>(), Ok(0x03));
+
+        let mut packet = Packet::from_slice(&[0x01, 0x02, 0x00]);
+        assert_eq!(
+            packet.set_count(2).unpack::<Vec<u16>>(),
+            Err(PacketError::NotEnoughBytes)
+        );
+
+        let mut packet = Packet::new();
+        packet.set_count(2).pack(&vec![0x01_u8, 0x02]).unwrap();
+        assert_eq!(packet.get_bytes(), &[0x01, 0x02]);
+        assert_eq!(
+            packet.set_count(3).pack(&vec![0x01_u8]),
+            Err(PacketError::InvalidBytes)
+        );
+    }
+
+    #[test]
+    fn test_vec_count_prefix() {
+        let mut packet = Packet::from_slice(&[0x02, 0x01, 0x00, 0x02, 0x00, 0xFF]);
+        assert_eq!(
+            packet.unpack_count::<u8>().unwrap().unpack::<Vec<u16>>(),
+            Ok(vec![0x0001, 0x0002])
+        );
+        assert_eq!(packet.unpack::<u8>(), Ok(0xFF));
+
+        let mut packet = Packet::new();
+        packet
+            .pack_count::<u16>()
+            .unwrap()
+            .pack(&vec![0x01_u8, 0x02, 0x03])
+            .unwrap();
+        packet.pack::<u8>(&0xFF).unwrap();
+        assert_eq!(packet.get_bytes(), &[0x03, 0x00, 0x01, 0x02, 0x03, 0xFF]);
+
+        let mut packet = Packet::new();
+        assert_eq!(
+            packet.pack_count::<u8>().unwrap().pack(&vec![0_u8; 256]),
+            Err(PacketError::InvalidBytes)
+        );
+    }
+
+    #[test]
+    fn test_bytes_after() {
+        let mut packet = Packet::from_slice(&[0x01, 0x02, 0x03, 0xFF]);
+        assert_eq!(
+            packet.set_bytes_after(1).unwrap().unpack::<Vec<u8>>(),
+            Ok(vec![0x01, 0x02, 0x03])
+        );
+        assert_eq!(packet.unpack::<u8>(), Ok(0xFF));
+
+        let mut packet = Packet::from_slice(&[0x01]);
+        assert_eq!(
+            packet.set_bytes_after(2).map(|_| ()),
+            Err(PacketError::NotEnoughBytes)
+        );
+    }
+
+    #[test]
+    fn test_vec_element_length() {
+        // (Handle, value) pairs with the value to the end of the element
+        let mut packet = Packet::from_slice(&[0x01, 0x00, 0xAA, 0xBB, 0x02, 0x00, 0xCC, 0xDD]);
+        assert_eq!(
+            packet.set_element_length(4).unpack::<Vec<(u16, Vec<u8>)>>(),
+            Ok(vec![(0x0001, vec![0xAA, 0xBB]), (0x0002, vec![0xCC, 0xDD])])
+        );
+
+        let mut packet = Packet::from_slice(&[0x01, 0x00, 0xAA]);
+        assert_eq!(
+            packet.set_element_length(4).unpack::<Vec<(u16, Vec<u8>)>>(),
+            Err(PacketError::NotEnoughBytes)
+        );
+
+        // Element must use all of its bytes
+        let mut packet = Packet::from_slice(&[0x01, 0x00, 0xAA]);
+        assert_eq!(
+            packet.set_element_length(3).unpack::<Vec<u16>>(),
+            Err(PacketError::InvalidBytes)
+        );
+
+        let mut packet = Packet::new();
+        let pairs = vec![(0x0001_u16, vec![0xAA_u8, 0xBB]), (0x0002, vec![0xCC])];
+        assert_eq!(
+            packet.set_element_length(4).pack(&pairs),
+            Err(PacketError::InvalidBytes)
+        );
+
+        // Nor half of a byte
+        let mut packet = Packet::from_slice(&[0x01, 0x00, 0xAB]);
+        assert_eq!(
+            packet.set_element_length(3).unpack::<Vec<(u16, Nibble)>>(),
+            Err(PacketError::InvalidBytes)
+        );
+    }
+
+    /// Low four bits of a byte
+    #[derive(Debug, PartialEq)]
+    struct Nibble(u8);
+
+    impl FromToPacket for Nibble {
+        fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
+            Ok(Nibble(bytes.set_bits(4).unpack()?))
+        }
+        fn to_packet(&self, bytes: &mut Packet) -> Result<(), PacketError> {
+            bytes.set_bits(4).pack(&self.0)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_vec_empty_elements() {
+        // Elements of no bytes never reach the end
+        let mut packet = Packet::from_slice(&[0x01]);
+        assert_eq!(
+            packet.unpack::<Vec<[u8; 0]>>(),
+            Err(PacketError::InvalidBytes)
+        );
+
+        // Unless they are counted
+        let mut packet = Packet::from_slice(&[0x01]);
+        assert_eq!(
+            packet.set_count(2).unpack::<Vec<[u8; 0]>>(),
+            Ok(vec![[], []])
+        );
+    }
 }
@@ -5,7 +5,11 @@ use crate::messages::*;
 use crate::packer::*;
 
 use crate::hcimanager::HciError;
+use crate::hcimanager::Layer;
 use crate::hcimanager::MsgProcessor;
+use crate::hcimanager::ProtocolError;
+use crate::routing::CID_SMP;
+use crate::routing::Interest;
 
 /// Take raw connection handle and returns PairedConnection, or SmpPairingFailure
 pub struct PairingHandler {
@@ -94,6 +98,20 @@ impl<'a> PairingHandler {
             .collect())
     }
 
+    /// Pairing Request and Pairing Response, which must precede the rest of
+    /// the pairing PDUs
+    fn pairing_exchange(&self, pdu: &SmpPdu) -> Result<([u8; 7], [u8; 7]), HciError> {
+        match (self.preq, self.pres) {
+            (Some(preq), Some(pres)) => Ok((preq, pres)),
+            _ => Err(HciError::Protocol(ProtocolError {
+                connection: Some(self.connection_handle.clone()),
+                layer: Layer::Smp,
+                pdu: Some(pdu.get_id()),
+                reason: "Pairing Request was not received".to_string(),
+            })),
+        }
+    }
+
     fn produce_cmd(&mut self, cmd: Vec<HciCommand>) -> Result<Vec<AppMsg>, HciError> {
         Ok(cmd
             .into_iter()
@@ -114,6 +132,15 @@ impl<'a> PairingHandler {
             ..
         } = packet
         {
+            // Reserved values or key size outside of 7..=16 are invalid parameters
+            if matches!(payload.io_capability, IOCapability::Reserved(_))
+                || matches!(payload.oob_data_flag, OOBDataFlag::Reserved(_))
+                || !(7..=16).contains(&payload.max_encryption_key_size)
+            {
+                let pairing_failed = SmpPdu::PairingFailed(SmpPairingFailure::InvalidParameters);
+                return self.produce_smp(vec![pairing_failed]);
+            }
+
             let pres = SmpPdu::PairingResponse(SmpPairingReqRes {
                 authentication_requirements: AuthenticationRequirements {
                     bonding: true,
@@ -152,16 +179,17 @@ impl<'a> PairingHandler {
 
         // 3. Wait for SmpPdu::PairingConfirm
         if let HciAcl {
-            msg: L2CapMessage::Smp(SmpPdu::PairingConfirmation(ref value)),
+            msg: L2CapMessage::Smp(ref pdu @ SmpPdu::PairingConfirmation(ref value)),
             ..
         } = packet
         {
+            let (preq, pres) = self.pairing_exchange(pdu)?;
             self.peer_confirm_value = Some(value.confirm_value);
             let server_confirm_value = u128::from_le_bytes(c1_rev(
                 &[0; 16],
                 &self.server_random.to_le_bytes(),
-                &self.pres.unwrap(),
-                &self.preq.unwrap(),
+                &pres,
+                &preq,
                 self.peer_address_type.to_bytes()[0],
                 &self.peer_address.to_bytes().try_into().unwrap(),
                 self.server_address_type.to_bytes()[0],
@@ -177,16 +205,17 @@ impl<'a> PairingHandler {
 
         // 5. Wait for SmpPdu::PairingRandom
         if let HciAcl {
-            msg: L2CapMessage::Smp(SmpPdu::PairingRandom(ref value)),
+            msg: L2CapMessage::Smp(ref pdu @ SmpPdu::PairingRandom(ref value)),
             ..
         } = packet
         {
+            let (preq, pres) = self.pairing_exchange(pdu)?;
             self.peer_random = Some(value.random_value);
             let peer_confirm_value = u128::from_le_bytes(c1_rev(
                 &[0; 16],
                 &value.random_value.to_le_bytes(),
-                &self.pres.unwrap(),
-                &self.preq.unwrap(),
+                &pres,
+                &preq,
                 self.peer_address_type.to_bytes()[0],
                 &self.peer_address.to_bytes().try_into().unwrap(),
                 self.server_address_type.to_bytes()[0],
@@ -194,9 +223,10 @@ impl<'a> PairingHandler {
             ));
 
             if self.peer_confirm_value != Some(peer_confirm_value) {
-                println!(
-                    "Configuration failed server {:?} peer {:?}",
-                    self.peer_confirm_value, peer_confirm_value
+                log::warn!(
+                    "Confirm value mismatch, received {:?} computed {:?}",
+                    self.peer_confirm_value,
+                    peer_confirm_value
                 );
                 // 6. Send SmpPdu::PairingFailed
                 let pairing_failed = SmpPdu::PairingFailed(SmpPairingFailure::ConfirmValueFailed);
@@ -275,6 +305,16 @@ impl MsgProcessor for PairingHandler {
             _ => Ok(vec![]),
         }
     }
+
+    fn interest(&self) -> Interest {
+        Interest {
+            connection: Some(self.connection_handle.clone()),
+            channels: Some(vec![CID_SMP]),
+            // Encryption Change and LE Meta
+            events: Some(vec![0x08, 0x3E]),
+            opcodes: Some(vec![]),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -483,4 +523,86 @@ mod tests {
             "Ensure central identification is sent"
         );
     }
+
+    #[test]
+    fn test_pairing_request_reserved_io_capability() {
+        let mut pairing_handler = PairingHandler::new(
+            LeConnectionComplete {
+                status: HciStatus::Success,
+                connection_handle: ConnectionHandle(64),
+                role: Role::Peripheral,
+                peer_address_type: AddressType::Public,
+                peer_address: BdAddr([38, 14, 214, 232, 194, 80]),
+                connection_interval: 48,
+                peripheral_latency: 0,
+                supervision_timeout: 960,
+                central_clock_accuracy: ClockAccuracy::Ppm250,
+            },
+            BdAddr([6, 51, 116, 214, 86, 211]),
+            AddressType::Random,
+            49055469533520638048878300062363381969,
+            282764688399516531784019739061630454460,
+            723151060346651216,
+        );
+
+        // Pairing request with IO capability 0x05 (reserved)
+        let mut packet = Packet::from_slice(&[
+            0x02, 0x40, 0x20, 0x0b, 0x00, 0x07, 0x00, 0x06, 0x00, 0x01, 0x05, 0x00, 0x2d, 0x10,
+            0x0e, 0x0f,
+        ]);
+        let preq = packet.unpack::<H4Packet>().unwrap();
+        let res = pairing_handler.process(AppMsg::Recv(preq)).unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(
+            res[0],
+            AppMsg::Send(H4Packet::Acl(HciAcl {
+                connection_handle: ConnectionHandle(64),
+                pb: PacketBoundaryFlag::FirstNonFlushable,
+                bc: BroadcastFlag::PointToPoint,
+                msg: L2CapMessage::Smp(SmpPdu::PairingFailed(SmpPairingFailure::InvalidParameters)),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_pairing_confirm_before_request() {
+        let mut pairing_handler = PairingHandler::new(
+            LeConnectionComplete {
+                status: HciStatus::Success,
+                connection_handle: ConnectionHandle(64),
+                role: Role::Peripheral,
+                peer_address_type: AddressType::Public,
+                peer_address: BdAddr([38, 14, 214, 232, 194, 80]),
+                connection_interval: 48,
+                peripheral_latency: 0,
+                supervision_timeout: 960,
+                central_clock_accuracy: ClockAccuracy::Ppm250,
+            },
+            BdAddr([6, 51, 116, 214, 86, 211]),
+            AddressType::Random,
+            49055469533520638048878300062363381969,
+            282764688399516531784019739061630454460,
+            723151060346651216,
+        );
+
+        let res = pairing_handler.process(AppMsg::Recv(H4Packet::Acl(HciAcl {
+            connection_handle: ConnectionHandle(64),
+            pb: PacketBoundaryFlag::FirstNonFlushable,
+            bc: BroadcastFlag::PointToPoint,
+            msg: L2CapMessage::Smp(SmpPdu::PairingConfirmation(SmpPairingConfirmation {
+                confirm_value: 1,
+            })),
+        })));
+        let error = HciError::Protocol(ProtocolError {
+            connection: Some(ConnectionHandle(64)),
+            layer: Layer::Smp,
+            pdu: Some(0x03),
+            reason: "Pairing Request was not received".to_string(),
+        });
+        assert_eq!(
+            error.to_string(),
+            "Smp protocol error on connection 0x0040 in PDU 0x03: Pairing Request was not received"
+        );
+        assert_eq!(res, Err(error));
+    }
 }
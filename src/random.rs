@@ -0,0 +1,55 @@
+/// Source of random numbers for keys and pairing values
+///
+/// Injected into `HciManager` so that tests can make them predictable.
+pub trait Random {
+    fn fill(&mut self, buf: &mut [u8]);
+
+    fn next_u64(&mut self) -> u64 {
+        let mut buf = [0; 8];
+        self.fill(&mut buf);
+        u64::from_le_bytes(buf)
+    }
+
+    fn next_u128(&mut self) -> u128 {
+        let mut buf = [0; 16];
+        self.fill(&mut buf);
+        u128::from_le_bytes(buf)
+    }
+
+    /// Number from `0` to `n - 1`
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// Random numbers from the operating system
+#[derive(Debug, Clone, Default)]
+pub struct SystemRandom;
+
+impl Random for SystemRandom {
+    fn fill(&mut self, buf: &mut [u8]) {
+        getrandom::fill(buf).expect("Operating system random source failed");
+    }
+}
+
+/// Repeatable random numbers from a seed, for generating test values
+#[derive(Debug, Clone)]
+pub struct SeededRandom(u64);
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> Self {
+        // Xorshift never leaves the zero state
+        SeededRandom(seed.max(1))
+    }
+}
+
+impl Random for SeededRandom {
+    fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            chunk.copy_from_slice(&self.0.to_le_bytes()[..chunk.len()]);
+        }
+    }
+}
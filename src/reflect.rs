@@ -0,0 +1,952 @@
+//! `FromToPacket` by walking the facet shape of a type at runtime
+//!
+//! `Reflected` reads the same doc comment attributes as
+//! `#[derive(FromToPacket)]`, from the docs facet keeps in the shape, so a new
+//! message type only needs `#[derive(Facet)]`. It packs the same bytes as the
+//! derive does, see `tests/reflect_test.rs`, but it's slower and the errors of
+//! invalid bytes may differ.
+//!
+//! Enums need a `#[repr(u8)]` for facet, and their `id_type` must be one of
+//! `ID_TYPES`. Ids are integers or tuple structs of integers, e.g. `0x01` or
+//! `OpCode(0x0006, 0x01)`, and `present_if` compares a field with such an id
+//! using `==` or `!=`.
+
+use std::borrow::Cow;
+use std::mem::MaybeUninit;
+
+use facet::{
+    ConstTypeId, Def, EnumRepr, Facet, Field, FieldFlags, PtrConst, PtrMut, PtrUninit, Shape,
+    Struct, StructKind, TypeParam, Variant,
+};
+use facet_reflect::{Peek, ReflectError};
+
+use crate::messages::OpCode;
+use crate::packer::{FixedSizeUtf8, FromToPacket, Packet, PacketError};
+
+/// Types an `id_type` can name, the shape only has the name of the type
+const ID_TYPES: &[&Shape] = &[u8::SHAPE, u16::SHAPE, u32::SHAPE, u64::SHAPE, OpCode::SHAPE];
+
+/// Packs and unpacks the value by reflection, e.g.
+/// `Reflected(H4Packet::Command(HciCommand::Reset)).to_bytes()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reflected<T>(pub T);
+
+impl<T: Facet + 'static> FromToPacket for Reflected<T> {
+    fn from_packet(bytes: &mut Packet) -> Result<Self, PacketError> {
+        let mut value = MaybeUninit::<T>::uninit();
+        unpack(T::SHAPE, PtrUninit::from_maybe_uninit(&mut value), bytes)?;
+        // SAFETY: the value is initialized when `unpack` succeeds
+        Ok(Reflected(unsafe { value.assume_init() }))
+    }
+    fn to_packet(&self, bytes: &mut Packet) -> Result<(), PacketError> {
+        let value = Value {
+            data: PtrConst::new(&raw const self.0),
+            shape: T::SHAPE,
+        };
+        pack(value, bytes)
+    }
+}
+
+impl From<ReflectError> for PacketError {
+    fn from(error: ReflectError) -> Self {
+        PacketError::Unspecified(error.to_string())
+    }
+}
+
+fn unsupported(what: String) -> PacketError {
+    PacketError::Unspecified(format!("Not supported by reflection: {}", what))
+}
+
+/// Runs `$call` with `$type` as the length type named `$name`, e.g. `u16` for
+/// `prepend_length = u16`
+macro_rules! with_length_type {
+    ($name:expr, $type:ident => $call:expr) => {
+        match $name {
+            "u8" => {
+                type $type = u8;
+                $call
+            }
+            "u16" => {
+                type $type = u16;
+                $call
+            }
+            "u32" => {
+                type $type = u32;
+                $call
+            }
+            "u64" => {
+                type $type = u64;
+                $call
+            }
+            name => return Err(unsupported(format!("length type `{}`", name))),
+        }
+    };
+}
+
+/// Value of the attribute in the doc comment lines, e.g. `12` for
+/// `/// bits = 12`, the last one counts like in the derive
+fn attribute(doc: &[&'static str], key: &str) -> Option<&'static str> {
+    doc.iter().rev().find_map(|line| {
+        let (name, value) = line.split_once('=')?;
+        (name.trim() == key).then_some(value.trim())
+    })
+}
+
+/// Integer literal, e.g. `0x0E`, `12` or `0b1010_0000u8`
+fn integer(literal: &str) -> Result<u128, PacketError> {
+    let literal = literal.trim().replace('_', "");
+    let literal = literal.split(['u', 'i']).next().unwrap_or_default();
+    let (digits, radix) = match literal.get(..2) {
+        Some("0x" | "0X") => (&literal[2..], 16),
+        Some("0o") => (&literal[2..], 8),
+        Some("0b") => (&literal[2..], 2),
+        _ => (literal, 10),
+    };
+    u128::from_str_radix(digits, radix).map_err(|_| unsupported(format!("integer `{}`", literal)))
+}
+
+/// Integers of an id, e.g. `[0x0006, 0x01]` for `OpCode(0x0006, 0x01)`
+fn literal(expr: &str) -> Result<Vec<u128>, PacketError> {
+    match expr.split_once('(') {
+        Some((_, arguments)) => arguments
+            .trim_end()
+            .strip_suffix(')')
+            .ok_or_else(|| unsupported(format!("id `{}`", expr)))?
+            .split(',')
+            .filter(|argument| !argument.trim().is_empty())
+            .map(integer)
+            .collect(),
+        None => Ok(vec![integer(expr)?]),
+    }
+}
+
+/// Integers of an earlier field by its name
+type Lookup<'a> = dyn Fn(&str) -> Result<Vec<u128>, PacketError> + 'a;
+
+/// Count, element length or bits, a number or an earlier field
+fn number(value: &str, lookup: &Lookup) -> Result<usize, PacketError> {
+    let integers = match integer(value) {
+        Ok(number) => vec![number],
+        Err(_) => lookup(value)?,
+    };
+    match integers[..] {
+        [number] => usize::try_from(number).map_err(|_| PacketError::InvalidBytes),
+        _ => Err(unsupported(format!("number `{}`", value))),
+    }
+}
+
+/// Does the `present_if` condition hold, e.g.
+/// `command_opcode != OpCode(0x0000, 0x00)`
+fn holds(condition: &str, lookup: &Lookup) -> Result<bool, PacketError> {
+    let (field, equal, value) = match (condition.split_once("!="), condition.split_once("==")) {
+        (Some((field, value)), _) => (field, false, value),
+        (None, Some((field, value))) => (field, true, value),
+        (None, None) => return Err(unsupported(format!("condition `{}`", condition))),
+    };
+    Ok((lookup(field.trim())? == literal(value)?) == equal)
+}
+
+/// Shape of the `id_type` of the item
+fn id_type(shape: &Shape) -> Result<&'static Shape, PacketError> {
+    let name = attribute(shape.doc, "id_type")
+        .ok_or_else(|| unsupported(format!("{} without `id_type`", shape)))?;
+    ID_TYPES
+        .iter()
+        .find(|id_type| id_type.to_string() == name)
+        .copied()
+        .ok_or_else(|| unsupported(format!("id type `{}`", name)))
+}
+
+/// Integers of the id of the variant, `None` for the passthrough `id = _`
+fn variant_id(variant: &Variant) -> Result<Option<Vec<u128>>, PacketError> {
+    match attribute(variant.doc, "id") {
+        Some("_") => Ok(None),
+        Some(id) => literal(id).map(Some),
+        None => Ok(Some(vec![variant.discriminant as u128])),
+    }
+}
+
+/// `N` of a `FixedSizeUtf8<N>`, a struct of a `String` with `N` as the type
+/// parameter of the shape of `[u8; N]`
+fn fixed_size(shape: &Shape) -> Option<usize> {
+    let Def::Struct(Struct {
+        fields: [field], ..
+    }) = shape.def
+    else {
+        return None;
+    };
+    let parameter = shape
+        .type_params
+        .iter()
+        .find(|parameter| parameter.name == "N")?;
+    match (parameter.shape)().def {
+        Def::Array(array) if field.shape() == String::SHAPE => Some(array.n),
+        _ => None,
+    }
+}
+
+/// `#[derive(Facet)]` drops const parameters, so `N` is kept as the type
+/// parameter `N` with the shape of `[u8; N]`, for `reflect::Reflected`
+unsafe impl<const N: usize> Facet for FixedSizeUtf8<N> {
+    const SHAPE: &'static Shape = &const {
+        Shape::builder()
+            .id(ConstTypeId::of::<Self>())
+            .layout(std::alloc::Layout::new::<Self>())
+            .type_params(&[TypeParam {
+                name: "N",
+                shape: || <[u8; N]>::SHAPE,
+            }])
+            .vtable(&const { facet::value_vtable_inner!(Self, |f, _opts| write!(f, "FixedSizeUtf8<{}>", N)) })
+            .def(Def::Struct(
+                Struct::builder()
+                    .kind(StructKind::TupleStruct)
+                    .fields(&const {
+                        [Field::builder()
+                            .name("0")
+                            .shape(|| String::SHAPE)
+                            .offset(std::mem::offset_of!(Self, 0))
+                            .flags(FieldFlags::EMPTY)
+                            .attributes(&[])
+                            .build()]
+                    })
+                    .build(),
+            ))
+            .build()
+    };
+}
+
+/// Path of the field for `Packet::unpack_field_with`, like the derive's
+/// `HciAcl.msg` or `H4Packet::Acl`
+fn field_path(
+    shape: &Shape,
+    variant: Option<&Variant>,
+    def: &Struct,
+    field: &Field,
+) -> Cow<'static, str> {
+    let mut path = shape.to_string();
+    if let Some(variant) = variant {
+        path = format!("{}::{}", path, variant.name);
+    }
+    if matches!(def.kind, StructKind::Struct) {
+        path = format!("{}.{}", path, field.name);
+    } else if def.fields.len() > 1 {
+        // facet names the fields of tuples `_0`, the derive `0`
+        let index = def
+            .fields
+            .iter()
+            .position(|other| other.name == field.name)
+            .unwrap_or_default();
+        path = format!("{}.{}", path, index);
+    }
+    Cow::Owned(path)
+}
+
+/// Value being packed
+///
+/// `Peek` doesn't give out its pointer, which byte arrays need, so the pointer
+/// is kept here with the shape.
+#[derive(Clone, Copy)]
+struct Value<'mem> {
+    data: PtrConst<'mem>,
+    shape: &'static Shape,
+}
+
+impl<'mem> Value<'mem> {
+    fn peek(self) -> Peek<'mem> {
+        // SAFETY: `data` points to a value of `shape`, from the `&T` of `Reflected`
+        unsafe { Peek::unchecked_new(self.data, self.shape) }
+    }
+
+    /// The fields of the struct, or of the variant of the enum
+    fn fields(self, fields: &'static [Field]) -> Vec<(&'static Field, Value<'mem>)> {
+        fields
+            .iter()
+            .map(|field| {
+                // SAFETY: the offsets of the fields, also those of variants, are from the start
+                let data = unsafe { self.data.as_byte_ptr().add(field.offset) };
+                let value = Value {
+                    data: PtrConst::new(data),
+                    shape: field.shape(),
+                };
+                (field, value)
+            })
+            .collect()
+    }
+}
+
+/// Integers of the value in order, e.g. `[0x0006, 0x01]` for
+/// `OpCode(0x0006, 0x01)`, to compare with ids or to use as a count
+fn integers(value: Value) -> Result<Vec<u128>, PacketError> {
+    macro_rules! integers_of {
+        ($($type:ty),*) => {
+            $(if value.shape == <$type>::SHAPE {
+                return Ok(vec![*value.peek().get::<$type>()? as u128]);
+            })*
+        };
+    }
+    integers_of!(u8, u16, u32, u64, u128, bool);
+    match value.shape.def {
+        Def::Struct(def) => Ok(value
+            .fields(def.fields)
+            .into_iter()
+            .map(|(_, field)| integers(field))
+            .collect::<Result<Vec<_>, _>>()?
+            .concat()),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Packs the integers as a value of the shape, e.g. an id
+fn pack_integers(
+    shape: &Shape,
+    integers: &mut std::slice::Iter<u128>,
+    bytes: &mut Packet,
+) -> Result<(), PacketError> {
+    macro_rules! pack_as {
+        ($($type:ty),*) => {
+            $(if shape == <$type>::SHAPE {
+                let integer = integers.next().ok_or(PacketError::InvalidBytes)?;
+                return <$type>::try_from(*integer).map_err(|_| PacketError::InvalidBytes)?.to_packet(bytes);
+            })*
+        };
+    }
+    pack_as!(u8, u16, u32, u64, u128);
+    let Def::Struct(def) = shape.def else {
+        return Err(unsupported(format!("id of type {}", shape)));
+    };
+    for field in def.fields {
+        if let Some(bits) = attribute(field.doc, "bits") {
+            bytes.set_bits(integer(bits)? as usize);
+        }
+        bytes.pack_bounded(|bytes| pack_integers(field.shape(), integers, bytes))?;
+    }
+    Ok(())
+}
+
+/// Packs the id of the variant or unit struct like `bytes.pack::<I>(&id)`
+fn pack_id(shape: &Shape, id: &[u128], bytes: &mut Packet) -> Result<(), PacketError> {
+    bytes.pack_bounded(|bytes| pack_integers(shape, &mut id.iter(), bytes))
+}
+
+/// Packs the length before the item or field, for its `prepend_length`
+fn pack_length(doc: &[&'static str], bytes: &mut Packet) -> Result<(), PacketError> {
+    let Some(length_type) = attribute(doc, "prepend_length") else {
+        return Ok(());
+    };
+    let offset = length_offset(doc)?;
+    with_length_type!(length_type, T => bytes.pack_length_with_offset::<T>(offset))?;
+    Ok(())
+}
+
+/// Unpacks the length before the item or field, for its `prepend_length`
+fn unpack_length(doc: &[&'static str], bytes: &mut Packet) -> Result<(), PacketError> {
+    let Some(length_type) = attribute(doc, "prepend_length") else {
+        return Ok(());
+    };
+    let offset = length_offset(doc)?;
+    with_length_type!(length_type, T => bytes.unpack_length_with_offset::<T>(offset))?;
+    Ok(())
+}
+
+fn length_offset(doc: &[&'static str]) -> Result<i32, PacketError> {
+    match attribute(doc, "prepend_length_offset") {
+        Some(offset) => offset
+            .replace(' ', "")
+            .parse()
+            .map_err(|_| unsupported(format!("length offset `{}`", offset))),
+        None => Ok(0),
+    }
+}
+
+/// Sets the bits and the `Vec` layout of the field before it's packed or
+/// unpacked
+fn set_layout(
+    doc: &[&'static str],
+    lookup: &Lookup,
+    packing: bool,
+    bytes: &mut Packet,
+) -> Result<(), PacketError> {
+    if let Some(bits) = attribute(doc, "bits") {
+        bytes.set_bits(number(bits, lookup)?);
+    }
+    if let Some(count_type) = attribute(doc, "count_prefix") {
+        if packing {
+            with_length_type!(count_type, T => bytes.pack_count::<T>())?;
+        } else {
+            with_length_type!(count_type, T => bytes.unpack_count::<T>())?;
+        }
+    }
+    if let Some(count) = attribute(doc, "count") {
+        bytes.set_count(number(count, lookup)?);
+    }
+    if let Some(length) = attribute(doc, "element_length") {
+        bytes.set_element_length(number(length, lookup)?);
+    }
+    if let Some(length) = attribute(doc, "bytes_after").filter(|_| !packing) {
+        bytes.set_bytes_after(number(length, lookup)?)?;
+    }
+    Ok(())
+}
+
+/// Packs the value like `FromToPacket::to_packet` of its type
+fn pack(value: Value, bytes: &mut Packet) -> Result<(), PacketError> {
+    let shape = value.shape;
+    macro_rules! pack_as {
+        ($($type:ty),*) => {
+            $(if shape == <$type>::SHAPE {
+                return value.peek().get::<$type>()?.to_packet(bytes);
+            })*
+        };
+    }
+    pack_as!(u8, u16, u32, u64, u128, bool);
+    if let (Some(size), Def::Struct(def)) = (fixed_size(shape), shape.def) {
+        let (_, string) = value.fields(def.fields)[0];
+        let string = string.peek();
+        let string = string.get::<String>()?.as_bytes();
+        if string.len() > size {
+            return Err(PacketError::InvalidBytes);
+        }
+        bytes.pack_bytes(string)?;
+        for _ in string.len()..size {
+            bytes.pack_bytes(&[0])?;
+        }
+        return Ok(());
+    }
+    match shape.def {
+        // Like the `FromToPacket` of tuples, the elements one after another
+        Def::Struct(def) if matches!(def.kind, StructKind::Tuple) => {
+            for (_, element) in value.fields(def.fields) {
+                pack(element, bytes)?;
+            }
+            Ok(())
+        }
+        Def::Struct(def) => {
+            pack_length(shape.doc, bytes)?;
+            bytes.pack_bounded(|bytes| match def.kind {
+                StructKind::Unit => {
+                    let id = attribute(shape.doc, "id")
+                        .ok_or_else(|| unsupported(format!("{} without `id`", shape)))?;
+                    pack_id(id_type(shape)?, &literal(id)?, bytes)
+                }
+                _ => pack_fields(&value.fields(def.fields), 0, bytes),
+            })
+        }
+        Def::Enum(_) => {
+            pack_length(shape.doc, bytes)?;
+            bytes.pack_bounded(|bytes| pack_variant(value, true, bytes))
+        }
+        Def::Array(def) if (def.t)() == u8::SHAPE => {
+            // SAFETY: the value is an array of `def.n` bytes
+            let array = unsafe { std::slice::from_raw_parts(value.data.as_byte_ptr(), def.n) };
+            bytes.pack_bytes(array)?;
+            Ok(())
+        }
+        Def::List(def) => {
+            let len = value.peek().into_list()?.len();
+            bytes.pack_elements(len, |index, bytes| {
+                // SAFETY: the index is within the list
+                let data = unsafe { (def.vtable.get_item_ptr)(value.data, index) };
+                pack(
+                    Value {
+                        data,
+                        shape: def.t(),
+                    },
+                    bytes,
+                )
+            })
+        }
+        _ => Err(unsupported(format!("type {}", shape))),
+    }
+}
+
+/// Packs the fields from `first` on, like the fields of the derive
+fn pack_fields(
+    fields: &[(&'static Field, Value)],
+    first: usize,
+    bytes: &mut Packet,
+) -> Result<(), PacketError> {
+    let lookup = |name: &str| match fields.iter().find(|(field, _)| field.name == name) {
+        Some((_, value)) => integers(*value),
+        None => Err(unsupported(format!("field `{}`", name))),
+    };
+    for (field, value) in &fields[first..] {
+        let doc = field.doc;
+        let value = match attribute(doc, "present_if") {
+            Some(condition) => {
+                let Def::Option(def) = value.shape.def else {
+                    return Err(unsupported(format!("`present_if` on {}", value.shape)));
+                };
+                // SAFETY: the value is an option of `def.t()`
+                let inner = unsafe { (def.vtable.get_value_fn)(value.data) };
+                match (holds(condition, &lookup)?, inner) {
+                    (true, Some(data)) => Value {
+                        data,
+                        shape: def.t(),
+                    },
+                    (false, None) => continue,
+                    _ => return Err(PacketError::InvalidBytes),
+                }
+            }
+            None => *value,
+        };
+        pack_length(doc, bytes)?;
+        set_layout(doc, &lookup, true, bytes)?;
+        match attribute(doc, "variant_by") {
+            // Like `Packet::pack_variant`
+            Some(id_field) => {
+                let variant = value.peek().into_enum()?.active_variant();
+                let id = match variant_id(variant)? {
+                    Some(id) => id,
+                    None => integers(value.fields(variant.data.fields)[0].1)?,
+                };
+                if id != lookup(id_field)? {
+                    return Err(PacketError::InvalidBytes);
+                }
+                bytes.pack_bounded(|bytes| pack_variant(value, false, bytes))?;
+            }
+            None => bytes.pack_bounded(|bytes| pack(value, bytes))?,
+        }
+    }
+    Ok(())
+}
+
+/// Packs the variant of the enum, with its id unless the id is packed by the
+/// field in `variant_by`, like `PacketVariant::to_packet_variant`
+fn pack_variant(value: Value, with_id: bool, bytes: &mut Packet) -> Result<(), PacketError> {
+    let shape = value.shape;
+    let variant = value.peek().into_enum()?.active_variant();
+    let fields = value.fields(variant.data.fields);
+    let id = variant_id(variant)?;
+    if with_id && let Some(id) = &id {
+        pack_id(id_type(shape)?, id, bytes)?;
+    }
+    // The passthrough would unpack as the variant of its id if it has the id
+    // of another variant
+    if id.is_none()
+        && let (Def::Enum(def), Some((_, first))) = (shape.def, fields.first())
+    {
+        let first = integers(*first)?;
+        for other in def.variants {
+            if variant_id(other)?.as_ref() == Some(&first) {
+                return Err(PacketError::InvalidBytes);
+            }
+        }
+    }
+    // The id of the passthrough is its first field
+    let first = usize::from(!with_id && id.is_none());
+    match attribute(shape.doc, "length_after_id") {
+        Some(length_type) => {
+            with_length_type!(length_type, T => bytes.pack_length::<T>())?;
+            bytes.pack_bounded(|bytes| pack_fields(&fields, first, bytes))
+        }
+        None => pack_fields(&fields, first, bytes),
+    }
+}
+
+/// Memory for a value being unpacked, freed without dropping the value, which
+/// is moved out or dropped before
+///
+/// The values are written in place rather than with `facet_reflect::Wip`,
+/// which in 0.10 can mix up the fields of a list element with those of the
+/// next one allocated at the same address.
+struct Allocation {
+    data: PtrUninit<'static>,
+    shape: &'static Shape,
+}
+
+impl Allocation {
+    fn new(shape: &'static Shape) -> Self {
+        Allocation {
+            data: shape.allocate(),
+            shape,
+        }
+    }
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        if self.shape.layout.size() != 0 {
+            // SAFETY: allocated by `Shape::allocate` with the layout of the shape
+            unsafe { std::alloc::dealloc(self.data.as_mut_byte_ptr(), self.shape.layout) };
+        }
+    }
+}
+
+/// Drops the value of the shape at `data`
+///
+/// # Safety
+///
+/// `data` must point to an initialized value of `shape`.
+unsafe fn drop_value(shape: &Shape, data: PtrMut) {
+    if let Some(drop_in_place) = shape.vtable.drop_in_place {
+        // SAFETY: guaranteed by the caller
+        unsafe { drop_in_place(data) };
+    }
+}
+
+/// Drops the fields of the struct or the variant at `data`, the last first
+///
+/// # Safety
+///
+/// The fields must be initialized.
+unsafe fn drop_fields<'a>(
+    data: PtrUninit,
+    fields: impl IntoIterator<Item = &'a Field, IntoIter: DoubleEndedIterator>,
+) {
+    for field in fields.into_iter().rev() {
+        // SAFETY: guaranteed by the caller
+        unsafe { drop_value(field.shape(), data.field_init_at(field.offset)) };
+    }
+}
+
+/// Unpacks the value like `FromToPacket::from_packet` of its type into `data`,
+/// with the integers of the value for the later fields
+///
+/// The value is initialized when this succeeds, and left uninitialized when it
+/// fails.
+fn unpack(
+    shape: &'static Shape,
+    data: PtrUninit,
+    bytes: &mut Packet,
+) -> Result<Vec<u128>, PacketError> {
+    macro_rules! unpack_as {
+        ($($type:ty),*) => {
+            $(if shape == <$type>::SHAPE {
+                let value = <$type>::from_packet(bytes)?;
+                // SAFETY: `data` is for a value of the shape
+                unsafe { data.put(value) };
+                return Ok(vec![value as u128]);
+            })*
+        };
+    }
+    unpack_as!(u8, u16, u32, u64, u128, bool);
+    if let (Some(size), Def::Struct(def)) = (fixed_size(shape), shape.def) {
+        let string = bytes.unpack_slice(size)?;
+        let string = string.split(|&b| b == 0).next().unwrap_or(&[]);
+        let string = String::from_utf8(string.to_vec()).map_err(|_| PacketError::InvalidBytes)?;
+        // SAFETY: the only field is the `String`
+        unsafe { data.field_uninit_at(def.fields[0].offset).put(string) };
+        return Ok(Vec::new());
+    }
+    match shape.def {
+        Def::Struct(def) if matches!(def.kind, StructKind::Tuple) => unpack_tuple(data, def, bytes),
+        Def::Struct(def) => unpack_item(shape, data, bytes, |bytes| {
+            unpack_fields(data, shape, None, def, 0, bytes)
+        }),
+        Def::Enum(def) => unpack_item(shape, data, bytes, |bytes| {
+            let checkpoint = bytes.checkpoint();
+            let id = unpack_integers(id_type(shape)?, bytes).ok();
+            for variant in def.variants {
+                match variant_id(variant)? {
+                    Some(variant_id) if id.as_ref() == Some(&variant_id) => {
+                        return unpack_after_id(data, shape, variant, bytes);
+                    }
+                    Some(_) => {}
+                    // The passthrough unpacks its id again as its first field
+                    None => {
+                        bytes.rewind(checkpoint);
+                        select_variant(data, shape, variant)?;
+                        return unpack_fields(data, shape, Some(variant), variant.data, 0, bytes);
+                    }
+                }
+            }
+            bytes.rewind(checkpoint);
+            Err(PacketError::Unspecified(format!(
+                "No matching variant found for {}",
+                shape
+            )))
+        }),
+        Def::Array(def) if (def.t)() == u8::SHAPE => {
+            let array = bytes.unpack_slice(def.n)?;
+            // SAFETY: `data` is for an array of `def.n` bytes
+            unsafe { data.copy_from(PtrConst::new(array.as_ptr()), shape) };
+            Ok(Vec::new())
+        }
+        Def::List(def) => {
+            let init = def
+                .vtable
+                .init_in_place_with_capacity
+                .ok_or_else(|| unsupported(format!("type {}", shape)))?;
+            // SAFETY: `data` is for a list of the shape
+            let list = unsafe { init(data, 0) };
+            let unpacked = bytes.unpack_elements((), |(), bytes| {
+                let element = Allocation::new(def.t());
+                unpack(def.t(), element.data, bytes)?;
+                // SAFETY: the element was unpacked, `push` moves it out
+                unsafe { (def.vtable.push)(list, element.data.assume_init()) };
+                Ok(())
+            });
+            if unpacked.is_err() {
+                // SAFETY: the list and the elements pushed so far are initialized
+                unsafe { drop_value(shape, list) };
+            }
+            unpacked.map(|()| Vec::new())
+        }
+        _ => Err(unsupported(format!("type {}", shape))),
+    }
+}
+
+/// Unpacks the elements of the tuple one after another, like its
+/// `FromToPacket`
+fn unpack_tuple(
+    data: PtrUninit,
+    def: Struct,
+    bytes: &mut Packet,
+) -> Result<Vec<u128>, PacketError> {
+    let mut integers = Vec::new();
+    for (index, field) in def.fields.iter().enumerate() {
+        // SAFETY: the field is within the tuple
+        match unpack(
+            field.shape(),
+            unsafe { data.field_uninit_at(field.offset) },
+            bytes,
+        ) {
+            Ok(element) => integers.extend(element),
+            Err(error) => {
+                // SAFETY: the elements before were unpacked
+                unsafe { drop_fields(data, &def.fields[..index]) };
+                return Err(error);
+            }
+        }
+    }
+    Ok(integers)
+}
+
+/// Unpacks a value of the shape as its integers, e.g. an id
+fn unpack_integers(shape: &'static Shape, bytes: &mut Packet) -> Result<Vec<u128>, PacketError> {
+    let value = Allocation::new(shape);
+    let integers = unpack(shape, value.data, bytes)?;
+    // SAFETY: the value was unpacked
+    unsafe { drop_value(shape, value.data.assume_init()) };
+    Ok(integers)
+}
+
+/// Unpacks the struct or enum with `f`, after its `id` and within its
+/// `prepend_length`
+fn unpack_item(
+    shape: &'static Shape,
+    data: PtrUninit,
+    bytes: &mut Packet,
+    f: impl FnOnce(&mut Packet) -> Result<Vec<u128>, PacketError>,
+) -> Result<Vec<u128>, PacketError> {
+    if let Some(id) = attribute(shape.doc, "id") {
+        let checkpoint = bytes.checkpoint();
+        if unpack_integers(id_type(shape)?, bytes).ok() != Some(literal(id)?) {
+            bytes.rewind(checkpoint);
+            return Err(PacketError::Unspecified(format!(
+                "No matching bytes found for {}",
+                shape
+            )));
+        }
+    }
+    unpack_length(shape.doc, bytes)?;
+    unpack_bounded(shape, data, bytes, f)
+}
+
+/// `Packet::unpack_bounded` of the value unpacked into `data` by `f`, which
+/// is dropped again when the bytes of the length aren't all used
+fn unpack_bounded(
+    shape: &Shape,
+    data: PtrUninit,
+    bytes: &mut Packet,
+    f: impl FnOnce(&mut Packet) -> Result<Vec<u128>, PacketError>,
+) -> Result<Vec<u128>, PacketError> {
+    let mut unpacked = false;
+    let integers = bytes.unpack_bounded(|bytes| {
+        let integers = f(bytes)?;
+        unpacked = true;
+        Ok(integers)
+    });
+    if integers.is_err() && unpacked {
+        // SAFETY: `f` succeeded, so the value is initialized
+        unsafe { drop_value(shape, data.assume_init()) };
+    }
+    integers
+}
+
+/// Writes the discriminant of the variant, the enum must be `#[repr(u8)]`
+fn select_variant(data: PtrUninit, shape: &Shape, variant: &Variant) -> Result<(), PacketError> {
+    match shape.def {
+        Def::Enum(def) if def.repr == EnumRepr::U8 => {
+            // SAFETY: a `#[repr(u8)]` enum starts with its discriminant
+            unsafe { data.put(variant.discriminant as u8) };
+            Ok(())
+        }
+        _ => Err(unsupported(format!("enum {} without `#[repr(u8)]`", shape))),
+    }
+}
+
+/// Unpacks the fields of the variant after its id, within the
+/// `length_after_id` of the enum if it has one
+fn unpack_after_id(
+    data: PtrUninit,
+    shape: &'static Shape,
+    variant: &'static Variant,
+    bytes: &mut Packet,
+) -> Result<Vec<u128>, PacketError> {
+    if let Some(length_type) = attribute(shape.doc, "length_after_id") {
+        with_length_type!(length_type, T => bytes.unpack_length::<T>())?;
+    }
+    select_variant(data, shape, variant)?;
+    unpack_bounded(shape, data, bytes, |bytes| {
+        unpack_fields(data, shape, Some(variant), variant.data, 0, bytes)
+    })
+}
+
+/// Unpacks the variant of the enum with the id from the field in
+/// `variant_by`, like `PacketVariant::from_packet_variant`
+fn unpack_variant(
+    shape: &'static Shape,
+    data: PtrUninit,
+    id: &[u128],
+    bytes: &mut Packet,
+) -> Result<Vec<u128>, PacketError> {
+    let Def::Enum(def) = shape.def else {
+        return Err(unsupported(format!("`variant_by` on {}", shape)));
+    };
+    for variant in def.variants {
+        match variant_id(variant)? {
+            Some(variant_id) if variant_id == id => {
+                return unpack_after_id(data, shape, variant, bytes);
+            }
+            Some(_) => {}
+            None => {
+                let Some(field) = variant.data.fields.first() else {
+                    return Err(unsupported(format!(
+                        "passthrough {}::{} without fields",
+                        shape, variant.name
+                    )));
+                };
+                select_variant(data, shape, variant)?;
+                // SAFETY: the first field of the variant, an id of only integers
+                // that needs no dropping if the other fields fail
+                put_integers(
+                    field.shape(),
+                    unsafe { data.field_uninit_at(field.offset) },
+                    &mut id.iter(),
+                )?;
+                return unpack_fields(data, shape, Some(variant), variant.data, 1, bytes);
+            }
+        }
+    }
+    Err(PacketError::Unspecified(format!(
+        "No matching variant found for {}",
+        shape
+    )))
+}
+
+/// Puts the integers as the value, the reverse of `integers`
+fn put_integers(
+    shape: &Shape,
+    data: PtrUninit,
+    integers: &mut std::slice::Iter<u128>,
+) -> Result<(), PacketError> {
+    macro_rules! put_as {
+        ($($type:ty),*) => {
+            $(if shape == <$type>::SHAPE {
+                let integer = integers.next().ok_or(PacketError::InvalidBytes)?;
+                let integer = <$type>::try_from(*integer).map_err(|_| PacketError::InvalidBytes)?;
+                // SAFETY: `data` is for a value of the shape
+                unsafe { data.put(integer) };
+                return Ok(());
+            })*
+        };
+    }
+    put_as!(u8, u16, u32, u64, u128);
+    let Def::Struct(def) = shape.def else {
+        return Err(unsupported(format!("id of type {}", shape)));
+    };
+    for field in def.fields {
+        // SAFETY: the field is within the struct
+        put_integers(
+            field.shape(),
+            unsafe { data.field_uninit_at(field.offset) },
+            integers,
+        )?;
+    }
+    Ok(())
+}
+
+/// Unpacks the fields from `first` on into the struct or the variant at
+/// `data`, like the fields of the derive
+///
+/// The fields unpacked before one that fails are dropped again.
+fn unpack_fields(
+    data: PtrUninit,
+    shape: &'static Shape,
+    variant: Option<&'static Variant>,
+    def: Struct,
+    first: usize,
+    bytes: &mut Packet,
+) -> Result<Vec<u128>, PacketError> {
+    let mut unpacked: Vec<(&'static Field, Vec<u128>)> = Vec::new();
+    for field in &def.fields[first..] {
+        let lookup = |name: &str| match unpacked.iter().find(|(unpacked, _)| unpacked.name == name)
+        {
+            Some((_, integers)) => Ok(integers.clone()),
+            None => Err(unsupported(format!(
+                "field `{}` after {}",
+                name, field.name
+            ))),
+        };
+        match unpack_field(data, shape, variant, &def, field, &lookup, bytes) {
+            Ok(integers) => unpacked.push((field, integers)),
+            Err(error) => {
+                // SAFETY: the fields before were unpacked
+                unsafe { drop_fields(data, unpacked.iter().map(|(field, _)| *field)) };
+                return Err(error);
+            }
+        }
+    }
+    Ok(unpacked
+        .into_iter()
+        .flat_map(|(_, integers)| integers)
+        .collect())
+}
+
+/// Unpacks the field into its place in `data`, an `Option` by its
+/// `present_if` and an enum by its `variant_by`
+fn unpack_field(
+    data: PtrUninit,
+    shape: &'static Shape,
+    variant: Option<&'static Variant>,
+    def: &Struct,
+    field: &'static Field,
+    lookup: &Lookup,
+    bytes: &mut Packet,
+) -> Result<Vec<u128>, PacketError> {
+    let doc = field.doc;
+    // SAFETY: the offsets of the fields, also those of variants, are from the start
+    let place = unsafe { data.field_uninit_at(field.offset) };
+    let option = match (attribute(doc, "present_if"), field.shape().def) {
+        (Some(condition), Def::Option(option)) => {
+            if !holds(condition, lookup)? {
+                // SAFETY: `place` is for an option of the shape
+                unsafe { (option.vtable.init_none_fn)(place) };
+                return Ok(Vec::new());
+            }
+            Some((option, Allocation::new(option.t())))
+        }
+        (Some(_), _) => return Err(unsupported(format!("`present_if` on {}", field.shape()))),
+        (None, _) => None,
+    };
+    let id = attribute(doc, "variant_by").map(lookup).transpose()?;
+    let (value_shape, value_data) = match &option {
+        Some((option, inner)) => (option.t(), inner.data),
+        None => (field.shape(), place),
+    };
+    let path = || field_path(shape, variant, def, field);
+    let integers = bytes.unpack_field_with(path, |bytes| {
+        unpack_length(doc, bytes)?;
+        set_layout(doc, lookup, false, bytes)?;
+        unpack_bounded(value_shape, value_data, bytes, |bytes| match &id {
+            Some(id) => unpack_variant(value_shape, value_data, id, bytes),
+            None => unpack(value_shape, value_data, bytes),
+        })
+    })?;
+    if let Some((option, inner)) = option {
+        // SAFETY: the inner value was unpacked, `init_some_fn` moves it out
+        unsafe { (option.vtable.init_some_fn)(place, inner.data.assume_init().as_const()) };
+    }
+    Ok(integers)
+}
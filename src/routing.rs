@@ -0,0 +1,200 @@
+use crate::hcimanager::AppMsg;
+use crate::messages::*;
+use crate::packer::PacketIdentifier;
+
+/// L2CAP channel of the Attribute Protocol
+pub const CID_ATT: u16 = 0x0004;
+
+/// L2CAP channel of the Security Manager Protocol
+pub const CID_SMP: u16 = 0x0006;
+
+/// Messages the processor wants to receive from `HciManager`
+///
+/// `None` matches everything, an empty list matches nothing. Messages not
+/// tied to a connection, channel, event or command (e.g. `AppMsg::Tick`) are
+/// delivered to every processor.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Interest {
+    /// Only messages of this connection
+    pub connection: Option<ConnectionHandle>,
+
+    /// L2CAP CIDs of the ACL data
+    pub channels: Option<Vec<u16>>,
+
+    /// HCI event codes
+    pub events: Option<Vec<u8>>,
+
+    /// Opcodes of the commands, and of Command Complete and Command Status
+    /// events
+    pub opcodes: Option<Vec<OpCode>>,
+}
+
+impl Interest {
+    /// Every message, the default for processors that don't declare interest
+    pub fn all() -> Self {
+        Interest::default()
+    }
+
+    /// Every message of the connection
+    pub fn connection(handle: ConnectionHandle) -> Self {
+        Interest {
+            connection: Some(handle),
+            ..Default::default()
+        }
+    }
+
+    pub fn matches(&self, msg: &AppMsg) -> bool {
+        match msg {
+            AppMsg::Send(packet) | AppMsg::Recv(packet) => self.matches_packet(packet),
+            AppMsg::Disconnect(handle)
+            | AppMsg::DisconnectComplete(handle)
+            | AppMsg::Pairing(handle)
+            | AppMsg::PairingComplete(handle)
+            | AppMsg::MtuChanged(handle, _)
+            | AppMsg::HidReport(handle, _)
+            | AppMsg::ConnectionFailed(handle, _) => self.matches_connection(handle),
+            _ => true,
+        }
+    }
+
+    fn matches_packet(&self, packet: &H4Packet) -> bool {
+        match packet {
+            H4Packet::Acl(acl) => {
+                self.matches_connection(&acl.connection_handle)
+                    && contains(&self.channels, &acl.msg.get_id())
+            }
+            H4Packet::Command(command) => contains(&self.opcodes, &command.get_id()),
+            H4Packet::Event(event) => {
+                if !contains(&self.events, &event.get_id()) {
+                    return false;
+                }
+                match event {
+                    HciEvent::CommandComplete(e) => contains(&self.opcodes, &e.command_opcode),
+                    HciEvent::CommandStatus(e) => contains(&self.opcodes, &e.command_opcode),
+                    HciEvent::NumberOfCompletedPackets(e) => e
+                        .handles
+                        .iter()
+                        .any(|h| self.matches_connection(&h.connection_handle)),
+                    _ => event_connection(event).is_none_or(|h| self.matches_connection(h)),
+                }
+            }
+        }
+    }
+
+    fn matches_connection(&self, handle: &ConnectionHandle) -> bool {
+        self.connection.as_ref().is_none_or(|c| c == handle)
+    }
+}
+
+fn contains<T: PartialEq>(list: &Option<Vec<T>>, value: &T) -> bool {
+    list.as_ref().is_none_or(|list| list.contains(value))
+}
+
+/// Connection the event is about, if any
+///
+/// Number Of Completed Packets can be about several connections and has none.
+pub fn event_connection(event: &HciEvent) -> Option<&ConnectionHandle> {
+    use EvtLeMeta::*;
+    use HciEvent::*;
+    match event {
+        DisconnectComplete(e) => Some(&e.connection_handle),
+        EncryptionChange(e) => Some(&e.connection_handle),
+        LeMeta(LeConnectionComplete(e)) => Some(&e.connection_handle),
+        LeMeta(LeConnectionUpdateComplete(e)) => Some(&e.connection_handle),
+        LeMeta(LeReadRemoteFeaturesPage0Complete(e)) => Some(&e.connection_handle),
+        LeMeta(LeLongTermKeyRequest(e)) => Some(&e.connection_handle),
+        LeMeta(LeDataLengthChange(e)) => Some(&e.connection_handle),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn acl(handle: u16, msg: L2CapMessage) -> AppMsg {
+        AppMsg::Recv(H4Packet::Acl(HciAcl {
+            connection_handle: ConnectionHandle(handle),
+            bc: BroadcastFlag::PointToPoint,
+            pb: PacketBoundaryFlag::FirstNonFlushable,
+            msg,
+        }))
+    }
+
+    #[test]
+    fn test_interest() {
+        let interest = Interest {
+            connection: Some(ConnectionHandle(0x40)),
+            channels: Some(vec![CID_ATT]),
+            events: Some(vec![0x08]),
+            opcodes: Some(vec![]),
+        };
+        let mtu = L2CapMessage::Att(AttPdu::ExchangeMtuRequest(244));
+        let encryption_change = |handle| {
+            AppMsg::Recv(H4Packet::Event(HciEvent::EncryptionChange(
+                EvtEncryptionChange {
+                    status: HciStatus::Success,
+                    connection_handle: ConnectionHandle(handle),
+                    encryption_enabled: true,
+                },
+            )))
+        };
+
+        assert!(interest.matches(&acl(0x40, mtu.clone())));
+        assert!(!interest.matches(&acl(0x41, mtu.clone())));
+        assert!(!interest.matches(&acl(0x40, L2CapMessage::Unknown(0x0005, vec![]))));
+        assert!(interest.matches(&encryption_change(0x40)));
+        assert!(!interest.matches(&encryption_change(0x41)));
+        assert!(
+            !interest.matches(&AppMsg::Recv(H4Packet::Event(HciEvent::HardwareError(
+                EvtHardwareError { hardware_code: 0 }
+            ))))
+        );
+        assert!(!interest.matches(&AppMsg::Send(H4Packet::Command(HciCommand::Reset))));
+        assert!(!interest.matches(&AppMsg::Disconnect(ConnectionHandle(0x41))));
+        assert!(interest.matches(&AppMsg::Tick));
+
+        assert!(Interest::all().matches(&acl(0x41, mtu)));
+    }
+
+    #[test]
+    fn test_interest_completed_packets() {
+        let completed = |handles: &[u16]| {
+            AppMsg::Recv(H4Packet::Event(HciEvent::NumberOfCompletedPackets(
+                EvtNumberOfCompletedPackets {
+                    handles: handles
+                        .iter()
+                        .map(|h| CompletedPackets {
+                            connection_handle: ConnectionHandle(*h),
+                            num_completed_packets: 1,
+                        })
+                        .collect(),
+                },
+            )))
+        };
+        let interest = Interest::connection(ConnectionHandle(0x41));
+        assert!(interest.matches(&completed(&[0x40, 0x41])));
+        assert!(!interest.matches(&completed(&[0x40])));
+    }
+
+    #[test]
+    fn test_interest_opcodes() {
+        let interest = Interest {
+            opcodes: Some(vec![OpCode(0x0003, 0x03)]),
+            ..Interest::all()
+        };
+        let complete = |opcode| {
+            AppMsg::Recv(H4Packet::Event(HciEvent::CommandComplete(
+                EvtCommandComplete {
+                    num_hci_command_packets: 1,
+                    command_opcode: opcode,
+                    status: Some(HciStatus::Success),
+                    data: vec![],
+                },
+            )))
+        };
+        assert!(interest.matches(&complete(OpCode(0x0003, 0x03))));
+        assert!(!interest.matches(&complete(OpCode(0x0001, 0x03))));
+        assert!(interest.matches(&AppMsg::Send(H4Packet::Command(HciCommand::Reset))));
+    }
+}
@@ -0,0 +1,163 @@
+use serde::Serialize;
+
+use crate::connection::{Connection, PairingStage, SecurityLevel};
+use crate::messages::*;
+use crate::routing::Interest;
+
+/// Internal state of `HciManager` for debugging, see `HciManager::snapshot`
+///
+/// Times are milliseconds on the clock of the manager.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Snapshot {
+    pub now_ms: u64,
+    pub connections: Vec<ConnectionSnapshot>,
+    pub commands: CommandsSnapshot,
+    pub processors: Vec<ProcessorSnapshot>,
+    pub timers: Vec<TimerSnapshot>,
+    pub advertising: bool,
+    pub shutting_down: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConnectionSnapshot {
+    pub handle: u16,
+    pub peer_address: String,
+    pub peer_address_type: String,
+    pub role: String,
+    pub interval: u16,
+    pub latency: u16,
+    pub supervision_timeout: u16,
+    pub mtu: u16,
+    pub security_level: SecurityLevel,
+    pub pairing: PairingStage,
+    pub acl_in_flight: u16,
+}
+
+impl From<&Connection> for ConnectionSnapshot {
+    fn from(c: &Connection) -> Self {
+        ConnectionSnapshot {
+            handle: c.handle.0,
+            peer_address: address(&c.peer_address),
+            peer_address_type: format!("{:?}", c.peer_address_type),
+            role: format!("{:?}", c.role),
+            interval: c.parameters.interval,
+            latency: c.parameters.latency,
+            supervision_timeout: c.parameters.supervision_timeout,
+            mtu: c.mtu,
+            security_level: c.security_level,
+            pairing: c.pairing,
+            acl_in_flight: c.acl_in_flight,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommandsSnapshot {
+    /// Commands the controller accepts, from the last Command Complete or
+    /// Command Status
+    pub credits: u8,
+
+    /// Command of the manager waiting for completion
+    pub in_flight: Option<String>,
+
+    /// Commands of the manager waiting for the one in flight
+    pub queued: Vec<String>,
+
+    /// Commands waiting for Command Complete or Command Status
+    pub pending: Vec<PendingCommandSnapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PendingCommandSnapshot {
+    pub opcode: String,
+    pub command: String,
+
+    /// Processor that sent the command, `None` for the manager
+    pub issuer: Option<usize>,
+    pub deadline_ms: u64,
+    pub retries_left: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProcessorSnapshot {
+    pub id: usize,
+    pub name: String,
+
+    /// Connection the processor belongs to, `None` for the application
+    /// processors
+    pub connection: Option<u16>,
+    pub interest: InterestSnapshot,
+}
+
+/// `Interest` of the processor, `None` matches everything
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InterestSnapshot {
+    pub connection: Option<u16>,
+    pub channels: Option<Vec<u16>>,
+    pub events: Option<Vec<u8>>,
+    pub opcodes: Option<Vec<String>>,
+}
+
+impl From<&Interest> for InterestSnapshot {
+    fn from(interest: &Interest) -> Self {
+        InterestSnapshot {
+            connection: interest.connection.as_ref().map(|h| h.0),
+            channels: interest.channels.clone(),
+            events: interest.events.clone(),
+            opcodes: interest
+                .opcodes
+                .as_ref()
+                .map(|opcodes| opcodes.iter().map(opcode).collect()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TimerSnapshot {
+    pub processor: usize,
+    pub id: u32,
+    pub deadline_ms: u64,
+}
+
+/// Opcode as written in the Core specification, e.g. `0x0C03` for Reset
+pub fn opcode(opcode: &OpCode) -> String {
+    format!("0x{:04X}", ((opcode.1 as u16) << 10) | opcode.0)
+}
+
+/// Address most significant byte first, e.g. `50:C2:E8:D6:0E:26`
+pub fn address(address: &BdAddr) -> String {
+    address.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatting() {
+        assert_eq!(opcode(&OpCode(0x0003, 0x03)), "0x0C03");
+        assert_eq!(opcode(&OpCode(0x000A, 0x08)), "0x200A");
+        assert_eq!(
+            address(&BdAddr([38, 14, 214, 232, 194, 80])),
+            "50:C2:E8:D6:0E:26"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let processor = ProcessorSnapshot {
+            id: 1,
+            name: "AttHandler".to_string(),
+            connection: Some(0x40),
+            interest: (&Interest::connection(ConnectionHandle(0x40))).into(),
+        };
+        assert_eq!(
+            serde_json::to_string(&processor).unwrap(),
+            r#"{"id":1,"name":"AttHandler","connection":64,"interest":{"connection":64,"channels":null,"events":null,"opcodes":null}}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&PairingStage::KeyDistribution).unwrap(),
+            r#""KeyDistribution""#
+        );
+    }
+}
@@ -1,16 +1,39 @@
 use std::collections::VecDeque;
+use std::time::Duration;
 
 use crate::messages::{EvtCommandComplete, LeConnectionComplete, *};
 use crate::packer::{FromToPacket, Packet, PacketIdentifier};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SocketError {
     ReadError,
     WriteError,
+    /// No packet came within the timeout of `Socket::read_timeout`
+    Timeout,
+}
+
+impl std::fmt::Display for SocketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SocketError::ReadError => write!(f, "Read error"),
+            SocketError::WriteError => write!(f, "Write error"),
+            SocketError::Timeout => write!(f, "Timed out"),
+        }
+    }
 }
 
 pub trait Socket {
     fn read(&mut self) -> Result<Option<H4Packet>, SocketError>;
+
+    /// Like `read`, but waits at most `timeout` for the packet, so that the
+    /// deadlines of the manager can be ticked in between
+    ///
+    /// Sockets that never block can keep the default, which just reads.
+    fn read_timeout(&mut self, timeout: Duration) -> Result<Option<H4Packet>, SocketError> {
+        let _ = timeout;
+        self.read()
+    }
+
     fn write(&mut self, packet: H4Packet) -> Result<(), SocketError>;
 }
 
@@ -22,14 +45,21 @@ impl MockSocket {
     pub fn new(packets: VecDeque<(bool, Vec<u8>)>) -> Self {
         MockSocket { packets }
     }
+
+    /// All expected packets have been read and written
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
 }
 
 impl Socket for MockSocket {
     fn read(&mut self) -> Result<Option<H4Packet>, SocketError> {
         if let Some((false, data)) = self.packets.front() {
             let mut p = Packet::from_slice(&data);
-            let m = H4Packet::from_packet(&mut p).map_err(|_| SocketError::ReadError)?;
-            println!("Reading packet: {:?}", m);
+            let m = H4Packet::from_packet(&mut p).map_err(|e| {
+                log::error!("Invalid packet {:02X?}: {}", data, e);
+                SocketError::ReadError
+            })?;
             self.packets.pop_front();
             Ok(Some(m))
         } else {
@@ -38,13 +68,11 @@ impl Socket for MockSocket {
     }
 
     fn write(&mut self, packet: H4Packet) -> Result<(), SocketError> {
-        println!("Writing packet: {:?}", packet);
         if let Some((true, data)) = self.packets.front() {
             let mut p = Packet::from_slice(&data);
             let h4msg = H4Packet::from_packet(&mut p).map_err(|_| SocketError::WriteError)?;
             if h4msg != packet {
-                println!("Expected packet: {:?}", packet);
-                println!("Got packet 🔴: {:?}", h4msg);
+                log::error!("Wrote packet {:?}, expected {:?}", packet, h4msg);
                 return Err(SocketError::WriteError);
             }
             self.packets.pop_front();
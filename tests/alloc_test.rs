@@ -0,0 +1,86 @@
+//! Counts allocations of packing and unpacking on the HID report path
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use bt_only_headers::messages::*;
+use bt_only_headers::packer::*;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Allocations made by `f` on this thread
+fn allocations<T>(f: impl FnOnce() -> T) -> (usize, T) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let value = f();
+    (ALLOCATIONS.with(Cell::get) - before, value)
+}
+
+/// Keyboard report as sent by the ATT handler
+fn report() -> H4Packet {
+    H4Packet::Acl(HciAcl {
+        connection_handle: ConnectionHandle(0x40),
+        pb: PacketBoundaryFlag::FirstNonFlushable,
+        bc: BroadcastFlag::PointToPoint,
+        msg: L2CapMessage::Att(AttPdu::HandleValueNotification(
+            AttHandleValueNotification {
+                handle: 0x002A,
+                value: vec![0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+            },
+        )),
+    })
+}
+
+#[test]
+fn test_pack_report_to_buffer() {
+    let report = report();
+    let mut buffer = Vec::new();
+    report.write_to(&mut buffer).unwrap();
+    assert_eq!(buffer, report.to_bytes());
+
+    let (count, result) = allocations(|| report.write_to(&mut buffer));
+    assert_eq!(result, Ok(()));
+    assert_eq!(count, 0);
+    assert_eq!(buffer, report.to_bytes());
+}
+
+#[test]
+fn test_unpack_report() {
+    let bytes = report().to_bytes();
+
+    // Only the report value is allocated
+    let (count, result) = allocations(|| Packet::from_slice(&bytes).unpack::<H4Packet>());
+    assert_eq!(result, Ok(report()));
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn test_unpack_event() {
+    let bytes = H4Packet::Event(HciEvent::CommandStatus(EvtCommandStatus {
+        status: HciStatus::Success,
+        num_hci_command_packets: 1,
+        command_opcode: OpCode(0x000D, 0x08),
+    }))
+    .to_bytes();
+
+    let (count, result) = allocations(|| Packet::from_slice(&bytes).unpack::<H4Packet>());
+    assert!(result.is_ok());
+    assert_eq!(count, 0);
+}
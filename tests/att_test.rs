@@ -0,0 +1,335 @@
+use bt_only_headers::messages::*;
+use bt_only_headers::packer::*;
+
+/// Unpacks the bytes to the PDU and packs it back to the same bytes
+fn round_trip(bytes: &[u8], pdu: AttPdu) {
+    let mut packet = Packet::from_slice(bytes);
+    assert_eq!(packet.unpack::<AttPdu>(), Ok(pdu.clone()));
+    assert_eq!(pdu.to_bytes(), bytes.to_vec());
+}
+
+#[test]
+fn test_error_response() {
+    round_trip(
+        &[0x01, 0x06, 0x01, 0x00, 0x0A],
+        AttPdu::ErrorResponse(AttErrorResponse {
+            request_opcode: 0x06,
+            handle: 0x0001,
+            error_code: AttErrorCode::AttributeNotFound,
+        }),
+    );
+    round_trip(
+        &[0x01, 0x12, 0x1A, 0x00, 0x80],
+        AttPdu::ErrorResponse(AttErrorResponse {
+            request_opcode: 0x12,
+            handle: 0x001A,
+            error_code: AttErrorCode::Other(0x80),
+        }),
+    );
+}
+
+#[test]
+fn test_error_response_acl() {
+    // Attribute Not Found for Find By Type Value Request
+    const DATA: [u8; 14] = [
+        0x02, 0x40, 0x00, 0x09, 0x00, 0x05, 0x00, 0x04, 0x00, 0x01, 0x06, 0x01, 0x00, 0x0A,
+    ];
+    let msg = H4Packet::Acl(HciAcl {
+        connection_handle: ConnectionHandle(0x40),
+        pb: PacketBoundaryFlag::FirstNonFlushable,
+        bc: BroadcastFlag::PointToPoint,
+        msg: L2CapMessage::Att(AttPdu::ErrorResponse(AttErrorResponse {
+            request_opcode: 0x06,
+            handle: 0x0001,
+            error_code: AttErrorCode::AttributeNotFound,
+        })),
+    });
+    assert_eq!(
+        Packet::from_slice(&DATA).unpack::<H4Packet>(),
+        Ok(msg.clone())
+    );
+    assert_eq!(msg.to_bytes(), DATA.to_vec());
+}
+
+#[test]
+fn test_read_blob() {
+    round_trip(
+        &[0x0C, 0x1A, 0x00, 0x16, 0x00],
+        AttPdu::ReadBlobRequest(AttReadBlobRequest {
+            handle: 0x001A,
+            offset: 22,
+        }),
+    );
+    round_trip(
+        &[0x0D, 0x05, 0x01, 0x09],
+        AttPdu::ReadBlobResponse(AttReadResponse {
+            value: vec![0x05, 0x01, 0x09],
+        }),
+    );
+}
+
+#[test]
+fn test_read_multiple() {
+    round_trip(
+        &[0x0E, 0x03, 0x00, 0x05, 0x00],
+        AttPdu::ReadMultipleRequest(AttReadMultipleRequest {
+            handles: vec![0x0003, 0x0005],
+        }),
+    );
+    round_trip(
+        &[0x0F, 0x48, 0x49, 0x44, 0x64],
+        AttPdu::ReadMultipleResponse(AttReadResponse {
+            value: vec![0x48, 0x49, 0x44, 0x64],
+        }),
+    );
+    round_trip(
+        &[0x20, 0x03, 0x00, 0x05, 0x00],
+        AttPdu::ReadMultipleVariableRequest(AttReadMultipleRequest {
+            handles: vec![0x0003, 0x0005],
+        }),
+    );
+    round_trip(
+        &[0x21, 0x03, 0x00, 0x48, 0x49, 0x44, 0x01, 0x00, 0x64],
+        AttPdu::ReadMultipleVariableResponse(AttReadMultipleVariableResponse {
+            values: vec![
+                AttLengthValue {
+                    value: b"HID".to_vec(),
+                },
+                AttLengthValue { value: vec![0x64] },
+            ],
+        }),
+    );
+}
+
+#[test]
+fn test_read_by_group_type() {
+    // Primary Service
+    round_trip(
+        &[0x10, 0x01, 0x00, 0xFF, 0xFF, 0x00, 0x28],
+        AttPdu::ReadByGroupTypeRequest(AttReadByTypeRequest {
+            starting_handle: 0x0001,
+            ending_handle: 0xFFFF,
+            uuid: vec![0x00, 0x28],
+        }),
+    );
+    // Generic Access 0x0001..0x0007 and HID 0x0008..0x0020
+    round_trip(
+        &[
+            0x11, 0x06, 0x01, 0x00, 0x07, 0x00, 0x00, 0x18, 0x08, 0x00, 0x20, 0x00, 0x12, 0x18,
+        ],
+        AttPdu::ReadByGroupTypeResponse(AttReadByGroupTypeResponse {
+            length: 6,
+            values: vec![
+                AttGroupData {
+                    handle: 0x0001,
+                    end_group_handle: 0x0007,
+                    value: vec![0x00, 0x18],
+                },
+                AttGroupData {
+                    handle: 0x0008,
+                    end_group_handle: 0x0020,
+                    value: vec![0x12, 0x18],
+                },
+            ],
+        }),
+    );
+}
+
+#[test]
+fn test_read_by_type_response() {
+    // Characteristic declarations of HID Information and Report Map
+    round_trip(
+        &[
+            0x09, 0x07, 0x09, 0x00, 0x02, 0x0A, 0x00, 0x4A, 0x2A, 0x0B, 0x00, 0x02, 0x0C, 0x00,
+            0x4B, 0x2A,
+        ],
+        AttPdu::ReadByTypeResponse(AttReadByTypeResponse {
+            pair_length: 7,
+            values: vec![
+                AttAttributeData {
+                    handle: 0x0009,
+                    value: vec![0x02, 0x0A, 0x00, 0x4A, 0x2A],
+                },
+                AttAttributeData {
+                    handle: 0x000B,
+                    value: vec![0x02, 0x0C, 0x00, 0x4B, 0x2A],
+                },
+            ],
+        }),
+    );
+
+    // Length must divide the attribute data evenly
+    assert_eq!(
+        Packet::from_slice(&[0x09, 0x07, 0x09, 0x00, 0x02, 0x0A])
+            .unpack::<AttPdu>()
+            .map_err(|e| e.kind().clone()),
+        Err(PacketError::NotEnoughBytes)
+    );
+    let mismatch = AttPdu::ReadByTypeResponse(AttReadByTypeResponse {
+        pair_length: 4,
+        values: vec![AttAttributeData {
+            handle: 0x0009,
+            value: vec![0x02],
+        }],
+    });
+    let mut packet = Packet::new();
+    assert_eq!(packet.pack(&mismatch), Err(PacketError::InvalidBytes));
+}
+
+#[test]
+fn test_find_information_response() {
+    // Format 0x01 has 16-bit UUIDs
+    round_trip(
+        &[0x05, 0x01, 0x01, 0x00, 0x00, 0x28, 0x02, 0x00, 0x03, 0x28],
+        AttPdu::FindInformationResponse(AttFindInformationResponse {
+            format: 0x01,
+            information: AttInformationData::Uuid16(vec![(0x0001, 0x2800), (0x0002, 0x2803)]),
+        }),
+    );
+
+    // Format 0x02 has 128-bit UUIDs
+    round_trip(
+        &[
+            0x05, 0x02, 0x10, 0x00, 0xFB, 0x34, 0x9B, 0x5F, 0x80, 0x00, 0x00, 0x80, 0x00, 0x10,
+            0x00, 0x00, 0x4A, 0x2A, 0x00, 0x00,
+        ],
+        AttPdu::FindInformationResponse(AttFindInformationResponse {
+            format: 0x02,
+            information: AttInformationData::Uuid128(vec![(
+                0x0010,
+                0x00002A4A_0000_1000_8000_00805F9B34FB,
+            )]),
+        }),
+    );
+
+    // Unknown format
+    assert_eq!(
+        Packet::from_slice(&[0x05, 0x03, 0x01, 0x00, 0x00, 0x28])
+            .unpack::<AttPdu>()
+            .map_err(|e| e.kind().clone()),
+        Err(PacketError::Unspecified(
+            "No matching variant found for AttInformationData".to_string()
+        ))
+    );
+
+    // Format must match the UUIDs
+    let mismatch = AttPdu::FindInformationResponse(AttFindInformationResponse {
+        format: 0x02,
+        information: AttInformationData::Uuid16(vec![(0x0001, 0x2800)]),
+    });
+    let mut packet = Packet::new();
+    assert_eq!(packet.pack(&mismatch), Err(PacketError::InvalidBytes));
+}
+
+#[test]
+fn test_write() {
+    round_trip(
+        &[0x12, 0x1B, 0x00, 0x01, 0x00],
+        AttPdu::WriteRequest(AttWriteRequest {
+            handle: 0x001B,
+            value: vec![0x01, 0x00],
+        }),
+    );
+    round_trip(&[0x13], AttPdu::WriteResponse);
+    round_trip(
+        &[0x52, 0x1C, 0x00, 0x01],
+        AttPdu::WriteCommand(AttWriteRequest {
+            handle: 0x001C,
+            value: vec![0x01],
+        }),
+    );
+
+    let mut signed = vec![0xD2, 0x1C, 0x00, 0x01];
+    signed.extend([0xAA; 12]);
+    round_trip(
+        &signed,
+        AttPdu::SignedWriteCommand(AttSignedWriteCommand {
+            handle: 0x001C,
+            value: vec![0x01],
+            signature: [0xAA; 12],
+        }),
+    );
+
+    // Value may be empty, the signature may not be short
+    let mut empty = vec![0xD2, 0x1C, 0x00];
+    empty.extend([0xAA; 12]);
+    round_trip(
+        &empty,
+        AttPdu::SignedWriteCommand(AttSignedWriteCommand {
+            handle: 0x001C,
+            value: vec![],
+            signature: [0xAA; 12],
+        }),
+    );
+    assert!(
+        Packet::from_slice(&empty[..14])
+            .unpack::<AttPdu>()
+            .is_err()
+    );
+}
+
+#[test]
+fn test_prepare_write() {
+    round_trip(
+        &[0x16, 0x1A, 0x00, 0x12, 0x00, 0x01, 0x02],
+        AttPdu::PrepareWriteRequest(AttPrepareWrite {
+            handle: 0x001A,
+            offset: 18,
+            value: vec![0x01, 0x02],
+        }),
+    );
+    round_trip(
+        &[0x17, 0x1A, 0x00, 0x12, 0x00, 0x01, 0x02],
+        AttPdu::PrepareWriteResponse(AttPrepareWrite {
+            handle: 0x001A,
+            offset: 18,
+            value: vec![0x01, 0x02],
+        }),
+    );
+    round_trip(
+        &[0x18, 0x01],
+        AttPdu::ExecuteWriteRequest(AttExecuteWriteRequest { flags: 0x01 }),
+    );
+    round_trip(&[0x19], AttPdu::ExecuteWriteResponse);
+}
+
+#[test]
+fn test_handle_value() {
+    round_trip(
+        &[0x1B, 0x1A, 0x00, 0x00, 0x00, 0x04],
+        AttPdu::HandleValueNotification(AttHandleValueNotification {
+            handle: 0x001A,
+            value: vec![0x00, 0x00, 0x04],
+        }),
+    );
+    round_trip(
+        &[0x1D, 0x05, 0x00, 0x01, 0x00, 0xFF, 0xFF],
+        AttPdu::HandleValueIndication(AttHandleValueNotification {
+            handle: 0x0005,
+            value: vec![0x01, 0x00, 0xFF, 0xFF],
+        }),
+    );
+    round_trip(&[0x1E], AttPdu::HandleValueConfirmation);
+    round_trip(
+        &[
+            0x23, 0x1A, 0x00, 0x01, 0x00, 0x04, 0x1E, 0x00, 0x01, 0x00, 0x00,
+        ],
+        AttPdu::MultipleHandleValueNotification(AttMultipleHandleValueNotification {
+            values: vec![
+                AttHandleLengthValue {
+                    handle: 0x001A,
+                    value: vec![0x04],
+                },
+                AttHandleLengthValue {
+                    handle: 0x001E,
+                    value: vec![0x00],
+                },
+            ],
+        }),
+    );
+}
+
+#[test]
+fn test_unknown_opcode() {
+    round_trip(&[0x30, 0x01, 0x02], AttPdu::Unknown(0x30, vec![0x01, 0x02]));
+}
@@ -0,0 +1,206 @@
+//! Fixtures and generated packets shared by the tests and the benches
+
+#![allow(dead_code)]
+
+use bt_only_headers::generate::Generate;
+use bt_only_headers::messages::*;
+use bt_only_headers::packer::*;
+use bt_only_headers::random::{Random, SeededRandom};
+
+/// The HCI dumps in `tests/`
+pub const FIXTURES: &[&str] = &[
+    "tests/hcidump-01.txt",
+    "tests/hcidump-02.txt",
+    "tests/hcidump-03.txt",
+];
+
+pub fn parse_hci_dump(dump: &str) -> Vec<(bool, Vec<u8>)> {
+    // Parse the HCI dump like this:
+    // If line starts with "<" then it is a command from the host to the controller
+    // If line starts with ">" then it is a command from the controller to the host
+    // If line starts wit " " then it is continued data from the previous line
+
+    // true = host to controller
+    // false = controller to host
+
+    let mut result = Vec::new();
+    let mut current: Option<(bool, Vec<u8>)> = None;
+
+    for line in dump.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        let (direction, data_str) = match line.chars().next() {
+            Some('<') => (true, &line[1..]),
+            Some('>') => (false, &line[1..]),
+            Some(' ') => {
+                if let Some((_, ref mut bytes)) = current {
+                    let data = line.trim();
+                    for byte in data.split_whitespace() {
+                        if let Ok(b) = u8::from_str_radix(byte, 16) {
+                            bytes.push(b);
+                        }
+                    }
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        let data = data_str
+            .split_whitespace()
+            .filter_map(|b| u8::from_str_radix(b, 16).ok())
+            .collect::<Vec<u8>>();
+
+        if let Some(c) = current.take() {
+            result.push(c);
+        }
+        current = Some((direction, data));
+    }
+
+    if let Some(c) = current {
+        result.push(c);
+    }
+
+    result
+}
+
+pub fn parse_hci_dump_from_file(file: &str) -> Vec<(bool, Vec<u8>)> {
+    let dump = std::fs::read_to_string(file).unwrap();
+    parse_hci_dump(&dump)
+}
+
+/// Packets of all the HCI dumps
+pub fn all_fixtures() -> Vec<Vec<u8>> {
+    FIXTURES
+        .iter()
+        .flat_map(|file| parse_hci_dump_from_file(file))
+        .map(|(_, bytes)| bytes)
+        .collect()
+}
+
+/// Calls `f::<T>(args)` for every message type, e.g.
+/// `for_each_message!(round_trip(&mut random))`
+#[allow(unused_macros)]
+macro_rules! for_each_message {
+    ($f:ident $args:tt) => {
+        $crate::common::for_each_message!(@types $f $args;
+            H4Packet,
+            HciCommand,
+            HciEvent,
+            HciAcl,
+            L2CapMessage,
+            AttPdu,
+            SmpPdu,
+            EvtLeMeta,
+            AttErrorResponse,
+            AttFindInformationRequest,
+            AttFindInformationResponse,
+            AttInformationData,
+            AttFindByTypeValueRequest,
+            AttFindByTypeValueResponse,
+            AttReadByTypeRequest,
+            AttReadByTypeResponse,
+            AttAttributeData,
+            AttReadRequest,
+            AttReadResponse,
+            AttReadBlobRequest,
+            AttReadMultipleRequest,
+            AttReadByGroupTypeResponse,
+            AttGroupData,
+            AttReadMultipleVariableResponse,
+            AttLengthValue,
+            AttMultipleHandleValueNotification,
+            AttHandleLengthValue,
+            AttWriteRequest,
+            AttSignedWriteCommand,
+            AttPrepareWrite,
+            AttExecuteWriteRequest,
+            AttHandleValueNotification,
+            SmpPairingReqRes,
+            SmpPairingConfirmation,
+            SmpPairingRandom,
+            SmpEncryptionInformation,
+            SmpCentralIdentification,
+            CmdDisconnect,
+            CmdReadLocalName,
+            LeSetAdvertisingParameters,
+            LeSetAdvertisingData,
+            LeSetDataLength,
+            LeLongTermKeyRequestReply,
+            OpCode,
+            CmdScanEnable,
+            LeConnectionComplete,
+            LeConnectionUpdateComplete,
+            LeReadRemoteFeaturesPage0Complete,
+            LeLongTermKeyRequest,
+            LeDataLengthChange,
+            LeReadLocalP256PublicKeyComplete,
+            EvtDisconnectComplete,
+            EvtEncryptionChange,
+            EvtHardwareError,
+            EvtDataBufferOverflow,
+            EvtNumberOfCompletedPackets,
+            CompletedPackets,
+            EvtCommandComplete,
+            EvtCommandStatus,
+            PacketBoundaryFlag,
+            BroadcastFlag,
+            HciStatus,
+            Role,
+            AddressType,
+            ClockAccuracy,
+            ConnectionHandle,
+            BdAddr,
+            KeyDistributionFlags,
+            AuthenticationRequirements,
+            IOCapability,
+            OOBDataFlag,
+            AttErrorCode,
+            SmpPairingFailure,
+        )
+    };
+    (@types $f:ident $args:tt; $($type:ty),* $(,)?) => {
+        $( $f::<$type> $args; )*
+    };
+}
+#[allow(unused_imports)]
+pub(crate) use for_each_message;
+
+/// Changes a length of the H4 packet by one either way: the parameter length
+/// of a command or an event, or the ACL or the L2CAP length of ACL data
+pub fn corrupt_length(random: &mut SeededRandom, bytes: &mut [u8]) {
+    let (index, size) = match bytes.first() {
+        Some(0x01) => (3, 1),
+        Some(0x02) => ([3, 5][random.below(2)], 2),
+        _ => (2, 1),
+    };
+    let Some(field) = bytes.get_mut(index..index + size) else {
+        return;
+    };
+    let mut length = [0; 2];
+    length[..size].copy_from_slice(field);
+    let length = u16::from_le_bytes(length);
+    let length = match random.below(2) {
+        0 => length.wrapping_sub(1),
+        _ => length.wrapping_add(1),
+    };
+    field.copy_from_slice(&length.to_le_bytes()[..size]);
+}
+
+/// Valid packet with a byte or a length changed, cut short or with a byte
+/// added, which reaches deeper than random bytes do
+pub fn corrupted_packet(random: &mut SeededRandom) -> Vec<u8> {
+    let mut bytes = H4Packet::generate(random).to_bytes();
+    match random.below(4) {
+        0 => {
+            let index = random.below(bytes.len());
+            bytes[index] = u8::generate(random);
+        }
+        1 => bytes.truncate(random.below(bytes.len())),
+        2 => corrupt_length(random, &mut bytes),
+        _ => bytes.push(u8::generate(random)),
+    }
+    bytes
+}
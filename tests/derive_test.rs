@@ -0,0 +1,65 @@
+//! Messages defined outside the crate with `#[derive(FromToPacket)]`
+
+use bt_only_headers::messages::OpCode;
+use bt_only_headers::packer::*;
+
+/// length_after_id = u8
+/// id_type = OpCode
+#[derive(Debug, Clone, PartialEq, Eq, FromToPacket)]
+enum VendorCommand {
+    /// id = OpCode(0x0001, 0x3F)
+    SetTxPower(i8),
+
+    /// id = OpCode(0x0002, 0x3F)
+    ReadTemperature,
+}
+
+/// Value of a vendor GATT characteristic
+#[derive(Debug, Clone, PartialEq, Eq, FromToPacket)]
+struct PowerState {
+    /// bits = 7
+    level: u8,
+
+    /// bits = 1
+    charging: bool,
+
+    /// prepend_length = u8
+    name: Vec<u8>,
+}
+
+#[test]
+fn test_vendor_command() {
+    let command = VendorCommand::SetTxPower(-4);
+    let bytes = [0x01, 0xFC, 0x01, 0xFC];
+    assert_eq!(command.to_bytes(), bytes);
+    assert_eq!(Packet::from_slice(&bytes).unpack(), Ok(command));
+    assert_eq!(
+        VendorCommand::ReadTemperature.get_id(),
+        OpCode(0x0002, 0x3F)
+    );
+
+    let bytes = [0x02, 0xFC, 0x00];
+    assert_eq!(VendorCommand::ReadTemperature.to_bytes(), bytes);
+    assert_eq!(
+        Packet::from_slice(&bytes).unpack(),
+        Ok(VendorCommand::ReadTemperature)
+    );
+
+    assert!(
+        Packet::from_slice(&[0x03, 0xFC, 0x00])
+            .unpack::<VendorCommand>()
+            .is_err()
+    );
+}
+
+#[test]
+fn test_gatt_payload() {
+    let state = PowerState {
+        level: 80,
+        charging: true,
+        name: b"kb".to_vec(),
+    };
+    let bytes = [0xD0, 0x02, b'k', b'b'];
+    assert_eq!(state.to_bytes(), bytes);
+    assert_eq!(Packet::from_slice(&bytes).unpack(), Ok(state));
+}
@@ -0,0 +1,155 @@
+//! btmon-like descriptions of the messages
+
+use bt_only_headers::messages::*;
+
+#[test]
+fn test_describe_connection_complete() {
+    let packet = H4Packet::Event(HciEvent::LeMeta(EvtLeMeta::LeConnectionComplete(
+        LeConnectionComplete {
+            status: HciStatus::Success,
+            connection_handle: ConnectionHandle(64),
+            role: Role::Peripheral,
+            peer_address_type: AddressType::Random,
+            peer_address: BdAddr([0x55, 0x44, 0x33, 0x22, 0x11, 0xC0]),
+            connection_interval: 39,
+            peripheral_latency: 0,
+            supervision_timeout: 96,
+            central_clock_accuracy: ClockAccuracy::Ppm500,
+        },
+    )));
+    assert_eq!(
+        packet.to_string(),
+        "\
+HCI Event (0x04)
+  LE Meta Event (0x3e)
+    LE Connection Complete (0x01)
+      Status: Success (0x00)
+      Handle: 64
+      Role: Peripheral (0x01)
+      Peer address type: Random (0x01)
+      Peer address: C0:11:22:33:44:55
+      Connection interval: 48.75 msec (0x0027)
+      Peripheral latency: 0
+      Supervision timeout: 960 msec (0x0060)
+      Central clock accuracy: 500 ppm (0x00)"
+    );
+}
+
+#[test]
+fn test_describe_command_complete() {
+    let event = HciEvent::CommandComplete(EvtCommandComplete {
+        num_hci_command_packets: 1,
+        command_opcode: OpCode(0x0003, 0x03),
+        status: Some(HciStatus::Success),
+        data: vec![],
+    });
+    assert_eq!(
+        event.to_string(),
+        "\
+Command Complete (0x0e)
+  Num HCI command packets: 1
+  Command opcode: Reset (0x03|0x0003)
+  Status: Success (0x00)
+  Data:"
+    );
+
+    // Opcodes without a command are only in hex
+    let status = EvtCommandStatus {
+        status: HciStatus::Failure(0x0C),
+        num_hci_command_packets: 1,
+        command_opcode: OpCode(0x000D, 0x08),
+    };
+    assert_eq!(
+        status.to_string(),
+        "\
+Status: Failure (0x0c)
+Num HCI command packets: 1
+Command opcode: 0x08|0x000d"
+    );
+}
+
+#[test]
+fn test_describe_pairing_request() {
+    let packet = H4Packet::Acl(HciAcl {
+        connection_handle: ConnectionHandle(0x40),
+        pb: PacketBoundaryFlag::FirstNonFlushable,
+        bc: BroadcastFlag::PointToPoint,
+        msg: L2CapMessage::Smp(SmpPdu::PairingRequest(SmpPairingReqRes {
+            io_capability: IOCapability::NoInputNoOutput,
+            oob_data_flag: OOBDataFlag::OobNotAvailable,
+            authentication_requirements: AuthenticationRequirements {
+                bonding: true,
+                secure_connections: true,
+                ct2: true,
+                ..Default::default()
+            },
+            max_encryption_key_size: 16,
+            initiator_key_distribution: KeyDistributionFlags {
+                enc_key: true,
+                id_key: true,
+                ..Default::default()
+            },
+            responder_key_distribution: KeyDistributionFlags::default(),
+        })),
+    });
+    assert_eq!(
+        packet.to_string(),
+        "\
+ACL Data (0x02)
+  Handle: 64
+  Packet boundary: First Non Flushable (0x00)
+  Broadcast: Point To Point (0x00)
+  L2CAP: SMP (0x0006)
+    Pairing Request (0x01)
+      IO capability: No Input No Output (0x03)
+      OOB data: OOB Not Available (0x00)
+      Authentication requirements: Bonding SC CT2
+      Max encryption key size: 16
+      Initiator key distribution: EncKey IdKey
+      Responder key distribution: None"
+    );
+}
+
+#[test]
+fn test_describe_att() {
+    let response = AttPdu::ReadByTypeResponse(AttReadByTypeResponse {
+        pair_length: 7,
+        values: vec![AttAttributeData {
+            handle: 0x0002,
+            value: vec![0x0A, 0x03, 0x00, 0x2A, 0x2A],
+        }],
+    });
+    assert_eq!(
+        response.to_string(),
+        "\
+Read By Type Response (0x09)
+  Values:
+    0:
+      Handle: 0x0002
+      Value: 0a 03 00 2a 2a"
+    );
+
+    let error = AttPdu::ErrorResponse(AttErrorResponse {
+        request_opcode: 0x08,
+        handle: 0x0010,
+        error_code: AttErrorCode::AttributeNotFound,
+    });
+    assert_eq!(
+        error.to_string(),
+        "\
+Error Response (0x01)
+  Request opcode: Read By Type Request (0x08)
+  Handle: 0x0010
+  Error code: Attribute Not Found (0x0a)"
+    );
+
+    // Longer opaque data is dumped 16 bytes per line
+    let unknown = L2CapMessage::Unknown(0x0055, (0..20).collect());
+    assert_eq!(
+        unknown.to_string(),
+        "\
+Unknown (0x0055)
+  00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f
+  10 11 12 13"
+    );
+}
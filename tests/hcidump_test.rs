@@ -8,63 +8,8 @@ use bt_only_headers::packer::*;
 use bt_only_headers::socket::MockSocket;
 use bt_only_headers::socket::Socket;
 
-fn parse_hci_dump(dump: String) -> Vec<(bool, Vec<u8>)> {
-    // Parse the HCI dump like this:
-    // If line starts with "<" then it is a command from the host to the controller
-    // If line starts with ">" then it is a command from the controller to the host
-    // If line starts wit " " then it is continued data from the previous line
-
-    // true = host to controller
-    // false = controller to host
-
-    let mut result = Vec::new();
-    let mut current: Option<(bool, Vec<u8>)> = None;
-
-    for line in dump.lines() {
-        let line = line.trim_end();
-        if line.is_empty() {
-            continue;
-        }
-        let (direction, data_str) = match line.chars().next() {
-            Some('<') => (true, &line[1..]),
-            Some('>') => (false, &line[1..]),
-            Some(' ') => {
-                if let Some((_, ref mut bytes)) = current {
-                    let data = line.trim();
-                    for byte in data.split_whitespace() {
-                        if let Ok(b) = u8::from_str_radix(byte, 16) {
-                            bytes.push(b);
-                        }
-                    }
-                }
-                continue;
-            }
-            _ => continue,
-        };
-
-        let data = data_str
-            .trim()
-            .split_whitespace()
-            .filter_map(|b| u8::from_str_radix(b, 16).ok())
-            .collect::<Vec<u8>>();
-
-        if let Some(c) = current.take() {
-            result.push(c);
-        }
-        current = Some((direction, data));
-    }
-
-    if let Some(c) = current {
-        result.push(c);
-    }
-
-    result
-}
-
-fn parse_hci_dump_from_file(file: &str) -> Vec<(bool, Vec<u8>)> {
-    let dump = std::fs::read_to_string(file).unwrap();
-    parse_hci_dump(dump)
-}
+mod common;
+use common::{all_fixtures, parse_hci_dump, parse_hci_dump_from_file};
 
 #[test]
 fn test_parse_hci_dump() {
@@ -76,7 +21,7 @@ fn test_parse_hci_dump() {
   04 01
 "#;
 
-    let parsed = parse_hci_dump(dump.to_string());
+    let parsed = parse_hci_dump(dump);
 
     assert_eq!(parsed[0].0, true);
     assert_eq!(parsed[0].1, vec![1, 3, 12, 0]);
@@ -233,3 +178,56 @@ fn test_hcimanager() {
 
     assert_eq!(queue.len(), 0, "Queue is now empty");
 }
+
+#[test]
+fn test_truncated_fixtures() {
+    for bytes in all_fixtures() {
+        let truncated = &bytes[..bytes.len() - 1];
+        let result = Packet::from_slice(truncated).unpack::<H4Packet>();
+        assert!(result.is_err(), "{:02X?} => {:?}", truncated, result);
+    }
+}
+
+#[test]
+fn test_acl_trailing_bytes() {
+    let acls = all_fixtures()
+        .into_iter()
+        .filter(|bytes| bytes[0] == 0x02)
+        .collect::<Vec<_>>();
+    assert!(!acls.is_empty());
+
+    for bytes in acls {
+        // One more byte in the ACL data than the L2CAP length covers
+        let mut padded = bytes.clone();
+        let length = u16::from_le_bytes([padded[3], padded[4]]);
+        padded[3..5].copy_from_slice(&(length + 1).to_le_bytes());
+        padded.push(0x00);
+        assert_eq!(
+            Packet::from_slice(&padded)
+                .unpack::<H4Packet>()
+                .map_err(|e| e.kind().clone()),
+            Err(PacketError::TrailingBytes {
+                length: length as usize + 1,
+                consumed: length as usize,
+            }),
+            "{:02X?}",
+            padded
+        );
+
+        // L2CAP length beyond the ACL data
+        let mut overrun = bytes.clone();
+        let l2cap_length = u16::from_le_bytes([overrun[5], overrun[6]]);
+        overrun[5..7].copy_from_slice(&(l2cap_length + 1).to_le_bytes());
+        assert_eq!(
+            Packet::from_slice(&overrun)
+                .unpack::<H4Packet>()
+                .map_err(|e| e.kind().clone()),
+            Err(PacketError::Truncated {
+                length: l2cap_length as usize + 3,
+                remaining: l2cap_length as usize + 2,
+            }),
+            "{:02X?}",
+            overrun
+        );
+    }
+}
@@ -1 +1,1137 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::Duration;
 
+use bt_only_headers::advertising::*;
+use bt_only_headers::clock::VirtualClock;
+use bt_only_headers::connection::*;
+use bt_only_headers::hcimanager::AppMsg;
+use bt_only_headers::hcimanager::CommandTimeoutConfig;
+use bt_only_headers::hcimanager::HciError;
+use bt_only_headers::hcimanager::HciManager;
+use bt_only_headers::hcimanager::Layer;
+use bt_only_headers::hcimanager::MsgProcessor;
+use bt_only_headers::hcimanager::ProtocolError;
+use bt_only_headers::hcimanager::TimerId;
+use bt_only_headers::hcimanager::TimerMsg;
+use bt_only_headers::hcimanager::UserMsg;
+use bt_only_headers::messages::*;
+use bt_only_headers::middleware::Layered;
+use bt_only_headers::middleware::PeerFilter;
+use bt_only_headers::packer::*;
+use bt_only_headers::random::Random;
+use bt_only_headers::routing::CID_SMP;
+use bt_only_headers::snapshot::ConnectionSnapshot;
+use bt_only_headers::socket::MockSocket;
+use bt_only_headers::socket::Socket;
+
+/// Packet read from the virtual controller
+fn rx(packet: H4Packet) -> (bool, Vec<u8>) {
+    (false, packet.to_bytes())
+}
+
+/// Packet the host is expected to write to the virtual controller
+fn tx(packet: H4Packet) -> (bool, Vec<u8>) {
+    (true, packet.to_bytes())
+}
+
+fn command_complete(opcode: OpCode) -> H4Packet {
+    H4Packet::Event(HciEvent::CommandComplete(EvtCommandComplete {
+        num_hci_command_packets: 1,
+        command_opcode: opcode,
+        status: Some(HciStatus::Success),
+        data: vec![],
+    }))
+}
+
+fn connection_complete(handle: u16) -> H4Packet {
+    H4Packet::Event(HciEvent::LeMeta(EvtLeMeta::LeConnectionComplete(
+        LeConnectionComplete {
+            status: HciStatus::Success,
+            connection_handle: ConnectionHandle(handle),
+            role: Role::Peripheral,
+            peer_address_type: AddressType::Public,
+            peer_address: BdAddr([38, 14, 214, 232, 194, 80]),
+            connection_interval: 48,
+            peripheral_latency: 0,
+            supervision_timeout: 960,
+            central_clock_accuracy: ClockAccuracy::Ppm250,
+        },
+    )))
+}
+
+fn mtu_request(handle: u16) -> H4Packet {
+    H4Packet::Acl(HciAcl {
+        connection_handle: ConnectionHandle(handle),
+        pb: PacketBoundaryFlag::FirstNonFlushable,
+        bc: BroadcastFlag::PointToPoint,
+        msg: L2CapMessage::Att(AttPdu::ExchangeMtuRequest(244)),
+    })
+}
+
+fn init_commands() -> Vec<HciCommand> {
+    vec![
+        HciCommand::SetEventMask(0x3DBFF807FFFBFFFF),
+        HciCommand::LeSetAdvertisingEnable(true),
+    ]
+}
+
+/// Runs the manager against the virtual controller until it has nothing to
+/// read, returns the messages not handled by the socket
+fn run(mgr: &mut HciManager, socket: &mut MockSocket, mut queue: VecDeque<AppMsg>) -> Vec<AppMsg> {
+    let mut app_msgs = vec![];
+    loop {
+        while let Some(msg) = queue.pop_front() {
+            queue.append(&mut mgr.process(msg.clone()).unwrap().into());
+            match msg {
+                AppMsg::Send(packet) => socket.write(packet).unwrap(),
+                AppMsg::Recv(_) => {}
+                other => app_msgs.push(other),
+            }
+        }
+        match socket.read().unwrap() {
+            Some(packet) => queue.push_back(AppMsg::Recv(packet)),
+            None => break,
+        }
+    }
+    app_msgs
+}
+
+#[test]
+fn test_init_sequence() {
+    let mut mgr = HciManager::new().unwrap();
+    mgr.set_init_commands(init_commands());
+
+    let mut socket = MockSocket::new(
+        vec![
+            tx(H4Packet::Command(HciCommand::SetEventMask(
+                0x3DBFF807FFFBFFFF,
+            ))),
+            rx(command_complete(OpCode(0x0001, 0x03))),
+            tx(H4Packet::Command(HciCommand::LeSetAdvertisingEnable(true))),
+            rx(command_complete(OpCode(0x000A, 0x08))),
+        ]
+        .into(),
+    );
+    run(&mut mgr, &mut socket, vec![AppMsg::InitHciManager].into());
+    assert!(socket.is_empty(), "All packets are exchanged");
+}
+
+#[test]
+fn test_hardware_error_recovery() {
+    let mut mgr = HciManager::new().unwrap();
+    mgr.set_init_commands(init_commands());
+
+    let mut socket = MockSocket::new(
+        vec![
+            rx(connection_complete(0x40)),
+            tx(mtu_request(0x40)),
+            rx(H4Packet::Event(HciEvent::HardwareError(EvtHardwareError {
+                hardware_code: 0x01,
+            }))),
+            tx(H4Packet::Command(HciCommand::Reset)),
+            rx(command_complete(OpCode(0x0003, 0x03))),
+            tx(H4Packet::Command(HciCommand::SetEventMask(
+                0x3DBFF807FFFBFFFF,
+            ))),
+            rx(command_complete(OpCode(0x0001, 0x03))),
+            tx(H4Packet::Command(HciCommand::LeSetAdvertisingEnable(true))),
+            rx(command_complete(OpCode(0x000A, 0x08))),
+            // Processors of the lost connection are gone, and new one works
+            rx(connection_complete(0x41)),
+            tx(mtu_request(0x41)),
+        ]
+        .into(),
+    );
+    let app_msgs = run(&mut mgr, &mut socket, VecDeque::new());
+    assert!(socket.is_empty(), "All packets are exchanged");
+    assert_eq!(
+        app_msgs,
+        vec![AppMsg::ConnectionsLost(vec![ConnectionHandle(0x40)])]
+    );
+}
+
+#[test]
+fn test_data_buffer_overflow_recovery() {
+    let mut mgr = HciManager::new().unwrap();
+    mgr.set_init_commands(init_commands());
+
+    let mut socket = MockSocket::new(
+        vec![
+            rx(H4Packet::Event(HciEvent::DataBufferOverflow(
+                EvtDataBufferOverflow { link_type: 0x01 },
+            ))),
+            tx(H4Packet::Command(HciCommand::Reset)),
+            rx(command_complete(OpCode(0x0003, 0x03))),
+            tx(H4Packet::Command(HciCommand::SetEventMask(
+                0x3DBFF807FFFBFFFF,
+            ))),
+            rx(command_complete(OpCode(0x0001, 0x03))),
+            tx(H4Packet::Command(HciCommand::LeSetAdvertisingEnable(true))),
+            rx(command_complete(OpCode(0x000A, 0x08))),
+        ]
+        .into(),
+    );
+    let app_msgs = run(&mut mgr, &mut socket, VecDeque::new());
+    assert!(socket.is_empty(), "All packets are exchanged");
+    assert_eq!(app_msgs, vec![AppMsg::ConnectionsLost(vec![])]);
+}
+
+#[test]
+fn test_hardware_error_decoding() {
+    let mut packet = Packet::from_slice(&[0x04, 0x10, 0x01, 0x2a]);
+    let msg = packet.unpack::<H4Packet>().unwrap();
+    assert_eq!(
+        msg,
+        H4Packet::Event(HciEvent::HardwareError(EvtHardwareError {
+            hardware_code: 0x2a
+        }))
+    );
+}
+
+/// Disconnects on request, and records what it receives
+struct Disconnector {
+    received: Rc<RefCell<Vec<AppMsg>>>,
+}
+
+impl MsgProcessor for Disconnector {
+    fn process(&mut self, msg: AppMsg) -> Result<Vec<AppMsg>, HciError> {
+        match msg {
+            AppMsg::Disconnect(handle) => Ok(vec![AppMsg::Send(H4Packet::Command(
+                HciCommand::Disconnect(CmdDisconnect {
+                    connection_handle: handle,
+                    reason: 0x13,
+                }),
+            ))]),
+            AppMsg::CommandFailed(_) => {
+                self.received.borrow_mut().push(msg);
+                Ok(vec![])
+            }
+            _ => Ok(vec![]),
+        }
+    }
+}
+
+#[test]
+fn test_command_timeout_retry() {
+    let clock = VirtualClock::new();
+    let mut mgr = HciManager::new().unwrap();
+    mgr.set_clock(Box::new(clock.clone()));
+    mgr.set_init_commands(init_commands());
+
+    // Virtual controller swallows the first SetEventMask
+    let mut socket = MockSocket::new(
+        vec![
+            tx(H4Packet::Command(HciCommand::SetEventMask(
+                0x3DBFF807FFFBFFFF,
+            ))),
+            tx(H4Packet::Command(HciCommand::SetEventMask(
+                0x3DBFF807FFFBFFFF,
+            ))),
+            rx(command_complete(OpCode(0x0001, 0x03))),
+            tx(H4Packet::Command(HciCommand::LeSetAdvertisingEnable(true))),
+            rx(command_complete(OpCode(0x000A, 0x08))),
+        ]
+        .into(),
+    );
+    run(&mut mgr, &mut socket, vec![AppMsg::InitHciManager].into());
+
+    // Not yet timed out
+    clock.advance(Duration::from_millis(1999));
+    run(&mut mgr, &mut socket, vec![AppMsg::Tick].into());
+    assert!(!socket.is_empty());
+
+    clock.advance(Duration::from_millis(1));
+    let app_msgs = run(&mut mgr, &mut socket, vec![AppMsg::Tick].into());
+    assert!(socket.is_empty(), "All packets are exchanged");
+    assert_eq!(app_msgs, vec![AppMsg::Tick]);
+}
+
+#[test]
+fn test_command_timeout_recovers_controller() {
+    let clock = VirtualClock::new();
+    let mut mgr = HciManager::new().unwrap();
+    mgr.set_clock(Box::new(clock.clone()));
+    mgr.set_init_commands(init_commands());
+    mgr.set_command_timeout(CommandTimeoutConfig {
+        timeout: Duration::from_secs(1),
+        retries: 0,
+    });
+
+    let mut socket = MockSocket::new(
+        vec![
+            tx(H4Packet::Command(HciCommand::SetEventMask(
+                0x3DBFF807FFFBFFFF,
+            ))),
+            tx(H4Packet::Command(HciCommand::Reset)),
+        ]
+        .into(),
+    );
+    run(&mut mgr, &mut socket, vec![AppMsg::InitHciManager].into());
+    clock.advance(Duration::from_secs(1));
+    let app_msgs = run(&mut mgr, &mut socket, vec![AppMsg::Tick].into());
+    assert!(socket.is_empty(), "All packets are exchanged");
+    assert_eq!(
+        app_msgs,
+        vec![AppMsg::Tick, AppMsg::ConnectionsLost(vec![])]
+    );
+}
+
+#[test]
+fn test_command_timeout_delivered_to_issuer() {
+    let clock = VirtualClock::new();
+    let mut mgr = HciManager::new().unwrap();
+    mgr.set_clock(Box::new(clock.clone()));
+
+    let issuer_received = Rc::new(RefCell::new(vec![]));
+    let other_received = Rc::new(RefCell::new(vec![]));
+    mgr.add_processor(Box::new(Disconnector {
+        received: issuer_received.clone(),
+    }));
+
+    // Disconnect is not idempotent, so it's not retried
+    let mut socket = MockSocket::new(
+        vec![tx(H4Packet::Command(HciCommand::Disconnect(
+            CmdDisconnect {
+                connection_handle: ConnectionHandle(0x40),
+                reason: 0x13,
+            },
+        )))]
+        .into(),
+    );
+    run(
+        &mut mgr,
+        &mut socket,
+        vec![AppMsg::Disconnect(ConnectionHandle(0x40))].into(),
+    );
+    assert!(socket.is_empty(), "All packets are exchanged");
+
+    // Added after the command was issued, must not get the failure
+    mgr.add_processor(Box::new(Disconnector {
+        received: other_received.clone(),
+    }));
+
+    clock.advance(Duration::from_secs(2));
+    run(&mut mgr, &mut socket, vec![AppMsg::Tick].into());
+    assert_eq!(
+        *issuer_received.borrow(),
+        vec![AppMsg::CommandFailed(HciError::CommandTimeout(OpCode(
+            0x0006, 0x01
+        )))]
+    );
+    assert_eq!(*other_received.borrow(), vec![]);
+}
+
+/// Two processors send the same command, the answer is for the one sent
+/// first and the other times out
+#[test]
+fn test_command_answered_in_order() {
+    let clock = VirtualClock::new();
+    let mut mgr = HciManager::new().unwrap();
+    mgr.set_clock(Box::new(clock.clone()));
+
+    let first_received = Rc::new(RefCell::new(vec![]));
+    let second_received = Rc::new(RefCell::new(vec![]));
+    mgr.add_processor(Box::new(Disconnector {
+        received: first_received.clone(),
+    }));
+    mgr.add_processor(Box::new(Disconnector {
+        received: second_received.clone(),
+    }));
+
+    let disconnect = H4Packet::Command(HciCommand::Disconnect(CmdDisconnect {
+        connection_handle: ConnectionHandle(0x40),
+        reason: 0x13,
+    }));
+    let mut socket = MockSocket::new(
+        vec![
+            tx(disconnect.clone()),
+            tx(disconnect),
+            rx(command_status(OpCode(0x0006, 0x01))),
+        ]
+        .into(),
+    );
+    run(
+        &mut mgr,
+        &mut socket,
+        vec![AppMsg::Disconnect(ConnectionHandle(0x40))].into(),
+    );
+    assert!(socket.is_empty(), "All packets are exchanged");
+
+    clock.advance(Duration::from_secs(2));
+    run(&mut mgr, &mut socket, vec![AppMsg::Tick].into());
+    assert_eq!(*first_received.borrow(), vec![]);
+    assert_eq!(
+        *second_received.borrow(),
+        vec![AppMsg::CommandFailed(HciError::CommandTimeout(OpCode(
+            0x0006, 0x01
+        )))]
+    );
+}
+
+/// Key-repeat like processor: starts a repeating timer on the first tick and
+/// stops after three expirations
+struct Repeater {
+    interval: Duration,
+    started: bool,
+    expired: Rc<RefCell<Vec<TimerId>>>,
+}
+
+impl MsgProcessor for Repeater {
+    fn process(&mut self, msg: AppMsg) -> Result<Vec<AppMsg>, HciError> {
+        match msg {
+            AppMsg::Tick if !self.started => {
+                self.started = true;
+                Ok(vec![
+                    AppMsg::Timer(TimerMsg::Start(TimerId(1), self.interval)),
+                    AppMsg::Timer(TimerMsg::Start(TimerId(2), Duration::from_secs(60))),
+                ])
+            }
+            AppMsg::Timer(TimerMsg::Expired(id)) => {
+                self.expired.borrow_mut().push(id);
+                if self.expired.borrow().len() < 3 {
+                    Ok(vec![AppMsg::Timer(TimerMsg::Start(id, self.interval))])
+                } else {
+                    Ok(vec![AppMsg::Timer(TimerMsg::Cancel(TimerId(2)))])
+                }
+            }
+            _ => Ok(vec![]),
+        }
+    }
+}
+
+#[test]
+fn test_timers() {
+    let clock = VirtualClock::new();
+    let mut mgr = HciManager::new().unwrap();
+    mgr.set_clock(Box::new(clock.clone()));
+
+    let fast = Rc::new(RefCell::new(vec![]));
+    let slow = Rc::new(RefCell::new(vec![]));
+    mgr.add_processor(Box::new(Repeater {
+        interval: Duration::from_millis(100),
+        started: false,
+        expired: fast.clone(),
+    }));
+    mgr.add_processor(Box::new(Repeater {
+        interval: Duration::from_millis(250),
+        started: false,
+        expired: slow.clone(),
+    }));
+
+    // Timer requests are consumed by the manager
+    assert_eq!(mgr.process(AppMsg::Tick).unwrap(), vec![]);
+    assert_eq!(mgr.next_deadline(), Some(Duration::from_millis(100)));
+
+    for _ in 0..10 {
+        clock.advance(Duration::from_millis(50));
+        mgr.process(AppMsg::Tick).unwrap();
+    }
+
+    // Same timer id in both processors, each gets only its own
+    assert_eq!(*fast.borrow(), vec![TimerId(1), TimerId(1), TimerId(1)]);
+    assert_eq!(*slow.borrow(), vec![TimerId(1), TimerId(1)]);
+
+    // Fast one cancelled its 60 second timer, the slow one still has both
+    assert_eq!(mgr.next_deadline(), Some(Duration::from_millis(750)));
+    assert_eq!(
+        mgr.time_to_next_deadline(),
+        Some(Duration::from_millis(250))
+    );
+    clock.advance(Duration::from_secs(60));
+    assert_eq!(mgr.time_to_next_deadline(), Some(Duration::ZERO));
+    mgr.process(AppMsg::Tick).unwrap();
+    assert_eq!(
+        *slow.borrow(),
+        vec![TimerId(1), TimerId(1), TimerId(1)],
+        "Cancelled by the third expiration, before the 60 second timer"
+    );
+    assert_eq!(mgr.next_deadline(), None);
+}
+
+#[test]
+fn test_connection_registry() {
+    let mut mgr = HciManager::new().unwrap();
+    let mut recv = |packet: H4Packet| mgr.process(AppMsg::Recv(packet)).unwrap();
+
+    recv(connection_complete(0x40));
+    recv(H4Packet::Acl(HciAcl {
+        connection_handle: ConnectionHandle(0x40),
+        pb: PacketBoundaryFlag::FirstNonFlushable,
+        bc: BroadcastFlag::PointToPoint,
+        msg: L2CapMessage::Att(AttPdu::ExchangeMtuResponse(100)),
+    }));
+    recv(H4Packet::Event(HciEvent::LeMeta(
+        EvtLeMeta::LeConnectionUpdateComplete(LeConnectionUpdateComplete {
+            status: HciStatus::Success,
+            connection_handle: ConnectionHandle(0x40),
+            interval: 12,
+            latency: 4,
+            timeout: 500,
+        }),
+    )));
+    recv(H4Packet::Event(HciEvent::EncryptionChange(
+        EvtEncryptionChange {
+            status: HciStatus::Success,
+            connection_handle: ConnectionHandle(0x40),
+            encryption_enabled: true,
+        },
+    )));
+    recv(connection_complete(0x41));
+
+    assert_eq!(
+        mgr.connections().handles(),
+        vec![ConnectionHandle(0x40), ConnectionHandle(0x41)]
+    );
+    assert_eq!(
+        mgr.connections().get(&ConnectionHandle(0x40)),
+        Some(&Connection {
+            handle: ConnectionHandle(0x40),
+            peer_address: BdAddr([38, 14, 214, 232, 194, 80]),
+            peer_address_type: AddressType::Public,
+            role: Role::Peripheral,
+            parameters: ConnectionParameters {
+                interval: 12,
+                latency: 4,
+                supervision_timeout: 500,
+            },
+            security_level: SecurityLevel::Unauthenticated,
+            mtu: 100,
+            pairing: PairingStage::Complete,
+            acl_in_flight: 3,
+        })
+    );
+    let other = mgr.connections().get(&ConnectionHandle(0x41)).unwrap();
+    assert_eq!(other.security_level, SecurityLevel::NoSecurity);
+    assert_eq!(other.mtu, DEFAULT_ATT_MTU);
+
+    let mut recv = |packet: H4Packet| mgr.process(AppMsg::Recv(packet)).unwrap();
+    recv(H4Packet::Event(HciEvent::DisconnectComplete(
+        EvtDisconnectComplete {
+            status: HciStatus::Success,
+            connection_handle: ConnectionHandle(0x40),
+            reason: 0x13,
+        },
+    )));
+
+    // Processors of the closed connection are gone, the other still answers
+    assert_eq!(recv(mtu_request(0x40)), vec![]);
+    assert_ne!(recv(mtu_request(0x41)), vec![]);
+    assert_eq!(mgr.connections().handles(), vec![ConnectionHandle(0x41)]);
+}
+
+#[test]
+fn test_failed_connection_not_registered() {
+    let mut mgr = HciManager::new().unwrap();
+    let msgs = mgr
+        .process(AppMsg::Recv(H4Packet::Event(HciEvent::LeMeta(
+            EvtLeMeta::LeConnectionComplete(LeConnectionComplete {
+                // Connection Failed to be Established
+                status: HciStatus::Failure(0x3E),
+                connection_handle: ConnectionHandle(0x40),
+                role: Role::Peripheral,
+                peer_address_type: AddressType::Public,
+                peer_address: BdAddr([38, 14, 214, 232, 194, 80]),
+                connection_interval: 48,
+                peripheral_latency: 0,
+                supervision_timeout: 960,
+                central_clock_accuracy: ClockAccuracy::Ppm250,
+            }),
+        ))))
+        .unwrap();
+    assert_eq!(msgs, vec![]);
+    assert!(mgr.connections().is_empty());
+}
+
+/// Predictable random numbers, each call returns the next byte values
+struct Counter(u8);
+
+impl Random for Counter {
+    fn fill(&mut self, buf: &mut [u8]) {
+        for b in buf.iter_mut() {
+            self.0 = self.0.wrapping_add(1);
+            *b = self.0;
+        }
+    }
+}
+
+fn acl(handle: u16, msg: L2CapMessage) -> H4Packet {
+    H4Packet::Acl(HciAcl {
+        connection_handle: ConnectionHandle(handle),
+        pb: PacketBoundaryFlag::FirstNonFlushable,
+        bc: BroadcastFlag::PointToPoint,
+        msg,
+    })
+}
+
+fn encryption_change(handle: u16) -> H4Packet {
+    H4Packet::Event(HciEvent::EncryptionChange(EvtEncryptionChange {
+        status: HciStatus::Success,
+        connection_handle: ConnectionHandle(handle),
+        encryption_enabled: true,
+    }))
+}
+
+/// Packets sent by the manager
+fn sent(msgs: Vec<AppMsg>) -> Vec<H4Packet> {
+    msgs.into_iter()
+        .filter_map(|msg| match msg {
+            AppMsg::Send(packet) => Some(packet),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Connections of the packets sent by the manager
+fn sent_to(msgs: Vec<AppMsg>) -> Vec<u16> {
+    sent(msgs)
+        .into_iter()
+        .filter_map(|packet| match packet {
+            H4Packet::Acl(acl) => Some(acl.connection_handle.0),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_two_centrals() {
+    let mut mgr = HciManager::new().unwrap();
+    mgr.set_random(Box::new(Counter(0)));
+    mgr.set_max_connections(2);
+    let mut recv = |packet: H4Packet| mgr.process(AppMsg::Recv(packet)).unwrap();
+
+    // Advertising continues after the first connection only
+    assert_eq!(
+        sent(recv(connection_complete(0x40))),
+        vec![
+            mtu_request(0x40),
+            H4Packet::Command(HciCommand::LeSetAdvertisingEnable(true)),
+        ]
+    );
+    recv(command_complete(OpCode(0x000A, 0x08)));
+    assert_eq!(
+        sent(recv(connection_complete(0x41))),
+        vec![mtu_request(0x41)]
+    );
+
+    // Both centrals pair at the same time
+    let pairing_request = Packet::from_slice(&[0x01, 0x03, 0x00, 0x2d, 0x10, 0x0e, 0x0f])
+        .unpack::<SmpPdu>()
+        .unwrap();
+    for handle in [0x41, 0x40] {
+        let msgs = recv(acl(handle, L2CapMessage::Smp(pairing_request.clone())));
+        assert_eq!(sent_to(msgs), vec![handle]);
+    }
+    let confirm = L2CapMessage::Smp(SmpPdu::PairingConfirmation(SmpPairingConfirmation {
+        confirm_value: 1,
+    }));
+    let confirm_values: Vec<H4Packet> = [0x40, 0x41]
+        .into_iter()
+        .flat_map(|handle| sent(recv(acl(handle, confirm.clone()))))
+        .collect();
+    assert_eq!(confirm_values.len(), 2);
+    assert_ne!(
+        confirm_values[0].to_bytes()[9..],
+        confirm_values[1].to_bytes()[9..],
+        "Connections have their own randoms"
+    );
+
+    // Reports go only to the given encrypted connection
+    let report = AttHandleValueNotification {
+        handle: 0x001A,
+        value: vec![0, 0, 4, 0, 0, 0, 0, 0],
+    };
+    assert_eq!(sent_to(recv(encryption_change(0x40))), vec![0x40, 0x40]);
+    let mut report_to = |handle| {
+        sent_to(
+            mgr.process(AppMsg::HidReport(ConnectionHandle(handle), report.clone()))
+                .unwrap(),
+        )
+    };
+    assert_eq!(report_to(0x40), vec![0x40]);
+    assert_eq!(report_to(0x41), vec![]);
+
+    // Second connection outlives the first
+    let mut recv = |packet: H4Packet| mgr.process(AppMsg::Recv(packet)).unwrap();
+    recv(H4Packet::Event(HciEvent::DisconnectComplete(
+        EvtDisconnectComplete {
+            status: HciStatus::Success,
+            connection_handle: ConnectionHandle(0x40),
+            reason: 0x13,
+        },
+    )));
+    assert_eq!(sent_to(recv(encryption_change(0x41))), vec![0x41, 0x41]);
+    let mut report_to = |handle| {
+        sent_to(
+            mgr.process(AppMsg::HidReport(ConnectionHandle(handle), report.clone()))
+                .unwrap(),
+        )
+    };
+    assert_eq!(report_to(0x40), vec![]);
+    assert_eq!(report_to(0x41), vec![0x41]);
+    assert_eq!(mgr.connections().handles(), vec![ConnectionHandle(0x41)]);
+}
+
+fn disconnect_complete(handle: u16) -> H4Packet {
+    H4Packet::Event(HciEvent::DisconnectComplete(EvtDisconnectComplete {
+        status: HciStatus::Success,
+        connection_handle: ConnectionHandle(handle),
+        reason: 0x13,
+    }))
+}
+
+/// Commands sent by the manager, each one is completed by the controller
+/// before the next one is sent
+fn commands(mgr: &mut impl MsgProcessor, msgs: Vec<AppMsg>) -> Vec<HciCommand> {
+    let mut commands = vec![];
+    let mut queue: VecDeque<AppMsg> = msgs.into();
+    while let Some(msg) = queue.pop_front() {
+        if let AppMsg::Send(H4Packet::Command(command)) = msg {
+            let opcode = command.get_id();
+            commands.push(command);
+            queue.extend(mgr.process(AppMsg::Recv(command_complete(opcode))).unwrap());
+        }
+    }
+    commands
+}
+
+#[test]
+fn test_readvertise_on_disconnect() {
+    let mut mgr = HciManager::new().unwrap();
+    let mut process = |msg| {
+        let msgs = mgr.process(msg).unwrap();
+        commands(&mut mgr, msgs)
+    };
+    assert_eq!(process(AppMsg::Recv(connection_complete(0x40))), vec![]);
+    assert_eq!(
+        process(AppMsg::Recv(disconnect_complete(0x40))),
+        vec![HciCommand::LeSetAdvertisingEnable(true)]
+    );
+
+    // Stopped advertising stays stopped after disconnect
+    process(AppMsg::Recv(connection_complete(0x41)));
+    assert_eq!(process(AppMsg::Advertising(AdvertisingMsg::Stop)), vec![]);
+    assert_eq!(process(AppMsg::Recv(disconnect_complete(0x41))), vec![]);
+    assert_eq!(
+        process(AppMsg::Advertising(AdvertisingMsg::Resume)),
+        vec![HciCommand::LeSetAdvertisingEnable(true)]
+    );
+    assert_eq!(
+        process(AppMsg::Advertising(AdvertisingMsg::Stop)),
+        vec![HciCommand::LeSetAdvertisingEnable(false)]
+    );
+}
+
+/// Controller stops advertising for a blocked peer too, the manager restarts
+/// it once the filter has disconnected the peer
+#[test]
+fn test_peer_filter_readvertises() {
+    let peer = BdAddr([38, 14, 214, 232, 194, 80]);
+    let mut layered = Layered::new(HciManager::new().unwrap()).with(PeerFilter::new([peer]));
+
+    let msgs = layered
+        .process(AppMsg::Recv(connection_complete(0x40)))
+        .unwrap();
+    assert_eq!(
+        sent(msgs),
+        vec![H4Packet::Command(HciCommand::Disconnect(CmdDisconnect {
+            connection_handle: ConnectionHandle(0x40),
+            reason: 0x13,
+        }))]
+    );
+    assert!(!layered.inner().snapshot().advertising);
+
+    let msgs = layered
+        .process(AppMsg::Recv(disconnect_complete(0x40)))
+        .unwrap();
+    assert_eq!(
+        commands(&mut layered, msgs),
+        vec![HciCommand::LeSetAdvertisingEnable(true)]
+    );
+    assert!(layered.inner().connections().handles().is_empty());
+}
+
+#[test]
+fn test_advertising_policy() {
+    let parameters = LeSetAdvertisingParameters {
+        advertising_interval_min: 512,
+        advertising_interval_max: 512,
+        advertising_type: 0x00,
+        own_address_type: 0x01,
+        peer_address_type: 0x00,
+        peer_address: BdAddr([0; 6]),
+        advertising_channel_map: 0x07,
+        advertising_filter_policy: 0x00,
+    };
+    let advertise = |interval: u16, peer: Option<BdAddr>| {
+        let mut parameters = parameters.clone();
+        parameters.advertising_interval_min = interval;
+        parameters.advertising_interval_max = interval;
+        if let Some(peer) = peer {
+            parameters.advertising_type = 0x04;
+            parameters.peer_address = peer;
+        }
+        HciCommand::LeSetAdvertisingParameters(parameters)
+    };
+    let enable = HciCommand::LeSetAdvertisingEnable(true);
+    let disable = HciCommand::LeSetAdvertisingEnable(false);
+    let host = BdAddr([38, 14, 214, 232, 194, 80]);
+
+    let clock = VirtualClock::new();
+    let mut mgr = HciManager::new().unwrap();
+    mgr.set_clock(Box::new(clock.clone()));
+    mgr.set_init_commands(init_commands());
+    mgr.set_advertising_policy(AdvertisingPolicy {
+        parameters: parameters.clone(),
+        fast_interval: 48,
+        fast_timeout: Duration::from_secs(30),
+        slow_interval: 1600,
+        slow_timeout: Some(Duration::from_secs(60)),
+        directed: true,
+    });
+    let mut process = |msg| {
+        let msgs = mgr.process(msg).unwrap();
+        commands(&mut mgr, msgs)
+    };
+
+    // Fast, then slow advertising in place of the init sequence's
+    assert_eq!(
+        process(AppMsg::InitHciManager),
+        vec![
+            HciCommand::SetEventMask(0x3DBFF807FFFBFFFF),
+            advertise(48, None),
+            enable.clone(),
+        ]
+    );
+    clock.advance(Duration::from_secs(29));
+    assert_eq!(process(AppMsg::Tick), vec![]);
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(
+        process(AppMsg::Tick),
+        vec![disable.clone(), advertise(1600, None), enable.clone()]
+    );
+
+    // Bonded host is advertised to directly after disconnect
+    process(AppMsg::Recv(connection_complete(0x40)));
+    process(AppMsg::Recv(encryption_change(0x40)));
+    assert_eq!(
+        process(AppMsg::Recv(disconnect_complete(0x40))),
+        vec![advertise(48, Some(host.clone())), enable.clone()]
+    );
+    clock.advance(Duration::from_secs(30));
+    assert_eq!(
+        process(AppMsg::Tick),
+        vec![disable.clone(), advertise(1600, None), enable.clone()]
+    );
+    clock.advance(Duration::from_secs(60));
+    assert_eq!(process(AppMsg::Tick), vec![disable.clone()]);
+    assert_eq!(process(AppMsg::Tick), vec![]);
+
+    // Application can restart the fast advertising
+    assert_eq!(
+        process(AppMsg::Advertising(AdvertisingMsg::Resume)),
+        vec![advertise(48, Some(host)), enable]
+    );
+    assert_eq!(
+        process(AppMsg::Advertising(AdvertisingMsg::Stop)),
+        vec![disable]
+    );
+    clock.advance(Duration::from_secs(30));
+    assert_eq!(process(AppMsg::Tick), vec![]);
+}
+
+#[derive(Debug, PartialEq)]
+struct KeyPress(u8);
+
+#[derive(Debug, PartialEq)]
+struct KeysTyped(usize);
+
+/// Counts the key presses sent by the application
+struct KeyCounter(usize);
+
+impl MsgProcessor for KeyCounter {
+    fn process(&mut self, msg: AppMsg) -> Result<Vec<AppMsg>, HciError> {
+        match msg {
+            AppMsg::User(user) if user.is::<KeyPress>() => {
+                self.0 += 1;
+                Ok(vec![AppMsg::User(UserMsg::new(KeysTyped(self.0)))])
+            }
+            _ => Ok(vec![]),
+        }
+    }
+}
+
+#[test]
+fn test_user_messages() {
+    let mut mgr = HciManager::new().unwrap();
+    mgr.add_processor(Box::new(KeyCounter(0)));
+
+    let msgs = mgr
+        .process(AppMsg::User(UserMsg::new(KeyPress(0x04))))
+        .unwrap();
+    assert_eq!(msgs, vec![AppMsg::User(UserMsg::new(KeysTyped(1)))]);
+    assert_eq!(msgs[0].clone(), msgs[0]);
+    let AppMsg::User(user) = &msgs[0] else {
+        panic!("Expected user message");
+    };
+    assert_eq!(user.downcast_ref::<KeysTyped>(), Some(&KeysTyped(1)));
+    assert_eq!(user.downcast_ref::<KeyPress>(), None);
+    assert_eq!(format!("{:?}", user), "KeysTyped(1)");
+
+    // Different types never compare equal
+    assert_ne!(UserMsg::new(KeyPress(1)), UserMsg::new(KeysTyped(1)));
+
+    // Own messages are ignored by the built-in processors
+    assert_eq!(
+        mgr.process(AppMsg::User(UserMsg::new(KeysTyped(1))))
+            .unwrap(),
+        vec![]
+    );
+}
+
+#[test]
+fn test_connection_failure_isolated() {
+    let mut mgr = HciManager::new().unwrap();
+    mgr.set_max_connections(2);
+    let mut recv = |packet: H4Packet| mgr.process(AppMsg::Recv(packet)).unwrap();
+    recv(connection_complete(0x40));
+    recv(command_complete(OpCode(0x000A, 0x08)));
+    recv(connection_complete(0x41));
+
+    // Pairing Confirm without Pairing Request fails the connection
+    let confirm = L2CapMessage::Smp(SmpPdu::PairingConfirmation(SmpPairingConfirmation {
+        confirm_value: 1,
+    }));
+    let msgs = recv(acl(0x40, confirm.clone()));
+    assert_eq!(
+        msgs,
+        vec![
+            AppMsg::ConnectionFailed(
+                ConnectionHandle(0x40),
+                HciError::Protocol(ProtocolError {
+                    connection: Some(ConnectionHandle(0x40)),
+                    layer: Layer::Smp,
+                    pdu: Some(0x03),
+                    reason: "Pairing Request was not received".to_string(),
+                })
+            ),
+            AppMsg::Send(H4Packet::Command(HciCommand::Disconnect(CmdDisconnect {
+                connection_handle: ConnectionHandle(0x40),
+                reason: 0x13,
+            }))),
+        ]
+    );
+
+    // Nothing is left to answer on the failed connection, the other one works
+    assert_eq!(recv(mtu_request(0x40)), vec![]);
+    assert_ne!(recv(mtu_request(0x41)), vec![]);
+    assert!(mgr.connections().contains(&ConnectionHandle(0x40)));
+
+    let mut recv = |packet: H4Packet| mgr.process(AppMsg::Recv(packet)).unwrap();
+    recv(command_status(OpCode(0x0006, 0x01)));
+    recv(disconnect_complete(0x40));
+    assert_eq!(mgr.connections().handles(), vec![ConnectionHandle(0x41)]);
+}
+
+/// Application processor failing on every packet from the controller
+struct FailingApp;
+
+impl MsgProcessor for FailingApp {
+    fn process(&mut self, msg: AppMsg) -> Result<Vec<AppMsg>, HciError> {
+        match msg {
+            AppMsg::Recv(_) => Err(HciError::Unknown("Failing app".to_string())),
+            _ => Ok(vec![]),
+        }
+    }
+}
+
+#[test]
+fn test_app_error_after_manager() {
+    let mut mgr = HciManager::new().unwrap();
+    mgr.set_init_commands(init_commands());
+    mgr.add_processor(Box::new(FailingApp));
+    mgr.process(AppMsg::InitHciManager).unwrap();
+
+    // Error is returned, the manager has still sent the next command and
+    // registered the connection
+    let error = Err(HciError::Unknown("Failing app".to_string()));
+    assert_eq!(
+        mgr.process(AppMsg::Recv(command_complete(OpCode(0x0001, 0x03)))),
+        error
+    );
+    assert_eq!(
+        mgr.snapshot().commands.in_flight,
+        Some("0x200A".to_string())
+    );
+    assert_eq!(mgr.process(AppMsg::Recv(connection_complete(0x40))), error);
+    assert_eq!(mgr.connections().handles(), vec![ConnectionHandle(0x40)]);
+}
+
+fn command_status(opcode: OpCode) -> H4Packet {
+    H4Packet::Event(HciEvent::CommandStatus(EvtCommandStatus {
+        status: HciStatus::Success,
+        num_hci_command_packets: 1,
+        command_opcode: opcode,
+    }))
+}
+
+fn power_off(handle: u16) -> H4Packet {
+    H4Packet::Command(HciCommand::Disconnect(CmdDisconnect {
+        connection_handle: ConnectionHandle(handle),
+        reason: 0x15,
+    }))
+}
+
+#[test]
+fn test_shutdown() {
+    let mut mgr = HciManager::new().unwrap();
+    mgr.set_max_connections(2);
+    let mut recv = |packet: H4Packet| mgr.process(AppMsg::Recv(packet)).unwrap();
+    recv(connection_complete(0x40));
+    recv(command_complete(OpCode(0x000A, 0x08)));
+    recv(encryption_change(0x40));
+    let report = |value: Vec<u8>| AttHandleValueNotification {
+        handle: 0x001A,
+        value,
+    };
+    mgr.process(AppMsg::HidReport(
+        ConnectionHandle(0x40),
+        report(vec![0, 0, 4, 0, 0, 0, 0, 0]),
+    ))
+    .unwrap();
+
+    // Keys are released before anything else
+    assert_eq!(
+        sent(mgr.process(AppMsg::Shutdown).unwrap()),
+        vec![
+            acl(
+                0x40,
+                L2CapMessage::Att(AttPdu::HandleValueNotification(report(vec![0; 8])))
+            ),
+            H4Packet::Command(HciCommand::LeSetAdvertisingEnable(false)),
+        ]
+    );
+    assert_eq!(mgr.process(AppMsg::Shutdown).unwrap(), vec![]);
+
+    let mut recv = |packet: H4Packet| mgr.process(AppMsg::Recv(packet)).unwrap();
+    assert_eq!(
+        sent(recv(command_complete(OpCode(0x000A, 0x08)))),
+        vec![power_off(0x40)]
+    );
+    assert_eq!(recv(command_status(OpCode(0x0006, 0x01))), vec![]);
+
+    // Controller is reset once the host has disconnected
+    assert_eq!(
+        sent(recv(disconnect_complete(0x40))),
+        vec![H4Packet::Command(HciCommand::Reset)]
+    );
+    assert_eq!(
+        recv(command_complete(OpCode(0x0003, 0x03))),
+        vec![AppMsg::ShutdownComplete]
+    );
+    assert!(mgr.connections().is_empty());
+}
+
+#[test]
+fn test_shutdown_timeout() {
+    let clock = VirtualClock::new();
+    let mut mgr = HciManager::new().unwrap();
+    mgr.set_clock(Box::new(clock.clone()));
+    mgr.process(AppMsg::Recv(connection_complete(0x40)))
+        .unwrap();
+
+    assert_eq!(
+        sent(mgr.process(AppMsg::Shutdown).unwrap()),
+        vec![power_off(0x40)]
+    );
+    mgr.process(AppMsg::Recv(command_status(OpCode(0x0006, 0x01))))
+        .unwrap();
+    assert_eq!(mgr.next_deadline(), Some(Duration::from_secs(2)));
+
+    // Host doesn't answer the disconnect
+    clock.advance(Duration::from_secs(2));
+    assert_eq!(
+        mgr.process(AppMsg::Tick).unwrap(),
+        vec![
+            AppMsg::ConnectionsLost(vec![ConnectionHandle(0x40)]),
+            AppMsg::Send(H4Packet::Command(HciCommand::Reset)),
+        ]
+    );
+    assert!(mgr.connections().is_empty());
+
+    // Nor does the controller answer the reset
+    let mut tick = || {
+        clock.advance(Duration::from_secs(2));
+        mgr.process(AppMsg::Tick).unwrap()
+    };
+    assert_eq!(sent(tick()), vec![H4Packet::Command(HciCommand::Reset)]);
+    assert_eq!(sent(tick()), vec![H4Packet::Command(HciCommand::Reset)]);
+    assert_eq!(tick(), vec![AppMsg::ShutdownComplete]);
+}
+
+#[test]
+fn test_snapshot() {
+    let clock = VirtualClock::new();
+    let mut mgr = HciManager::new().unwrap();
+    mgr.set_clock(Box::new(clock.clone()));
+    mgr.set_max_connections(2);
+    mgr.add_processor(Box::new(KeyCounter(0)));
+    let mut recv = |packet: H4Packet| mgr.process(AppMsg::Recv(packet)).unwrap();
+    recv(connection_complete(0x40));
+    let pairing_request = Packet::from_slice(&[0x01, 0x03, 0x00, 0x2d, 0x10, 0x0e, 0x0f])
+        .unpack::<SmpPdu>()
+        .unwrap();
+    recv(acl(0x40, L2CapMessage::Smp(pairing_request)));
+    recv(H4Packet::Event(HciEvent::NumberOfCompletedPackets(
+        EvtNumberOfCompletedPackets {
+            handles: vec![CompletedPackets {
+                connection_handle: ConnectionHandle(0x40),
+                num_completed_packets: 1,
+            }],
+        },
+    )));
+    clock.advance(Duration::from_millis(500));
+
+    let snapshot = mgr.snapshot();
+    assert_eq!(snapshot.now_ms, 500);
+    assert_eq!(
+        snapshot.connections,
+        vec![ConnectionSnapshot {
+            handle: 0x40,
+            peer_address: "50:C2:E8:D6:0E:26".to_string(),
+            peer_address_type: "Public".to_string(),
+            role: "Peripheral".to_string(),
+            interval: 48,
+            latency: 0,
+            supervision_timeout: 960,
+            mtu: DEFAULT_ATT_MTU,
+            security_level: SecurityLevel::NoSecurity,
+            pairing: PairingStage::FeatureExchange,
+            // MTU request and Pairing Response, one of them completed
+            acl_in_flight: 1,
+        }]
+    );
+
+    // Advertising is re-enabled after the connection, the controller hasn't
+    // answered any command yet
+    assert_eq!(snapshot.commands.credits, 0);
+    assert_eq!(snapshot.commands.in_flight, Some("0x200A".to_string()));
+    assert_eq!(snapshot.commands.pending.len(), 1);
+    assert_eq!(snapshot.commands.pending[0].issuer, None);
+    assert_eq!(snapshot.commands.pending[0].deadline_ms, 2000);
+    assert!(snapshot.advertising);
+
+    let names: Vec<&str> = snapshot
+        .processors
+        .iter()
+        .map(|p| p.name.rsplit("::").next().unwrap())
+        .collect();
+    assert_eq!(names, vec!["KeyCounter", "AttHandler", "PairingHandler"]);
+    assert_eq!(snapshot.processors[0].interest.connection, None);
+    assert_eq!(snapshot.processors[2].connection, Some(0x40));
+    assert_eq!(
+        snapshot.processors[2].interest.channels,
+        Some(vec![CID_SMP])
+    );
+}
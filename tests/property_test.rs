@@ -0,0 +1,84 @@
+//! Round trips of generated values and decoding of random bytes, for every
+//! message type
+
+use bt_only_headers::generate::Generate;
+use bt_only_headers::messages::*;
+use bt_only_headers::packer::*;
+use bt_only_headers::random::{Random, SeededRandom};
+
+mod common;
+use common::{corrupted_packet, for_each_message};
+
+/// Values generated of every type
+const CASES: usize = 500;
+
+/// Unpacks what the value packs to
+fn round_trip<T: Generate + FromToPacket + std::fmt::Debug + PartialEq>(random: &mut SeededRandom) {
+    for _ in 0..CASES {
+        let value = T::generate(random);
+        let bytes = value.to_bytes();
+        assert_eq!(
+            Packet::from_slice(&bytes).unpack::<T>(),
+            Ok(value),
+            "bytes {:02x?}",
+            bytes
+        );
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let mut random = SeededRandom::new(0x5EED);
+    for_each_message!(round_trip(&mut random));
+}
+
+/// Decoding doesn't panic, whatever the bytes
+fn decode<T: FromToPacket>(bytes: &[u8]) {
+    let _ = Packet::from_slice(bytes).unpack::<T>();
+}
+
+fn decode_all(bytes: &[u8]) {
+    decode::<H4Packet>(bytes);
+    decode::<HciEvent>(bytes);
+    decode::<HciCommand>(bytes);
+    decode::<L2CapMessage>(bytes);
+    decode::<AttPdu>(bytes);
+    decode::<SmpPdu>(bytes);
+}
+
+#[test]
+fn test_decode_random_bytes() {
+    let mut random = SeededRandom::new(0xB17E5);
+    for _ in 0..CASES * 10 {
+        let mut bytes = vec![0; random.below(64)];
+        random.fill(&mut bytes);
+        decode_all(&bytes);
+    }
+}
+
+/// Corrupted packets reach deeper than random bytes do
+#[test]
+fn test_decode_corrupted_packets() {
+    let mut random = SeededRandom::new(0xC0DE);
+    for _ in 0..CASES * 10 {
+        decode_all(&corrupted_packet(&mut random));
+    }
+}
+
+/// Elements of an `element_length` field differ, e.g. in their values
+#[test]
+fn test_element_length_distinct() {
+    let mut random = SeededRandom::new(0xE1E);
+    let mut distinct = 0;
+    for _ in 0..CASES {
+        let response = AttReadByTypeResponse::generate(&mut random);
+        let first = &response.values[0];
+        for value in &response.values[1..] {
+            assert_eq!(value.value.len(), first.value.len());
+            if value != first {
+                distinct += 1;
+            }
+        }
+    }
+    assert!(distinct > CASES / 2, "{} distinct elements", distinct);
+}
@@ -0,0 +1,148 @@
+//! `Reflected` packs and unpacks the same as the derived `FromToPacket`, for
+//! the HCI dump fixtures and generated values of every message type
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::fmt::Debug;
+
+use bt_only_headers::generate::Generate;
+use bt_only_headers::messages::*;
+use bt_only_headers::packer::*;
+use bt_only_headers::random::SeededRandom;
+use bt_only_headers::reflect::Reflected;
+use facet::Facet;
+
+mod common;
+use common::{all_fixtures, corrupt_length, corrupted_packet, for_each_message};
+
+/// Values generated of every type
+const CASES: usize = 200;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATED: Cell<isize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.with(|allocated| allocated.set(allocated.get() + layout.size() as isize));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.with(|allocated| allocated.set(allocated.get() - layout.size() as isize));
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Bytes allocated by `f` on this thread and not freed again
+fn leaked(f: impl FnOnce()) -> isize {
+    let before = ALLOCATED.with(Cell::get);
+    f();
+    ALLOCATED.with(Cell::get) - before
+}
+
+fn pack<T: FromToPacket>(value: &T) -> Result<Vec<u8>, PacketError> {
+    let mut packet = Packet::new();
+    value.to_packet(&mut packet)?;
+    Ok(packet.into_bytes())
+}
+
+/// Unpacks the bytes both ways, the results must be the same
+fn assert_same_unpack<T>(bytes: &[u8])
+where
+    T: FromToPacket + Facet + Debug + Clone + PartialEq + 'static,
+{
+    let derived = Packet::from_slice(bytes).unpack::<T>();
+    let reflected = Packet::from_slice(bytes)
+        .unpack::<Reflected<T>>()
+        .map(|reflected| reflected.0);
+    assert_eq!(reflected, derived, "bytes {:02x?}", bytes);
+}
+
+/// Packs both ways, then unpacks what was packed
+fn assert_same<T>(value: &T)
+where
+    T: FromToPacket + Facet + Debug + Clone + PartialEq + 'static,
+{
+    let bytes = pack(value);
+    assert_eq!(pack(&Reflected(value.clone())), bytes, "value {:?}", value);
+    if let Ok(bytes) = bytes {
+        assert_same_unpack::<T>(&bytes);
+    }
+}
+
+#[test]
+fn test_fixtures() {
+    let fixtures = all_fixtures();
+    assert!(!fixtures.is_empty());
+    for bytes in fixtures {
+        assert_same_unpack::<H4Packet>(&bytes);
+        let packet = Packet::from_slice(&bytes).unpack::<H4Packet>().unwrap();
+        assert_eq!(pack(&Reflected(packet)), Ok(bytes));
+    }
+}
+
+fn generated<T>(random: &mut SeededRandom)
+where
+    T: Generate + FromToPacket + Facet + Debug + Clone + PartialEq + 'static,
+{
+    for _ in 0..CASES {
+        assert_same(&T::generate(random));
+    }
+}
+
+#[test]
+fn test_generated() {
+    let mut random = SeededRandom::new(0xFACE7);
+    for_each_message!(generated(&mut random));
+}
+
+/// Catch-all variants with the id of another variant fail the same way
+#[test]
+fn test_reserved_with_assigned_value() {
+    assert_same(&Role::Reserved(0x01));
+    assert_same(&HciStatus::Failure(0x00));
+    assert_same(&AttPdu::Unknown(0x52, vec![0x01]));
+    assert_eq!(
+        pack(&Reflected(Role::Reserved(0x01))),
+        Err(PacketError::InvalidBytes)
+    );
+}
+
+/// Invalid bytes fail the same way
+#[test]
+fn test_corrupted_packets() {
+    let mut random = SeededRandom::new(0xBAD);
+    for _ in 0..CASES * 10 {
+        let bytes = corrupted_packet(&mut random);
+        assert_same_unpack::<H4Packet>(&bytes);
+        assert_same_unpack::<L2CapMessage>(&bytes);
+        assert_same_unpack::<AttPdu>(&bytes);
+        assert_same_unpack::<SmpPdu>(&bytes);
+    }
+}
+
+/// A length one too long or short fails after the value within it was
+/// unpacked, which must then be dropped again
+///
+/// The paths of the fields are kept once, so the bytes are unpacked before the
+/// leaks are counted. Also run it with `RUSTFLAGS=-Zsanitizer=address`, for
+/// the drops of the values.
+#[test]
+fn test_corrupted_lengths() {
+    let mut random = SeededRandom::new(0x1E47);
+    for _ in 0..CASES * 10 {
+        let mut bytes = H4Packet::generate(&mut random).to_bytes();
+        corrupt_length(&mut random, &mut bytes);
+        assert_same_unpack::<H4Packet>(&bytes);
+        let leaked = leaked(|| {
+            let _ = Packet::from_slice(&bytes).unpack::<Reflected<H4Packet>>();
+        });
+        assert_eq!(leaked, 0, "bytes {:02x?}", bytes);
+    }
+}
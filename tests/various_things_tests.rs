@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 use bt_only_headers::messages::HciAcl;
 use bt_only_headers::messages::LeConnectionComplete;
 use bt_only_headers::messages::SmpPairingReqRes;
@@ -74,6 +76,64 @@ fn deserialize_role() {
     assert_eq!(handle, Role::Central);
 }
 
+#[test]
+fn deserialize_role_reserved() {
+    let mut packet = Packet::from_slice(&[0x07]);
+    let role = Role::from_packet(&mut packet).unwrap();
+    assert_eq!(role, Role::Reserved(0x07));
+    assert_eq!(role.to_bytes(), vec![0x07]);
+}
+
+#[test]
+fn test_reserved_with_assigned_value() {
+    // Would unpack as `Role::Peripheral`, `IOCapability::NoInputNoOutput`, ...
+    fn pack(value: &impl FromToPacket) -> Result<Vec<u8>, PacketError> {
+        Ok(Packet::new().pack(value)?.get_bytes().to_vec())
+    }
+    assert_eq!(pack(&Role::Reserved(0x01)), Err(PacketError::InvalidBytes));
+    assert_eq!(
+        pack(&IOCapability::Reserved(0x03)),
+        Err(PacketError::InvalidBytes)
+    );
+    assert_eq!(
+        pack(&HciStatus::Failure(0x00)),
+        Err(PacketError::InvalidBytes)
+    );
+    assert_eq!(
+        pack(&AttPdu::Unknown(0x52, vec![0x01])),
+        Err(PacketError::InvalidBytes)
+    );
+    assert_eq!(pack(&Role::Reserved(0x07)), Ok(vec![0x07]));
+}
+
+#[test]
+fn test_pairing_request_reserved_values() {
+    // IO capability 0x09 and OOB data flag 0x02 are reserved
+    const DATA: [u8; 7] = [0x01, 0x09, 0x02, 0x2d, 0x10, 0x0e, 0x0f];
+    let mut packet = Packet::from_slice(&DATA);
+    let msg = packet.unpack::<SmpPdu>().unwrap();
+    match &msg {
+        SmpPdu::PairingRequest(req) => {
+            assert_eq!(req.io_capability, IOCapability::Reserved(0x09));
+            assert_eq!(req.oob_data_flag, OOBDataFlag::Reserved(0x02));
+        }
+        _ => panic!("Expected PairingRequest"),
+    }
+
+    // Serializes back to the original bytes
+    assert_eq!(msg.to_bytes(), DATA.to_vec());
+}
+
+#[test]
+fn test_pairing_failed_reserved_reason() {
+    let mut packet = Packet::from_slice(&[0x05, 0x20]);
+    let msg = packet.unpack::<SmpPdu>().unwrap();
+    assert_eq!(
+        msg,
+        SmpPdu::PairingFailed(SmpPairingFailure::Reserved(0x20))
+    );
+}
+
 #[test]
 fn test_hci_command_reset() {
     let mut packet = Packet::from_slice(&[0x03, 0x0C, 0x00]);
@@ -153,6 +213,108 @@ fn test_event() {
     assert_eq!(new_packet.get_bytes(), DATA.to_vec());
 }
 
+#[test]
+fn test_command_complete_nop() {
+    // NOP has no return parameters, not even the status
+    const NOP: [u8; 5] = [0x0E, 0x03, 0x01, 0x00, 0x00];
+    let expected = HciEvent::CommandComplete(EvtCommandComplete {
+        num_hci_command_packets: 1,
+        command_opcode: OpCode(0x0000, 0x00),
+        status: None,
+        data: vec![],
+    });
+    let mut packet = Packet::from_slice(&NOP);
+    assert_eq!(HciEvent::from_packet(&mut packet), Ok(expected.clone()));
+    assert_eq!(expected.to_bytes(), NOP.to_vec());
+
+    const RESET: [u8; 6] = [0x0E, 0x04, 0x01, 0x03, 0x0C, 0x00];
+    let expected = HciEvent::CommandComplete(EvtCommandComplete {
+        num_hci_command_packets: 1,
+        command_opcode: OpCode(0x0003, 0x03),
+        status: Some(HciStatus::Success),
+        data: vec![],
+    });
+    let mut packet = Packet::from_slice(&RESET);
+    assert_eq!(HciEvent::from_packet(&mut packet), Ok(expected.clone()));
+    assert_eq!(expected.to_bytes(), RESET.to_vec());
+
+    // Status is required for other commands
+    let missing = HciEvent::CommandComplete(EvtCommandComplete {
+        num_hci_command_packets: 1,
+        command_opcode: OpCode(0x0003, 0x03),
+        status: None,
+        data: vec![],
+    });
+    let mut packet = Packet::new();
+    assert_eq!(packet.pack(&missing), Err(PacketError::InvalidBytes));
+}
+
+#[test]
+fn test_number_of_completed_packets() {
+    const DATA: [u8; 11] = [
+        0x13, 0x09, 0x02, 0x40, 0x00, 0x02, 0x00, 0x41, 0x00, 0x01, 0x00,
+    ];
+    let expected = HciEvent::NumberOfCompletedPackets(EvtNumberOfCompletedPackets {
+        handles: vec![
+            CompletedPackets {
+                connection_handle: ConnectionHandle(0x0040),
+                num_completed_packets: 2,
+            },
+            CompletedPackets {
+                connection_handle: ConnectionHandle(0x0041),
+                num_completed_packets: 1,
+            },
+        ],
+    });
+    let mut packet = Packet::from_slice(&DATA);
+    assert_eq!(HciEvent::from_packet(&mut packet), Ok(expected.clone()));
+
+    let mut new_packet = Packet::new();
+    new_packet.pack(&expected).unwrap();
+    assert_eq!(new_packet.get_bytes(), DATA.to_vec());
+
+    // Fewer handles than the count
+    let mut packet = Packet::from_slice(&[0x13, 0x05, 0x02, 0x40, 0x00, 0x02, 0x00]);
+    assert_eq!(
+        HciEvent::from_packet(&mut packet).map_err(|e| e.kind().clone()),
+        Err(PacketError::NotEnoughBytes)
+    );
+}
+
+#[test]
+fn test_error_path() {
+    // Pairing Request cut after the SMP code
+    const DATA: [u8; 10] = [0x02, 0x40, 0x00, 0x05, 0x00, 0x01, 0x00, 0x06, 0x00, 0x01];
+    let error = Packet::from_slice(&DATA).unpack::<H4Packet>().unwrap_err();
+    assert_eq!(
+        error,
+        PacketError::Field(Box::new(FieldError {
+            error: PacketError::NotEnoughBytes,
+            offset: 10,
+            bit_offset: 0,
+            path: [
+                "H4Packet::Acl",
+                "HciAcl.msg",
+                "L2CapMessage::Smp",
+                "SmpPdu::PairingRequest",
+                "SmpPairingReqRes.io_capability",
+                "IOCapability::Reserved",
+            ]
+            .map(Cow::Borrowed)
+            .to_vec(),
+            bytes: vec![],
+        }))
+    );
+
+    let error = Packet::from_slice(&[0x02, 0x40])
+        .unpack::<H4Packet>()
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Not enough bytes at offset 1 in H4Packet::Acl > HciAcl.connection_handle > ConnectionHandle: 40"
+    );
+}
+
 #[test]
 fn test_auth_req() {
     let m = AuthenticationRequirements {